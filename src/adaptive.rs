@@ -0,0 +1,684 @@
+//! Adaptive strategies, where each query may depend on the feedback to the earlier ones, for
+//! identification puzzles in general ([`Puzzle`]). The battery puzzle is one of them
+//! ([`BatteryPuzzle`]), and so are the variant with a battery tester
+//! ([`CountingBatteryPuzzle`]), the counterfeit coin puzzle ([`CoinPuzzle`]) and group testing
+//! ([`GroupTesting`]). [`adaptive`] finds decision trees with the fewest queries in the worst
+//! case and [`adaptive_expected`] ones with the fewest on average.
+//!
+//! ```
+//! use batteries::adaptive::{adaptive, BatteryPuzzle};
+//! use batteries::Params;
+//!
+//! let (tries, _, _) = adaptive(&BatteryPuzzle::new(Params::default()), 7).unwrap();
+//! assert_eq!(tries, 7);
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::group_testing::{
+    canonical_form, class_selections, interchangeable_classes, Goal, GroupTesting,
+};
+use crate::{binomial, rank, BitSet, CombinationIter, EliminationTable, Params};
+
+/// An identification puzzle: some hidden state out of [`Puzzle::states`] is the real one, and each
+/// query gets a feedback that depends on it. The battery puzzle is one instance (the hidden state
+/// is which batteries are functional, a query is a try and the feedback whether the toy turns
+/// on).
+pub trait Puzzle {
+    type State: fmt::Display;
+    type Query: fmt::Display;
+    type Feedback: fmt::Display + Eq;
+
+    /// Every hidden state that is possible at the start.
+    fn states(&self) -> Vec<Self::State>;
+    /// The queries worth asking when only `states` are still possible. Returning just one of
+    /// each group of queries that are equivalent by symmetry keeps the search small.
+    fn queries(&self, states: &[&Self::State]) -> Vec<Self::Query>;
+    fn feedback(&self, query: &Self::Query, state: &Self::State) -> Self::Feedback;
+    /// Whether the puzzle is over once `feedback` was given and `states` are still possible.
+    fn solved(&self, feedback: &Self::Feedback, states: &[&Self::State]) -> bool;
+    /// At least this many queries are needed when `states` are still possible; a tighter bound
+    /// lets the search give up on hopeless branches sooner.
+    fn lower_bound(&self, _states: &[&Self::State]) -> usize {
+        1
+    }
+    /// The same for all sets of `states` that are relabelings of each other under a symmetry of
+    /// the puzzle, e.g. the [`canonical_form`] of the sets of functional batteries, so that the
+    /// searches remember what they found out about a set of states for all its relabelings. By
+    /// default they remember it for just the set itself.
+    fn symmetry_key(&self, _states: &[&Self::State]) -> Option<Vec<u64>> {
+        None
+    }
+}
+
+// What the searches remember their findings by: the puzzle's symmetry key of a set of states,
+// or else the indices of the states themselves.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Memo {
+    Symmetric(Vec<u64>),
+    States(Vec<usize>),
+}
+
+impl Memo {
+    fn of<P: Puzzle>(puzzle: &P, states: &[&P::State], remaining: &[usize]) -> Self {
+        match puzzle.symmetry_key(states) {
+            Some(key) => Memo::Symmetric(key),
+            None => Memo::States(remaining.to_vec()),
+        }
+    }
+}
+
+/// What to do next; the states of a leaf are indices into [`Puzzle::states`].
+pub enum DecisionTree<P: Puzzle> {
+    Done(Vec<usize>),
+    Ask {
+        query: P::Query,
+        branches: Vec<(P::Feedback, DecisionTree<P>)>,
+    },
+}
+
+// Depth-first search for a decision tree, remembering for each set of states that could not be
+// dealt with the most queries it was tried with.
+struct AdaptiveSearch<'a, P: Puzzle> {
+    puzzle: &'a P,
+    states: Vec<P::State>,
+    failed: BTreeMap<Memo, usize>,
+}
+
+impl<P: Puzzle> AdaptiveSearch<'_, P> {
+    fn solve(&mut self, remaining: &[usize], queries: usize) -> Option<DecisionTree<P>> {
+        if queries == 0 {
+            return None;
+        }
+        let (memo, candidates) = {
+            let states: Vec<_> = remaining.iter().map(|&i| &self.states[i]).collect();
+            if self.puzzle.lower_bound(&states) > queries {
+                return None;
+            }
+            let memo = Memo::of(self.puzzle, &states, remaining);
+            if self.failed.get(&memo).is_some_and(|&q| q >= queries) {
+                return None;
+            }
+            (memo, self.puzzle.queries(&states))
+        };
+        'queries: for query in candidates {
+            let mut branches = vec![];
+            for (feedback, part, solved) in split(self.puzzle, &self.states, &query, remaining) {
+                if solved {
+                    branches.push((feedback, DecisionTree::Done(part)));
+                } else if part.len() == remaining.len() {
+                    // The query tells nothing, so asking it would only waste a query.
+                    continue 'queries;
+                } else {
+                    match self.solve(&part, queries - 1) {
+                        Some(tree) => branches.push((feedback, tree)),
+                        None => continue 'queries,
+                    }
+                }
+            }
+            return Some(DecisionTree::Ask { query, branches });
+        }
+        self.failed.insert(memo, queries);
+        None
+    }
+}
+
+// Splits `remaining` by the feedback their states give to `query`, telling for each part whether
+// the puzzle is over once that feedback is given.
+fn split<P: Puzzle>(
+    puzzle: &P,
+    states: &[P::State],
+    query: &P::Query,
+    remaining: &[usize],
+) -> Vec<(P::Feedback, Vec<usize>, bool)> {
+    let mut parts: Vec<(P::Feedback, Vec<usize>)> = vec![];
+    for &i in remaining {
+        let feedback = puzzle.feedback(query, &states[i]);
+        match parts.iter_mut().find(|(f, _)| *f == feedback) {
+            Some((_, part)) => part.push(i),
+            None => parts.push((feedback, vec![i])),
+        }
+    }
+    parts
+        .into_iter()
+        .map(|(feedback, part)| {
+            let solved = {
+                let part_states: Vec<_> = part.iter().map(|&i| &states[i]).collect();
+                puzzle.solved(&feedback, &part_states)
+            };
+            (feedback, part, solved)
+        })
+        .collect()
+}
+
+// Search for a decision tree with the fewest queries on average over the states, all equally
+// likely, among the ones that need at most a given number of queries in the worst case. The cost
+// of a tree is the total number of queries over all states: every state left at a query pays for
+// it. The best cost for each set of states and number of queries left is remembered.
+struct ExpectedSearch<'a, P: Puzzle> {
+    puzzle: &'a P,
+    states: Vec<P::State>,
+    best: BTreeMap<(Memo, usize), Option<u64>>,
+}
+
+impl<P: Puzzle> ExpectedSearch<'_, P> {
+    fn candidates(&self, remaining: &[usize], queries: usize) -> Vec<P::Query> {
+        let states: Vec<_> = remaining.iter().map(|&i| &self.states[i]).collect();
+        if queries == 0 || self.puzzle.lower_bound(&states) > queries {
+            return vec![];
+        }
+        self.puzzle.queries(&states)
+    }
+
+    fn cost(&mut self, remaining: &[usize], queries: usize) -> Option<u64> {
+        let memo = {
+            let states: Vec<_> = remaining.iter().map(|&i| &self.states[i]).collect();
+            (Memo::of(self.puzzle, &states, remaining), queries)
+        };
+        if let Some(&cost) = self.best.get(&memo) {
+            return cost;
+        }
+        let mut best = None;
+        for query in self.candidates(remaining, queries) {
+            if let Some(cost) = self.query_cost(&query, remaining, queries) {
+                best = Some(best.map_or(cost, |b: u64| b.min(cost)));
+            }
+        }
+        self.best.insert(memo, best);
+        best
+    }
+
+    fn query_cost(&mut self, query: &P::Query, remaining: &[usize], queries: usize) -> Option<u64> {
+        let mut cost = remaining.len() as u64;
+        for (_, part, solved) in split(self.puzzle, &self.states, query, remaining) {
+            if solved {
+                continue;
+            }
+            if part.len() == remaining.len() {
+                return None;
+            }
+            cost += self.cost(&part, queries - 1)?;
+        }
+        Some(cost)
+    }
+
+    // Rebuilds a best tree from the remembered costs.
+    fn tree(&mut self, remaining: &[usize], queries: usize) -> Option<DecisionTree<P>> {
+        let best = self.cost(remaining, queries)?;
+        let query = self
+            .candidates(remaining, queries)
+            .into_iter()
+            .find(|query| self.query_cost(query, remaining, queries) == Some(best))?;
+        let mut branches = vec![];
+        for (feedback, part, solved) in split(self.puzzle, &self.states, &query, remaining) {
+            let branch = match solved {
+                true => DecisionTree::Done(part),
+                false => self.tree(&part, queries - 1)?,
+            };
+            branches.push((feedback, branch));
+        }
+        Some(DecisionTree::Ask { query, branches })
+    }
+}
+
+/// Finds a decision tree with the fewest queries on average among the ones that need at most
+/// `max_queries` in the worst case, if there is one. Returns the total number of queries over
+/// all states, the states and the tree.
+pub fn adaptive_expected<P: Puzzle>(
+    puzzle: &P,
+    max_queries: usize,
+) -> Option<(u64, Vec<P::State>, DecisionTree<P>)> {
+    let mut search = ExpectedSearch {
+        puzzle,
+        states: puzzle.states(),
+        best: BTreeMap::new(),
+    };
+    let all: Vec<usize> = (0..search.states.len()).collect();
+    let tree = search.tree(&all, max_queries)?;
+    let cost = search.cost(&all, max_queries)?;
+    Some((cost, search.states, tree))
+}
+
+/// Finds a decision tree that deals with every state in the fewest queries in the worst case, if
+/// at most `max_queries` are enough. Returns the number of queries, the states and the tree.
+pub fn adaptive<P: Puzzle>(
+    puzzle: &P,
+    max_queries: usize,
+) -> Option<(usize, Vec<P::State>, DecisionTree<P>)> {
+    let mut search = AdaptiveSearch {
+        puzzle,
+        states: puzzle.states(),
+        failed: BTreeMap::new(),
+    };
+    let all: Vec<usize> = (0..search.states.len()).collect();
+    (1..=max_queries).find_map(|queries| {
+        let tree = search.solve(&all, queries)?;
+        Some((queries, core::mem::take(&mut search.states), tree))
+    })
+}
+
+/// A set of batteries or items: a universe, a try or a pool, together with its [`rank`] among
+/// the sets of its size. It is shown as just the elements, e.g. `[0, 1]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Numbered {
+    pub index: usize,
+    pub set: BitSet,
+}
+
+impl Numbered {
+    pub fn new(set: BitSet) -> Self {
+        Numbered {
+            index: rank(set) as usize,
+            set,
+        }
+    }
+}
+
+impl fmt::Display for Numbered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (i, element) in self.set.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{element}")?;
+        }
+        f.write_str("]")
+    }
+}
+
+// The sets of `size` of the elements 0..n, one for each way to pick them from the classes of
+// elements that are interchangeable in `states`.
+fn selections(
+    n: u64,
+    states: &[&Numbered],
+    sizes: core::ops::RangeInclusive<u64>,
+) -> Vec<Numbered> {
+    let masks: Vec<u64> = states.iter().map(|s| s.set.0).collect();
+    let classes = interchangeable_classes(n as usize, &masks);
+    let sizes = *sizes.start() as usize..=*sizes.end() as usize;
+    class_selections(&classes, sizes)
+        .into_iter()
+        .map(|selection| Numbered::new(selection.into_iter().collect()))
+        .collect()
+}
+
+// Relabeling the elements of a puzzle whose queries are all sets of some sizes maps its states
+// to states that are just as hard to tell apart.
+fn symmetry_key(n: u64, states: &[&Numbered]) -> Vec<u64> {
+    let masks: Vec<u64> = states.iter().map(|s| s.set.0).collect();
+    canonical_form(n as usize, &masks)
+}
+
+/// What the toy does when it is tried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Toy {
+    Off,
+    On,
+}
+
+impl fmt::Display for Toy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Toy::Off => "Off",
+            Toy::On => "On",
+        })
+    }
+}
+
+/// The battery puzzle itself: some `good` of the `total` batteries work and the toy needs `slots`
+/// functional batteries. The puzzle is over once the toy turns on.
+pub struct BatteryPuzzle {
+    params: Params,
+    table: EliminationTable,
+}
+
+impl BatteryPuzzle {
+    pub fn new(params: Params) -> Self {
+        BatteryPuzzle {
+            params,
+            table: EliminationTable::new(params),
+        }
+    }
+}
+
+impl Puzzle for BatteryPuzzle {
+    type State = Numbered;
+    type Query = Numbered;
+    type Feedback = Toy;
+
+    fn states(&self) -> Vec<Numbered> {
+        let universes = self.table.universes().iter().enumerate();
+        universes
+            .map(|(index, &set)| Numbered { index, set })
+            .collect()
+    }
+
+    fn queries(&self, states: &[&Numbered]) -> Vec<Numbered> {
+        let Params { total, slots, .. } = self.params;
+        selections(total, states, slots..=slots)
+    }
+
+    fn feedback(&self, query: &Numbered, state: &Numbered) -> Toy {
+        if self.table.turns_on(query.index, state.index) {
+            Toy::On
+        } else {
+            Toy::Off
+        }
+    }
+
+    fn solved(&self, feedback: &Toy, _: &[&Numbered]) -> bool {
+        *feedback == Toy::On
+    }
+
+    // Every try but the last has to fail, and a try rules out at most the universes in which
+    // its batteries are functional.
+    fn lower_bound(&self, states: &[&Numbered]) -> usize {
+        let Params {
+            total, good, slots, ..
+        } = self.params;
+        let ruled_out = binomial(total - slots, good - slots) as usize;
+        states.len().div_ceil(ruled_out)
+    }
+
+    fn symmetry_key(&self, states: &[&Numbered]) -> Option<Vec<u64>> {
+        Some(symmetry_key(self.params.total, states))
+    }
+}
+
+/// How many of the batteries of a try are functional, as a battery tester tells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Functional(pub u64);
+
+impl fmt::Display for Functional {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} functional", self.0)
+    }
+}
+
+/// The battery puzzle with a tester instead of the toy: each try tells how many of its batteries
+/// are functional, and the puzzle is over once all of them are, which is when the toy would turn
+/// on.
+pub struct CountingBatteryPuzzle {
+    pub params: Params,
+}
+
+impl Puzzle for CountingBatteryPuzzle {
+    type State = Numbered;
+    type Query = Numbered;
+    type Feedback = Functional;
+
+    fn states(&self) -> Vec<Numbered> {
+        CombinationIter::new(self.params.total, self.params.good)
+            .enumerate()
+            .map(|(index, set)| Numbered { index, set })
+            .collect()
+    }
+
+    fn queries(&self, states: &[&Numbered]) -> Vec<Numbered> {
+        let Params { total, slots, .. } = self.params;
+        selections(total, states, slots..=slots)
+    }
+
+    fn feedback(&self, batteries: &Numbered, universe: &Numbered) -> Functional {
+        Functional(u64::from((batteries.set & universe.set).len()))
+    }
+
+    fn solved(&self, feedback: &Functional, _: &[&Numbered]) -> bool {
+        feedback.0 == self.params.slots
+    }
+
+    // A try turns on the toy in at most `ruled_out` of the universes, and a tree of `q` tries
+    // has at most 1 + slots + ... + slots^(q - 1) of them, one after each way the earlier tries
+    // can fall short.
+    fn lower_bound(&self, states: &[&Numbered]) -> usize {
+        let Params {
+            total, good, slots, ..
+        } = self.params;
+        let ruled_out = binomial(total - slots, good - slots) as usize;
+        let (mut tries, mut nodes, mut level) = (1, 1usize, 1usize);
+        while nodes.saturating_mul(ruled_out) < states.len() {
+            level = level.saturating_mul(slots as usize);
+            nodes = nodes.saturating_add(level);
+            tries += 1;
+        }
+        tries
+    }
+
+    fn symmetry_key(&self, states: &[&Numbered]) -> Option<Vec<u64>> {
+        Some(symmetry_key(self.params.total, states))
+    }
+}
+
+/// Which coin is counterfeit and how it differs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counterfeit {
+    Heavier(usize),
+    Lighter(usize),
+}
+
+impl fmt::Display for Counterfeit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Counterfeit::Heavier(coin) => write!(f, "coin {coin} is heavier"),
+            Counterfeit::Lighter(coin) => write!(f, "coin {coin} is lighter"),
+        }
+    }
+}
+
+/// The coins on each pan of the balance scale, shown like `[0, 1] vs [2, 3]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Weighing {
+    pub left: Vec<usize>,
+    pub right: Vec<usize>,
+}
+
+impl fmt::Display for Weighing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} vs {:?}", self.left, self.right)
+    }
+}
+
+/// How a weighing comes out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Balance {
+    LeftDown,
+    Even,
+    RightDown,
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Balance::LeftDown => "left pan down",
+            Balance::Even => "even",
+            Balance::RightDown => "right pan down",
+        })
+    }
+}
+
+/// The counterfeit coin puzzle: one of `coins` is heavier or lighter than the others, and a
+/// balance scale has to find out which one and how it differs.
+pub struct CoinPuzzle {
+    pub coins: usize,
+}
+
+impl Puzzle for CoinPuzzle {
+    type State = Counterfeit;
+    type Query = Weighing;
+    type Feedback = Balance;
+
+    fn states(&self) -> Vec<Counterfeit> {
+        (0..self.coins)
+            .flat_map(|c| [Counterfeit::Heavier(c), Counterfeit::Lighter(c)])
+            .collect()
+    }
+
+    // Coins are interchangeable if they can be heavier, lighter, both or neither alike, so a
+    // weighing only matters up to how many coins of each kind go on each pan.
+    fn queries(&self, states: &[&Counterfeit]) -> Vec<Weighing> {
+        let mut kinds = [vec![], vec![], vec![], vec![]];
+        for coin in 0..self.coins {
+            let heavier = states
+                .iter()
+                .any(|s| matches!(s, Counterfeit::Heavier(c) if *c == coin));
+            let lighter = states
+                .iter()
+                .any(|s| matches!(s, Counterfeit::Lighter(c) if *c == coin));
+            kinds[usize::from(heavier) * 2 + usize::from(lighter)].push(coin);
+        }
+
+        let mut weighings = vec![Weighing {
+            left: vec![],
+            right: vec![],
+        }];
+        for kind in &kinds {
+            weighings = weighings
+                .into_iter()
+                .flat_map(|w| {
+                    (0..=kind.len()).flat_map(move |l| {
+                        let w = Weighing {
+                            left: [&w.left[..], &kind[..l]].concat(),
+                            right: w.right.clone(),
+                        };
+                        (0..=kind.len() - l).map(move |r| Weighing {
+                            left: w.left.clone(),
+                            right: [&w.right[..], &kind[l..l + r]].concat(),
+                        })
+                    })
+                })
+                .collect();
+        }
+        weighings.retain(|w| !w.left.is_empty() && w.left.len() == w.right.len());
+        for w in &mut weighings {
+            w.left.sort_unstable();
+            w.right.sort_unstable();
+        }
+        weighings
+    }
+
+    fn feedback(&self, weighing: &Weighing, state: &Counterfeit) -> Balance {
+        let (coin, heavier) = match *state {
+            Counterfeit::Heavier(c) => (c, true),
+            Counterfeit::Lighter(c) => (c, false),
+        };
+        match (
+            weighing.left.contains(&coin),
+            weighing.right.contains(&coin),
+        ) {
+            (true, _) if heavier => Balance::LeftDown,
+            (true, _) => Balance::RightDown,
+            (_, true) if heavier => Balance::RightDown,
+            (_, true) => Balance::LeftDown,
+            _ => Balance::Even,
+        }
+    }
+
+    fn solved(&self, _: &Balance, states: &[&Counterfeit]) -> bool {
+        states.len() <= 1
+    }
+}
+
+/// Whether a pool has a defective item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestResult {
+    Negative,
+    Positive,
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TestResult::Negative => "negative",
+            TestResult::Positive => "positive",
+        })
+    }
+}
+
+/// The states are the sets of defective items and the queries the pools, both as [`Numbered`]
+/// sets of items.
+impl Puzzle for GroupTesting {
+    type State = Numbered;
+    type Query = Numbered;
+    type Feedback = TestResult;
+
+    fn states(&self) -> Vec<Numbered> {
+        self.candidates().into_iter().map(Numbered::new).collect()
+    }
+
+    fn queries(&self, states: &[&Numbered]) -> Vec<Numbered> {
+        selections(self.items, states, self.pool_sizes.clone())
+    }
+
+    fn feedback(&self, pool: &Numbered, state: &Numbered) -> TestResult {
+        match (pool.set & state.set).is_empty() {
+            true => TestResult::Negative,
+            false => TestResult::Positive,
+        }
+    }
+
+    fn solved(&self, feedback: &TestResult, states: &[&Numbered]) -> bool {
+        match self.goal {
+            Goal::FindGoodPool => *feedback == TestResult::Negative || states.is_empty(),
+            Goal::Identify => states.len() <= 1,
+        }
+    }
+
+    fn symmetry_key(&self, states: &[&Numbered]) -> Option<Vec<u64>> {
+        Some(symmetry_key(self.items, states))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth<P: Puzzle>(tree: &DecisionTree<P>) -> usize {
+        match tree {
+            DecisionTree::Done(_) => 0,
+            DecisionTree::Ask { branches, .. } => {
+                1 + branches.iter().map(|(_, b)| depth(b)).max().unwrap_or(0)
+            }
+        }
+    }
+
+    #[test]
+    fn twelve_coins_take_three_weighings() {
+        let puzzle = CoinPuzzle { coins: 12 };
+        assert!(adaptive(&puzzle, 2).is_none());
+        let (weighings, states, tree) = adaptive(&puzzle, 3).unwrap();
+        assert_eq!((weighings, states.len(), depth(&tree)), (3, 24, 3));
+    }
+
+    #[test]
+    fn group_testing_agrees_with_its_own_search() {
+        for (items, defectives) in [(5, 1), (6, 2), (7, 2)] {
+            for goal in [Goal::FindGoodPool, Goal::Identify] {
+                let puzzle = GroupTesting {
+                    items,
+                    defectives,
+                    pool_sizes: 1..=items,
+                    goal,
+                };
+                let own = puzzle.solve(items as usize).unwrap().depth();
+                let (tests, _, tree) = adaptive(&puzzle, items as usize).unwrap();
+                assert_eq!((tests, depth(&tree)), (own, own), "{items} {defectives}");
+            }
+        }
+    }
+
+    #[test]
+    fn fewest_tries_on_average_stay_within_the_worst_case() {
+        let params = Params::default();
+        let (cost, states, tree) = adaptive_expected(&BatteryPuzzle::new(params), 7).unwrap();
+        assert_eq!(states.len(), 70);
+        assert!(depth(&tree) <= 7);
+        // Every universe takes at least one try and the worst ones all seven.
+        assert!(70 < cost && cost < 7 * 70);
+        let (tries, _, _) = adaptive(&CountingBatteryPuzzle { params }, 7).unwrap();
+        assert!(tries < 7);
+    }
+}
@@ -0,0 +1,187 @@
+//! Batch files, which list puzzles to run one after the other with `batteries --batch FILE`. They
+//! are the small part of TOML that this needs: `key = value` lines before the first
+//! `[[puzzle]]`, which apply to every puzzle, and in each `[[puzzle]]` table, which override
+//! them. A key stands for the option with dashes instead of underscores; `true` passes a flag,
+//! `false` leaves it out, and strings, numbers and arrays of numbers (`[4, 4]` for `4,4`) are its
+//! value. `command` and `output` are the exceptions: the subcommand to run and the file to write
+//! the output of the puzzle to.
+//!
+//! ```
+//! use batteries::batch::parse_batch;
+//!
+//! let entries = parse_batch("tries = 7\n[[puzzle]]\ntotal = 8\n[[puzzle]]\ntries = 6\n").unwrap();
+//! assert_eq!(entries[0].args, ["--tries", "7", "--total", "8"]);
+//! assert_eq!(entries[1].args, ["--tries", "6"]);
+//! ```
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// One `[[puzzle]]` of a batch file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchEntry {
+    /// The line the `[[puzzle]]` is on.
+    pub line: usize,
+    /// The command line it stands for, without the program name.
+    pub args: Vec<String>,
+    /// The file its output goes to instead of stdout, if any.
+    pub output: Option<String>,
+}
+
+// The keys of a batch file table in order, with their values as arguments, or `None` for `false`.
+type BatchTable = Vec<(String, Option<String>)>;
+
+/// Reads the puzzles of a batch file, or says on which line it is not one.
+pub fn parse_batch(contents: &str) -> Result<Vec<BatchEntry>, String> {
+    let parse_value = |value: &str| -> Option<Option<String>> {
+        if let Some(quoted) = value.strip_prefix('"') {
+            let (text, rest) = quoted.split_once('"')?;
+            let rest = rest.trim_start();
+            return (rest.is_empty() || rest.starts_with('#')).then(|| Some(text.to_string()));
+        }
+        let value = value.split('#').next().unwrap_or_default().trim();
+        let bare = |text: &str| {
+            !text.is_empty()
+                && text
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "._+-".contains(c))
+        };
+        match value {
+            "true" => Some(Some(String::new())),
+            "false" => Some(None),
+            _ if value.starts_with('[') && value.ends_with(']') => {
+                let items: Vec<&str> = value[1..value.len() - 1]
+                    .split(',')
+                    .map(str::trim)
+                    .collect();
+                items
+                    .iter()
+                    .all(|item| bare(item))
+                    .then(|| Some(items.join(",")))
+            }
+            _ if bare(value) => Some(Some(value.replace('_', ""))),
+            _ => None,
+        }
+    };
+
+    let mut defaults: BatchTable = vec![];
+    let mut tables: Vec<(usize, BatchTable)> = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        let text = line.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if text.split('#').next().unwrap_or_default().trim() == "[[puzzle]]" {
+            tables.push((i + 1, vec![]));
+            continue;
+        }
+        let invalid = |what: &str| format!("line {}: {what}", i + 1);
+        if text.starts_with('[') {
+            return Err(invalid("only `[[puzzle]]` tables are supported"));
+        }
+        let (key, value) = text
+            .split_once('=')
+            .ok_or_else(|| invalid("expected `key = value`"))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+            return Err(invalid(&format!("invalid key `{key}`")));
+        }
+        let value = parse_value(value.trim())
+            .ok_or_else(|| invalid(&format!("invalid value for `{key}`")))?;
+        let keys = match tables.last_mut() {
+            Some((_, keys)) => keys,
+            None => &mut defaults,
+        };
+        if keys.iter().any(|(k, _)| k == key) {
+            return Err(invalid(&format!("`{key}` is given twice")));
+        }
+        keys.push((key.to_string(), value));
+    }
+
+    let entries = tables.into_iter().map(|(line, keys)| {
+        let mut args = vec![];
+        let mut output = None;
+        let inherited = defaults
+            .iter()
+            .filter(|(key, _)| keys.iter().all(|(k, _)| k != key));
+        for (key, value) in inherited.chain(&keys) {
+            match (key.as_str(), value) {
+                ("command", Some(command)) => args.insert(0, command.clone()),
+                ("output", Some(file)) => output = Some(file.clone()),
+                (_, None) => {}
+                (key, Some(value)) => {
+                    args.push(format!("--{}", key.replace('_', "-")));
+                    if !value.is_empty() {
+                        args.push(value.clone());
+                    }
+                }
+            }
+        }
+        BatchEntry { line, args, output }
+    });
+    Ok(entries.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_tables_into_command_lines() {
+        let contents = "\
+            # Every puzzle runs with these, unless it says otherwise.
+            total = 8
+            all = true
+            output = \"all.txt\"
+
+            [[puzzle]]
+            good = 4 # the original one
+            groups = [4, 4]
+
+            [[puzzle]]
+            command = \"count\"
+            all = false
+            time_limit = 1_000
+            output = \"count.txt\"
+        ";
+        let entries = parse_batch(contents).unwrap();
+        assert_eq!(
+            entries,
+            [
+                BatchEntry {
+                    line: 6,
+                    args: ["--total", "8", "--all", "--good", "4", "--groups", "4,4"]
+                        .map(String::from)
+                        .to_vec(),
+                    output: Some("all.txt".to_string()),
+                },
+                BatchEntry {
+                    line: 10,
+                    args: ["count", "--total", "8", "--time-limit", "1000"]
+                        .map(String::from)
+                        .to_vec(),
+                    output: Some("count.txt".to_string()),
+                },
+            ]
+        );
+        assert_eq!(parse_batch("total = 8\n"), Ok(vec![]));
+    }
+
+    #[test]
+    fn says_which_line_is_wrong() {
+        let error = |contents: &str| parse_batch(contents).unwrap_err();
+        assert_eq!(
+            error("[[puzzle]]\n[puzzle]\n"),
+            "line 2: only `[[puzzle]]` tables are supported"
+        );
+        assert_eq!(error("total 8\n"), "line 1: expected `key = value`");
+        assert_eq!(error("Total = 8\n"), "line 1: invalid key `Total`");
+        assert_eq!(error("total = 8 8\n"), "line 1: invalid value for `total`");
+        assert_eq!(
+            error("[[puzzle]]\ntotal = 8\ntotal = 9\n"),
+            "line 3: `total` is given twice"
+        );
+    }
+}
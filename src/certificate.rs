@@ -0,0 +1,438 @@
+//! Certificates that no strategy always turns on the toy within a number of tries, which can be
+//! checked without searching again. The search ranks the candidates, the sets of tries besides
+//! the first and the last one, in [`CombinationIter`] order; a certificate has lines that each
+//! start at a candidate rank and list a few universes, by their rank in the same order, in which
+//! no try turns on the toy. A line stands for the candidates from its rank up to the next line,
+//! and holds if all of these universes survive each of them: then the last try cannot work.
+//!
+//! ```
+//! use batteries::certificate::{Certificate, Flaw};
+//! use batteries::{BitSet, Params};
+//!
+//! // 6 tries are not enough in the original puzzle.
+//! let params = Params { tries: 6, ..Params::default() };
+//! let mut certificate = Certificate::build(params, Some(BitSet(0b11))).unwrap();
+//! assert!(certificate.verify().is_ok());
+//!
+//! certificate.lines.remove(0);
+//! assert!(matches!(certificate.verify(), Err(Flaw::Uncovered { .. })));
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{rank, BitSet, CombinationIter, Params};
+
+/// Starts the text of every [`Certificate`].
+pub const HEADER: &str = "# batteries certificate";
+
+/// A proof that the puzzle with `params` has no strategy with the first try `anchor`, if it is
+/// given, that always turns on the toy. Candidates that repeat the first try need no line:
+/// replacing the repeat by any other try gives a candidate that rules out at least as many
+/// universes, so the universes that survive that one survive this one as well.
+///
+/// The text written by `Display` starts with [`HEADER`] and the puzzle, and has a line
+/// `RANK: UNIVERSES` for each of `lines`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Certificate {
+    pub params: Params,
+    pub anchor: Option<BitSet>,
+    /// The rank of the first candidate each line stands for and the universes it gives, by rank.
+    pub lines: Vec<(u64, Vec<usize>)>,
+}
+
+/// Why a [`Certificate`] does not hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flaw {
+    /// The ranks of the lines do not increase.
+    Unordered { rank: u64 },
+    /// A universe of a line does not exist in the puzzle.
+    NoSuchUniverse { rank: u64, universe: usize },
+    /// A universe of a line is ruled out by the first try.
+    RuledOutFirst { rank: u64, universe: usize },
+    /// A try turns on the toy in all universes of a line.
+    TryWorks { rank: u64 },
+    /// A candidate comes before the first line.
+    Uncovered { rank: u64 },
+    /// A candidate rules out a universe of the line that stands for it.
+    RuledOut {
+        rank: u64,
+        start: u64,
+        universe: usize,
+    },
+    /// A line starts after the last candidate.
+    NoSuchCandidate { rank: u64 },
+}
+
+impl fmt::Display for Flaw {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Flaw::Unordered { rank } => write!(
+                f,
+                "the candidate ranks have to increase, up to the line for candidate {rank}"
+            ),
+            Flaw::NoSuchUniverse { rank, universe } => write!(
+                f,
+                "universe {universe} of the line for candidate {rank} does not exist"
+            ),
+            Flaw::RuledOutFirst { rank, universe } => write!(
+                f,
+                "universe {universe} of the line for candidate {rank} does not survive the first \
+                try"
+            ),
+            Flaw::TryWorks { rank } => write!(
+                f,
+                "a try turns on the toy in all universes of the line for candidate {rank}"
+            ),
+            Flaw::Uncovered { rank } => write!(f, "no line covers candidate {rank}"),
+            Flaw::RuledOut {
+                rank,
+                start,
+                universe,
+            } => write!(
+                f,
+                "candidate {rank} rules out universe {universe} of the line for candidate {start}"
+            ),
+            Flaw::NoSuchCandidate { rank } => write!(
+                f,
+                "there is a line for candidate {rank}, which does not exist"
+            ),
+        }
+    }
+}
+
+impl Certificate {
+    /// Writes down the proof for the puzzle with `params` and the first try `anchor`, going
+    /// through all candidates, or returns the rank of one that is a solution. Each line starts at
+    /// the first candidate that leaves none of the universes in common with the ones since the
+    /// last line that would do for all of them, and gives a few of those.
+    ///
+    /// Like the search, this takes C(total, slots) <= 128 and as many tries as it allows.
+    pub fn build(params: Params, anchor: Option<BitSet>) -> Result<Certificate, u64> {
+        let space = Exhaustion::new(params, anchor);
+        // The rank the current line starts at and the universes that survive all candidates
+        // since.
+        let mut line: Option<(u64, Vec<u128>)> = None;
+        let mut lines = vec![];
+        let witness = |(start, left): (u64, Vec<u128>)| {
+            let witness = space
+                .witness(&left)
+                .expect("a line only covers candidates with a witness");
+            (start, witness)
+        };
+        for (rank, steps) in space.candidates() {
+            if space.skips(steps) {
+                continue;
+            }
+            let left = space.left(steps);
+            if let Some((_, common)) = &mut line {
+                let both: Vec<u128> = common.iter().zip(&left).map(|(a, b)| a & b).collect();
+                if space.witness(&both).is_some() {
+                    *common = both;
+                    continue;
+                }
+            }
+            if space.witness(&left).is_none() {
+                return Err(rank);
+            }
+            if let Some(line) = line.replace((rank, left)) {
+                lines.push(witness(line));
+            }
+        }
+        lines.push(witness(
+            line.expect("some candidate does not repeat the first try"),
+        ));
+        Ok(Certificate {
+            params,
+            anchor,
+            lines,
+        })
+    }
+
+    /// Checks the proof by going through all candidates, returning how many of them it rules
+    /// out, i.e. all but those that repeat the first try. Like [`build`](Certificate::build),
+    /// this takes a puzzle that the search handles.
+    pub fn verify(&self) -> Result<u64, Flaw> {
+        let space = Exhaustion::new(self.params, self.anchor);
+        for (i, (rank, universes)) in self.lines.iter().enumerate() {
+            let rank = *rank;
+            if i > 0 && self.lines[i - 1].0 >= rank {
+                return Err(Flaw::Unordered { rank });
+            }
+            if let Some(&universe) = universes.iter().find(|&&u| u >= space.universes.len()) {
+                return Err(Flaw::NoSuchUniverse { rank, universe });
+            }
+            if let Some(&universe) = universes.iter().find(|&&u| !is_possible(&space.initial, u)) {
+                return Err(Flaw::RuledOutFirst { rank, universe });
+            }
+            let common = universes
+                .iter()
+                .map(|&u| space.universes[u])
+                .reduce(|a, b| a & b);
+            if common.is_none_or(|common| u64::from(common.len()) >= self.params.slots) {
+                return Err(Flaw::TryWorks { rank });
+            }
+        }
+        let mut next = 0;
+        let mut current = None;
+        let mut checked = 0;
+        for (rank, steps) in space.candidates() {
+            while self
+                .lines
+                .get(next)
+                .is_some_and(|&(start, _)| start <= rank)
+            {
+                current = Some(&self.lines[next]);
+                next += 1;
+            }
+            if space.skips(steps) {
+                continue;
+            }
+            let Some((start, universes)) = current else {
+                return Err(Flaw::Uncovered { rank });
+            };
+            if let Some(&universe) = universes.iter().find(|&&u| !space.survives(steps, u)) {
+                return Err(Flaw::RuledOut {
+                    rank,
+                    start: *start,
+                    universe,
+                });
+            }
+            checked += 1;
+        }
+        match self.lines.get(next) {
+            Some(&(rank, _)) => Err(Flaw::NoSuchCandidate { rank }),
+            None => Ok(checked),
+        }
+    }
+}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Params {
+            total,
+            good,
+            slots,
+            tries,
+        } = self.params;
+        let anchor = match self.anchor {
+            Some(anchor) => anchor
+                .into_iter()
+                .map(|b| b.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            None => "none".to_string(),
+        };
+        writeln!(f, "{HEADER}")?;
+        writeln!(
+            f,
+            "total {total}\ngood {good}\nslots {slots}\ntries {tries}"
+        )?;
+        writeln!(f, "anchor {anchor}")?;
+        writeln!(
+            f,
+            "# RANK: UNIVERSES that survive the candidates from RANK up to the next line, \
+            without a try that works in all of them"
+        )?;
+        for (rank, universes) in &self.lines {
+            let universes: Vec<String> = universes.iter().map(usize::to_string).collect();
+            writeln!(f, "{rank}: {}", universes.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+// The candidates that a certificate goes through: the sets of `free_steps` tries besides the
+// anchor and the last try, ranked by the indices of their tries in `CombinationIter` order like
+// `Solver` ranks them. `survivors` says which universes each try leaves possible when it fails,
+// and `initial` which ones the anchor does, with bit `u % 128` of word `u / 128` for universe `u`.
+struct Exhaustion {
+    params: Params,
+    anchor_index: Option<usize>,
+    free_steps: u64,
+    universes: Vec<BitSet>,
+    survivors: Vec<Vec<u128>>,
+    initial: Vec<u128>,
+}
+
+impl Exhaustion {
+    fn new(params: Params, anchor: Option<BitSet>) -> Self {
+        let pairs: Vec<BitSet> = CombinationIter::new(params.total, params.slots).collect();
+        let universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+        let possible = |pair: Option<BitSet>| {
+            let mut mask = vec![0; universes.len().div_ceil(128)];
+            for (u, &universe) in universes.iter().enumerate() {
+                if !pair.is_some_and(|pair| pair.is_subset(universe)) {
+                    mask[u / 128] |= 1 << (u % 128);
+                }
+            }
+            mask
+        };
+        Exhaustion {
+            params,
+            anchor_index: anchor.map(|anchor| rank(anchor) as usize),
+            free_steps: params.tries - 1 - u64::from(anchor.is_some()),
+            survivors: pairs.iter().map(|&pair| possible(Some(pair))).collect(),
+            initial: possible(anchor),
+            universes,
+        }
+    }
+
+    // Without free steps the only candidate is the empty one, which `CombinationIter` does not
+    // enumerate.
+    fn candidates(&self) -> impl Iterator<Item = (u64, BitSet<u128>)> {
+        let pairs = self.survivors.len() as u64;
+        let steps = (self.free_steps > 0).then(|| CombinationIter::new(pairs, self.free_steps));
+        let empty = (self.free_steps == 0).then(BitSet::default);
+        (0..).zip(steps.into_iter().flatten().chain(empty))
+    }
+
+    fn skips(&self, steps: BitSet<u128>) -> bool {
+        self.anchor_index
+            .is_some_and(|anchor| steps.contains(anchor))
+    }
+
+    fn survives(&self, steps: BitSet<u128>, universe: usize) -> bool {
+        steps
+            .into_iter()
+            .all(|pair| is_possible(&self.survivors[pair], universe))
+    }
+
+    // The universes that survive the anchor and the tries of the candidate.
+    fn left(&self, steps: BitSet<u128>) -> Vec<u128> {
+        let mut left = self.initial.clone();
+        for pair in steps {
+            for (word, mask) in left.iter_mut().zip(&self.survivors[pair]) {
+                *word &= mask;
+            }
+        }
+        left
+    }
+
+    // A few of the universes in `left` such that no try turns on the toy in all of them, picking
+    // each time the one that leaves the fewest batteries functional in all picked so far. None if
+    // there are no such universes, i.e. trying what they are left by always turns on the toy.
+    fn witness(&self, left: &[u128]) -> Option<Vec<usize>> {
+        let left: Vec<usize> = (0..self.universes.len())
+            .filter(|&u| is_possible(left, u))
+            .collect();
+        let mut picked = vec![*left.first()?];
+        let mut common = self.universes[picked[0]];
+        while u64::from(common.len()) >= self.params.slots {
+            let &next = left
+                .iter()
+                .min_by_key(|&&u| (common & self.universes[u]).len())
+                .expect("some universe is left");
+            // All the universes left have the batteries in common, so the last try works.
+            if common & self.universes[next] == common {
+                return None;
+            }
+            common = common & self.universes[next];
+            picked.push(next);
+        }
+        Some(picked)
+    }
+}
+
+fn is_possible(mask: &[u128], universe: usize) -> bool {
+    mask[universe / 128] >> (universe % 128) & 1 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    // Turán's theorem needs 6 tries for 6 batteries with 3 functional and a toy that takes 2.
+    fn impossible() -> Params {
+        Params {
+            total: 6,
+            good: 3,
+            slots: 2,
+            tries: 5,
+        }
+    }
+
+    #[test]
+    fn proves_that_too_few_tries_do_not_work() {
+        for anchor in [None, Some(BitSet(0b11))] {
+            let certificate = Certificate::build(impossible(), anchor).unwrap();
+            let candidates = match anchor {
+                // The candidates with the anchor that do not repeat it: 3 of the other 14 tries.
+                Some(_) => 364,
+                None => 1365,
+            };
+            assert_eq!(certificate.verify(), Ok(candidates));
+        }
+        let possible = Params {
+            tries: 6,
+            ..impossible()
+        };
+        assert!(Certificate::build(possible, Some(BitSet(0b11))).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_certificates() {
+        let certificate = Certificate::build(impossible(), Some(BitSet(0b11))).unwrap();
+        let (start, _) = certificate.lines[0];
+        let tampered = |tamper: &dyn Fn(&mut Certificate)| {
+            let mut certificate = certificate.clone();
+            tamper(&mut certificate);
+            certificate.verify()
+        };
+        // Universe 0 is {0, 1, 2}, which the first try turns on the toy in.
+        assert_eq!(
+            tampered(&|c| c.lines[0].1[0] = 0),
+            Err(Flaw::RuledOutFirst {
+                rank: start,
+                universe: 0
+            })
+        );
+        assert_eq!(
+            tampered(&|c| c.lines[0].1.push(20)),
+            Err(Flaw::NoSuchUniverse {
+                rank: start,
+                universe: 20
+            })
+        );
+        assert_eq!(
+            tampered(&|c| c.lines[0].1.truncate(1)),
+            Err(Flaw::TryWorks { rank: start })
+        );
+        assert_eq!(
+            tampered(&|c| {
+                c.lines.remove(0);
+            }),
+            Err(Flaw::Uncovered { rank: start })
+        );
+        assert_eq!(
+            tampered(&|c| {
+                let last = c.lines[c.lines.len() - 1].1.clone();
+                c.lines.push((1 << 20, last));
+            }),
+            Err(Flaw::NoSuchCandidate { rank: 1 << 20 })
+        );
+        // The witnesses of one line do not do for the candidates of another.
+        if certificate.lines.len() > 1 {
+            assert!(matches!(
+                tampered(&|c| c.lines[1].1 = c.lines[0].1.clone()),
+                Err(Flaw::RuledOut { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn writes_the_puzzle_and_a_line_each() {
+        let certificate = Certificate::build(impossible(), None).unwrap();
+        let text = certificate.to_string();
+        assert!(text.starts_with(&format!(
+            "{HEADER}\ntotal 6\ngood 3\nslots 2\ntries 5\nanchor none\n"
+        )));
+        assert_eq!(
+            text.lines().filter(|line| !line.starts_with('#')).count(),
+            5 + certificate.lines.len()
+        );
+    }
+}
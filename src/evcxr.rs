@@ -0,0 +1,264 @@
+//! Rich output in [evcxr](https://github.com/evcxr/evcxr) notebooks, with the `evcxr` feature.
+//! evcxr shows a value by calling its `evcxr_display` method, which prints HTML between the
+//! `EVCXR_BEGIN_CONTENT` and `EVCXR_END_CONTENT` markers: a [`Strategy`] is drawn as a tree, an
+//! [`Evaluation`] and a [`SearchReport`] as tables. A [`Solution`] is a plain `Vec` that cannot
+//! have methods of its own, so it is shown with [`display_solution`].
+
+use std::fmt::Write;
+
+use crate::group_testing::Strategy;
+use crate::{BitSet, Evaluation, SearchOutcome, SearchReport, Solution, Word};
+
+// Horizontal distance between the leaves of a strategy tree and vertical distance between its
+// levels, in pixels.
+const COLUMN: f64 = 90.0;
+const ROW: f64 = 70.0;
+
+fn print_html(html: &str) {
+    println!("EVCXR_BEGIN_CONTENT text/html\n{html}\nEVCXR_END_CONTENT");
+}
+
+impl<W: Word> BitSet<W> {
+    /// Shows the elements in set notation.
+    pub fn evcxr_display(&self) {
+        print_html(&format!("<code>{self:#}</code>"));
+    }
+}
+
+impl Strategy {
+    /// Draws the decision tree, with the test that finds the pool all good on the left.
+    pub fn evcxr_display(&self) {
+        print_html(&strategy_svg(self));
+    }
+}
+
+impl Evaluation {
+    /// Shows how often each try is the first to turn on the toy and the success rate after it.
+    pub fn evcxr_display(&self) {
+        print_html(&evaluation_table(self));
+    }
+}
+
+impl SearchReport {
+    /// Shows how much of the search space was explored and what the search settled.
+    pub fn evcxr_display(&self) {
+        print_html(&report_table(self));
+    }
+}
+
+/// Shows the tries of `solution` as a numbered list, in an evcxr notebook.
+pub fn display_solution(solution: &Solution) {
+    print_html(&solution_list(solution));
+}
+
+struct Node {
+    x: f64,
+    depth: usize,
+    label: String,
+}
+
+// Places the leaves of the tree in columns from left to right and every test above the middle of
+// its two outcomes, returning the index of the node of `strategy`.
+fn place(
+    strategy: &Strategy,
+    depth: usize,
+    leaves: &mut usize,
+    nodes: &mut Vec<Node>,
+    edges: &mut Vec<(usize, usize, &'static str)>,
+) -> usize {
+    let (x, label) = match strategy {
+        Strategy::Done(sets) => {
+            let x = *leaves as f64;
+            *leaves += 1;
+            let label = match sets.as_slice() {
+                [set] => format!("{set:#}"),
+                _ => format!("{} sets", sets.len()),
+            };
+            (x, label)
+        }
+        Strategy::Test {
+            pool,
+            all_good,
+            some_defective,
+        } => {
+            let good = place(all_good, depth + 1, leaves, nodes, edges);
+            let defective = place(some_defective, depth + 1, leaves, nodes, edges);
+            let x = (nodes[good].x + nodes[defective].x) / 2.0;
+            // The children are pushed before their parent, so the parent is the next node.
+            edges.push((nodes.len(), good, "good"));
+            edges.push((nodes.len(), defective, "defective"));
+            (x, format!("{pool:#}"))
+        }
+    };
+    nodes.push(Node { x, depth, label });
+    nodes.len() - 1
+}
+
+fn strategy_svg(strategy: &Strategy) -> String {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    let mut leaves = 0;
+    place(strategy, 0, &mut leaves, &mut nodes, &mut edges);
+    let position = |node: &Node| {
+        (
+            COLUMN / 2.0 + node.x * COLUMN,
+            ROW / 2.0 + node.depth as f64 * ROW,
+        )
+    };
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+        font-family=\"monospace\" font-size=\"12\" text-anchor=\"middle\">",
+        leaves as f64 * COLUMN,
+        (strategy.depth() + 1) as f64 * ROW
+    );
+    for &(parent, child, label) in &edges {
+        let (x1, y1) = position(&nodes[parent]);
+        let (x2, y2) = position(&nodes[child]);
+        let _ = write!(
+            svg,
+            "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"gray\"/>\
+            <text x=\"{}\" y=\"{}\" fill=\"gray\">{label}</text>",
+            (x1 + x2) / 2.0,
+            (y1 + y2) / 2.0
+        );
+    }
+    for node in &nodes {
+        let (x, y) = position(node);
+        let _ = write!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"20\" rx=\"4\" fill=\"white\" \
+            stroke=\"black\"/><text x=\"{x}\" y=\"{}\">{}</text>",
+            x - COLUMN * 0.4,
+            y - 10.0,
+            COLUMN * 0.8,
+            y + 4.0,
+            node.label
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn evaluation_table(evaluation: &Evaluation) -> String {
+    let mut html = format!(
+        "<table><caption>{} {}universes</caption>\
+        <tr><th>Try</th><th>Turns on the toy first in</th><th>Success rate after it</th></tr>",
+        evaluation.universes,
+        if evaluation.exact { "" } else { "sampled " }
+    );
+    for (i, &turned_on) in evaluation.turned_on.iter().enumerate() {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td>{turned_on}</td><td>{:.2}%</td></tr>",
+            i + 1,
+            100.0 * evaluation.success_rate(i + 1)
+        );
+    }
+    html.push_str("</table>");
+    html
+}
+
+fn report_table(report: &SearchReport) -> String {
+    let outcome = match report.outcome() {
+        SearchOutcome::Found => "found a strategy",
+        SearchOutcome::NoneExist => "no strategy exists",
+        SearchOutcome::Unfinished => "unfinished",
+    };
+    let mut html = format!(
+        "<table><tr><th>Outcome</th><td>{outcome}</td></tr>\
+        <tr><th>Examined</th><td>{} of {} candidates</td></tr>\
+        <tr><th>Pruned</th><td>{}</td></tr>\
+        <tr><th>Found</th><td>{}</td></tr>\
+        <tr><th>Timed out</th><td>{}</td></tr>",
+        report.examined,
+        report.total,
+        report.pruned,
+        report.found,
+        if report.timed_out { "yes" } else { "no" }
+    );
+    if let Some(fewest) = report.fewest_remaining {
+        let _ = write!(
+            html,
+            "<tr><th>Fewest universes left</th><td>{fewest}</td></tr>"
+        );
+    }
+    html.push_str("</table>");
+    html
+}
+
+fn solution_list(solution: &Solution) -> String {
+    let mut html = String::from("<ol>");
+    for pair in solution {
+        let batteries: Vec<String> = pair.iter().map(|b| b.to_string()).collect();
+        let _ = write!(html, "<li><code>{{{}}}</code></li>", batteries.join(","));
+    }
+    html.push_str("</ol>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_testing::{Goal, GroupTesting};
+    use crate::Params;
+
+    fn nodes(strategy: &Strategy) -> usize {
+        match strategy {
+            Strategy::Done(_) => 1,
+            Strategy::Test {
+                all_good,
+                some_defective,
+                ..
+            } => 1 + nodes(all_good) + nodes(some_defective),
+        }
+    }
+
+    #[test]
+    fn draws_every_test_and_outcome_of_a_strategy() {
+        // Finding the one defective item of 4 takes two halvings.
+        let puzzle = GroupTesting {
+            items: 4,
+            defectives: 1,
+            pool_sizes: 1..=2,
+            goal: Goal::Identify,
+        };
+        let strategy = puzzle.solve(2).expect("2 tests are enough");
+        let svg = strategy_svg(&strategy);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), nodes(&strategy));
+        assert_eq!(svg.matches("<line").count(), nodes(&strategy) - 1);
+    }
+
+    #[test]
+    fn tabulates_evaluations_and_reports() {
+        let params = Params::default();
+        let tries = [
+            0b11, 0b1100, 0b10100, 0b11000, 0b100001, 0b100010, 0b11000000,
+        ]
+        .map(BitSet);
+        let html = evaluation_table(&Evaluation::exact(params, &tries));
+        assert!(html.contains("<caption>70 universes</caption>"));
+        assert_eq!(html.matches("<tr>").count(), 1 + tries.len());
+        assert!(html.contains("<td>7</td><td>9</td><td>100.00%</td>"));
+
+        let report = SearchReport {
+            examined: 10,
+            pruned: 4,
+            total: 10,
+            found: 0,
+            timed_out: false,
+            fewer_tries_suffice: false,
+            fewest_remaining: Some(3),
+        };
+        let html = report_table(&report);
+        assert!(html.contains("<td>no strategy exists</td>"));
+        assert!(html.contains("<td>10 of 10 candidates</td>"));
+        assert!(html.contains("<td>3</td>"));
+
+        let html = solution_list(&vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(
+            html,
+            "<ol><li><code>{0,1}</code></li><li><code>{2,3}</code></li></ol>"
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! Combinatorial group testing: some of the items are defective, and a test of a pool of items
+//! tells whether all of them are good. The battery puzzle is one configuration of it, where the
+//! defective items are the batteries that do not work and the game ends with the first test of
+//! `slots` batteries that all work ([`GroupTesting::battery_puzzle`]).
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
+use crate::{BitSet, CombinationIter, Params};
+
+/// When the tests are over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Goal {
+    /// As soon as a pool turns out to be all good, like the toy turning on.
+    FindGoodPool,
+    /// Once the defective items are known.
+    Identify,
+}
+
+/// `defectives` of the `items` (at most 64) are defective, and a test may pool any number of
+/// items in `pool_sizes`.
+#[derive(Clone, Debug)]
+pub struct GroupTesting {
+    pub items: u64,
+    pub defectives: u64,
+    pub pool_sizes: RangeInclusive<u64>,
+    pub goal: Goal,
+}
+
+/// A decision tree of tests.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Strategy {
+    /// No more tests; these sets of defective items are still possible.
+    Done(Vec<BitSet>),
+    Test {
+        pool: BitSet,
+        all_good: Box<Strategy>,
+        some_defective: Box<Strategy>,
+    },
+}
+
+impl Strategy {
+    /// Number of tests in the worst case.
+    pub fn depth(&self) -> usize {
+        match self {
+            Strategy::Done(_) => 0,
+            Strategy::Test {
+                all_good,
+                some_defective,
+                ..
+            } => 1 + all_good.depth().max(some_defective.depth()),
+        }
+    }
+}
+
+impl GroupTesting {
+    /// The battery puzzle with `params`: the batteries that do not work are the defective items
+    /// and the toy takes `slots` of them.
+    pub fn battery_puzzle(params: Params) -> Self {
+        GroupTesting {
+            items: params.total,
+            defectives: params.total - params.good,
+            pool_sizes: params.slots..=params.slots,
+            goal: Goal::FindGoodPool,
+        }
+    }
+
+    /// Every set of defective items that is possible at the start.
+    pub fn candidates(&self) -> Vec<BitSet> {
+        if self.defectives == 0 {
+            return vec![BitSet::default()];
+        }
+        CombinationIter::new(self.items, self.defectives).collect()
+    }
+
+    fn solved(&self, all_good: bool, candidates: &[BitSet]) -> bool {
+        match self.goal {
+            Goal::FindGoodPool => all_good || candidates.is_empty(),
+            Goal::Identify => candidates.len() <= 1,
+        }
+    }
+
+    /// A strategy with the fewest tests in the worst case, if at most `max_tests` are enough.
+    pub fn solve(&self, max_tests: usize) -> Option<Strategy> {
+        let candidates = self.candidates();
+        let mut failed = BTreeMap::new();
+        (0..=max_tests).find_map(|tests| self.search(&candidates, tests, &mut failed))
+    }
+
+    // Depth-first search for a strategy with at most `tests` tests, remembering for each set of
+    // candidates that could not be dealt with the most tests it was tried with.
+    fn search(
+        &self,
+        candidates: &[BitSet],
+        tests: usize,
+        failed: &mut BTreeMap<Vec<BitSet>, usize>,
+    ) -> Option<Strategy> {
+        if self.goal == Goal::Identify && self.solved(false, candidates) {
+            return Some(Strategy::Done(candidates.to_vec()));
+        }
+        if tests == 0 || failed.get(candidates).is_some_and(|&t| t >= tests) {
+            return None;
+        }
+        let masks: Vec<u64> = candidates.iter().map(|c| c.0).collect();
+        let classes = interchangeable_classes(self.items as usize, &masks);
+        let sizes = *self.pool_sizes.start() as usize..=*self.pool_sizes.end() as usize;
+        'pools: for pool in class_selections(&classes, sizes) {
+            let pool: BitSet = pool.into_iter().collect();
+            let (good, defective): (Vec<BitSet>, Vec<BitSet>) =
+                candidates.iter().partition(|&&c| (pool & c).is_empty());
+
+            let mut branches = vec![];
+            for (all_good, part) in [(true, good), (false, defective)] {
+                if self.solved(all_good, &part) {
+                    branches.push(Strategy::Done(part));
+                } else if part.len() == candidates.len() {
+                    // The test tells nothing, so making it would only waste a test.
+                    continue 'pools;
+                } else {
+                    match self.search(&part, tests - 1, failed) {
+                        Some(strategy) => branches.push(strategy),
+                        None => continue 'pools,
+                    }
+                }
+            }
+            let some_defective = Box::new(branches.pop().expect("two branches"));
+            let all_good = Box::new(branches.pop().expect("two branches"));
+            return Some(Strategy::Test {
+                pool,
+                all_good,
+                some_defective,
+            });
+        }
+        failed.insert(candidates.to_vec(), tests);
+        None
+    }
+}
+
+/// Splits 0..n into classes of interchangeable elements: swapping two elements of a class maps
+/// the set of `states` (each a mask of elements) onto itself. Swaps within a class generate all
+/// permutations of it, so queries only need to be asked up to permuting the classes.
+pub fn interchangeable_classes(n: usize, states: &[u64]) -> Vec<Vec<usize>> {
+    let set: BTreeSet<u64> = states.iter().copied().collect();
+    let swap = |s: u64, i: usize, j: usize| {
+        let (bi, bj) = ((s >> i) & 1, (s >> j) & 1);
+        (s & !(1 << i) & !(1 << j)) | bi << j | bj << i
+    };
+    let mut classes: Vec<Vec<usize>> = vec![];
+    for e in 0..n {
+        match classes
+            .iter_mut()
+            .find(|class| states.iter().all(|&s| set.contains(&swap(s, class[0], e))))
+        {
+            Some(class) => class.push(e),
+            None => classes.push(vec![e]),
+        }
+    }
+    classes
+}
+
+/// A form of the set of `states` (each a mask of elements of 0..n) under relabeling elements:
+/// the sorted masks after a relabeling. Sets of states that have the same form are relabelings
+/// of each other, and relabelings of each other mostly have the same form, so it can key a table
+/// of what is known about sets of states up to symmetry.
+///
+/// Elements are first ordered by invariants that do not depend on their labels (how many states
+/// they are in, and then which kinds of elements share these states), and the form is the
+/// smallest over the orders of elements that these invariants cannot tell apart, up to swapping
+/// interchangeable elements. If there are too many such orders, a single one is used, and then
+/// some relabelings of the states may have other forms.
+pub fn canonical_form(n: usize, states: &[u64]) -> Vec<u64> {
+    // Any order is as good as any other beyond this many.
+    const MAX_ORDERS: u64 = 720;
+
+    let mut colors = vec![0usize; n];
+    for &s in states {
+        for e in BitSet(s) {
+            colors[e] += 1;
+        }
+    }
+    let mut count = 0;
+    loop {
+        // What an element sees: its color, and the multiset of colors of each of its states.
+        let signatures: Vec<(usize, Vec<Vec<usize>>)> = (0..n)
+            .map(|e| {
+                let mut around: Vec<Vec<usize>> = states
+                    .iter()
+                    .filter(|&&s| s & 1 << e != 0)
+                    .map(|&s| {
+                        let mut seen: Vec<usize> =
+                            BitSet(s).into_iter().map(|x| colors[x]).collect();
+                        seen.sort_unstable();
+                        seen
+                    })
+                    .collect();
+                around.sort_unstable();
+                (colors[e], around)
+            })
+            .collect();
+        let order: Vec<&(usize, Vec<Vec<usize>>)> = signatures
+            .iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        colors = signatures
+            .iter()
+            .map(|sig| {
+                order
+                    .binary_search(&sig)
+                    .expect("every signature is listed")
+            })
+            .collect();
+        if order.len() == count {
+            break;
+        }
+        count = order.len();
+    }
+
+    // The elements of each color, as the interchangeable classes they belong to.
+    let classes = interchangeable_classes(n, states);
+    let mut cells: Vec<Vec<usize>> = vec![vec![]; count];
+    for (class, members) in classes.iter().enumerate() {
+        for &e in members {
+            cells[colors[e]].push(class);
+        }
+    }
+    let orders = cells.iter().try_fold(1u64, |orders, cell| {
+        orders
+            .checked_mul(arrangements(cell))
+            .filter(|&o| o <= MAX_ORDERS)
+    });
+    if orders.is_none() {
+        return relabeled(states, &cells, &classes);
+    }
+    for cell in &mut cells {
+        cell.sort_unstable();
+    }
+    let mut best = relabeled(states, &cells, &classes);
+    // Steps through the combinations of orders of the cells like a counter with a digit per cell.
+    while cells.iter_mut().any(|cell| next_arrangement(cell)) {
+        best = best.min(relabeled(states, &cells, &classes));
+    }
+    best
+}
+
+// Number of distinct orders of the multiset `cell`.
+fn arrangements(cell: &[usize]) -> u64 {
+    let mut sorted = cell.to_vec();
+    sorted.sort_unstable();
+    let mut orders = 1u64;
+    let mut same = 0;
+    for i in 0..sorted.len() {
+        same = if i > 0 && sorted[i] == sorted[i - 1] {
+            same + 1
+        } else {
+            1
+        };
+        // C(i + 1, same) orders so far, built up one element at a time.
+        orders = orders.saturating_mul(i as u64 + 1) / same;
+    }
+    orders
+}
+
+// Moves `cell` to the next larger order of its elements and returns true, or back to the
+// smallest one and returns false if it was the largest.
+fn next_arrangement(cell: &mut [usize]) -> bool {
+    let Some(i) = (1..cell.len()).rev().find(|&i| cell[i - 1] < cell[i]) else {
+        cell.reverse();
+        return false;
+    };
+    let j = (i..cell.len())
+        .rev()
+        .find(|&j| cell[j] > cell[i - 1])
+        .expect("a larger one");
+    cell.swap(i - 1, j);
+    cell[i..].reverse();
+    true
+}
+
+// The sorted states after numbering the elements cell by cell, in the order of `cells`, which
+// lists the class of each element: the members of a class are numbered in turn.
+fn relabeled(states: &[u64], cells: &[Vec<usize>], classes: &[Vec<usize>]) -> Vec<u64> {
+    let mut label = [0; 64];
+    let mut taken = vec![0; classes.len()];
+    for (next, &class) in cells.iter().flatten().enumerate() {
+        label[classes[class][taken[class]]] = next;
+        taken[class] += 1;
+    }
+    let mut form: Vec<u64> = states
+        .iter()
+        .map(|&s| BitSet(s).into_iter().fold(0, |m, e| m | 1 << label[e]))
+        .collect();
+    form.sort_unstable();
+    form
+}
+
+/// One subset for each way to pick a number of elements from every class, with `sizes` elements
+/// in total: the first ones of each class are picked.
+pub fn class_selections(classes: &[Vec<usize>], sizes: RangeInclusive<usize>) -> Vec<Vec<usize>> {
+    let mut selections = vec![vec![]];
+    for class in classes {
+        selections = selections
+            .into_iter()
+            .flat_map(|selection: Vec<usize>| {
+                let most = class.len().min(sizes.end().saturating_sub(selection.len()));
+                (0..=most).map(move |count| [&selection[..], &class[..count]].concat())
+            })
+            .collect();
+    }
+    selections.retain(|s| sizes.contains(&s.len()));
+    for selection in &mut selections {
+        selection.sort_unstable();
+    }
+    selections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random_combination, Rng};
+
+    fn relabel(states: &[u64], map: &[usize]) -> Vec<u64> {
+        states
+            .iter()
+            .map(|&s| BitSet(s).into_iter().fold(0, |m, e| m | 1 << map[e]))
+            .collect()
+    }
+
+    #[test]
+    fn relabelings_share_their_canonical_form() {
+        let mut rng = Rng::new(213);
+        for _ in 0..200 {
+            let n = 2 + rng.below(7) as usize;
+            let size = 1 + rng.below(n as u64 - 1);
+            let count = 1 + rng.below(8);
+            let states: Vec<u64> = (0..count)
+                .map(|_| random_combination(n as u64, size, &mut rng).0)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            let form = canonical_form(n, &states);
+            assert_eq!(form.len(), states.len());
+
+            // A random permutation of 0..n, by Fisher-Yates.
+            let mut map: Vec<usize> = (0..n).collect();
+            for i in (1..n).rev() {
+                map.swap(i, rng.below(i as u64 + 1) as usize);
+            }
+            assert_eq!(canonical_form(n, &relabel(&states, &map)), form);
+        }
+    }
+
+    #[test]
+    fn different_structures_have_different_forms() {
+        // A path 0-1-2-3 and a star around 0, both with three edges.
+        let path = [0b11, 0b110, 0b1100];
+        let star = [0b11, 0b101, 0b1001];
+        assert_ne!(canonical_form(4, &path), canonical_form(4, &star));
+        assert_eq!(
+            canonical_form(4, &path),
+            canonical_form(4, &[0b1001, 0b110, 0b101])
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Deciding whether two solutions are the same up to relabeling batteries and reordering tries.
+//! A try is compared as the mask of its batteries, and the guaranteed batteries of a solution are
+//! just another try, which is what they are when the strategy is carried out.
+//!
+//! ```
+//! use batteries::isomorphism::{isomorphism, same_solution};
+//!
+//! let a = vec![vec![0, 1], vec![1, 2], vec![3]];
+//! let b = vec![vec![2], vec![1, 3], vec![0, 1]];
+//! assert!(same_solution(&a, &b));
+//! assert_eq!(isomorphism(&a, &b), Some(vec![0, 1, 3, 2]));
+//! assert!(!same_solution(&a, &[vec![0, 1], vec![2, 3], vec![1]]));
+//! ```
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+use crate::{BitSet, Solution};
+
+fn mask(batteries: &[usize]) -> u64 {
+    batteries.iter().fold(0, |acc, &b| acc | 1 << b)
+}
+
+fn masks(tries: &[Vec<usize>]) -> Vec<u64> {
+    tries.iter().map(|t| mask(t)).collect()
+}
+
+/// Order-independent form of a solution after renaming each battery `b` to `map[b]`: the sorted
+/// masks of its tries. Two solutions are equal once relabeled by `map` exactly when their keys
+/// are.
+pub fn strategy_key(solution: &[Vec<usize>], map: &[usize]) -> Vec<u64> {
+    let mut tries: Vec<u64> = solution
+        .iter()
+        .map(|batteries| batteries.iter().fold(0, |acc, &b| acc | 1 << map[b]))
+        .collect();
+    tries.sort_unstable();
+    tries
+}
+
+/// Whether relabeling batteries turns the tries of `a` into the tries of `b`, in any order.
+pub fn same_solution(a: &[Vec<usize>], b: &[Vec<usize>]) -> bool {
+    relabeling(a, b).is_some()
+}
+
+/// A relabeling that turns the tries of `a` into the tries of `b`, if there is one: `map[x]` is
+/// the label of each battery `x` that `a` uses.
+pub fn relabeling(a: &[Vec<usize>], b: &[Vec<usize>]) -> Option<[usize; 64]> {
+    let mut found = None;
+    relabelings(&masks(a), &masks(b), &mut |map| {
+        found = Some(*map);
+        true
+    });
+    found
+}
+
+/// A relabeling of all batteries up to the largest one either solution uses that turns `a` into
+/// `b`, if there is one: the batteries that `a` uses go where [`relabeling`] puts them, and the
+/// ones neither solution uses go to the labels `b` leaves free, in increasing order.
+pub fn isomorphism(a: &Solution, b: &Solution) -> Option<Vec<usize>> {
+    let map = relabeling(a, b)?;
+    let used = |strategy: &Solution| strategy.iter().fold(0, |acc, t| acc | mask(t));
+    let (used_a, used_b) = (used(a), used(b));
+    let batteries = 64 - (used_a | used_b).leading_zeros() as usize;
+    let mut unused = (0..batteries).filter(|&label| used_b & 1 << label == 0);
+    Some(
+        (0..batteries)
+            .map(|from| match used_a & 1 << from != 0 {
+                true => map[from],
+                false => unused.next().expect("both solutions use as many batteries"),
+            })
+            .collect(),
+    )
+}
+
+/// Goes through the relabelings of the batteries that turn the tries `a` into the tries `b` (as
+/// masks), in any order, calling `visit` with each one until it returns true, and returns whether
+/// it did. The relabeling is built one battery at a time (busiest first), and dropped as soon as
+/// a try of `a` whose batteries all got a label does not turn into a try of `b`.
+///
+/// Visiting every relabeling of `a` onto itself counts its automorphisms.
+pub fn relabelings(a: &[u64], b: &[u64], visit: &mut dyn FnMut(&[usize; 64]) -> bool) -> bool {
+    fn extend(
+        a: &[u64],
+        b: &BTreeSet<u64>,
+        order: &[usize],
+        map: &mut [usize; 64],
+        free: u64,
+        degrees: &([usize; 64], [usize; 64]),
+        visit: &mut dyn FnMut(&[usize; 64]) -> bool,
+    ) -> bool {
+        let Some((&battery, rest)) = order.split_first() else {
+            return visit(map);
+        };
+        let labeled = a.iter().fold(0, |acc, t| acc | t) & !mask(rest);
+        for label in BitSet(free) {
+            if degrees.1[label] != degrees.0[battery] {
+                continue;
+            }
+            map[battery] = label;
+            let consistent = a
+                .iter()
+                .filter(|&&t| t & 1 << battery != 0 && t & !labeled == 0)
+                .all(|&t| b.contains(&BitSet(t).into_iter().fold(0, |acc, x| acc | 1 << map[x])));
+            if consistent && extend(a, b, rest, map, free & !(1 << label), degrees, visit) {
+                return true;
+            }
+        }
+        false
+    }
+
+    let b: BTreeSet<u64> = b.iter().copied().collect();
+    if a.len() != b.len() {
+        return false;
+    }
+    let degrees = |tries: &mut dyn Iterator<Item = &u64>| {
+        let mut degrees = [0; 64];
+        for &t in tries {
+            for battery in BitSet(t) {
+                degrees[battery] += 1;
+            }
+        }
+        degrees
+    };
+    let degrees = (degrees(&mut a.iter()), degrees(&mut b.iter()));
+    let mut order: Vec<usize> = BitSet(a.iter().fold(0, |acc, t| acc | t))
+        .into_iter()
+        .collect();
+    order.sort_by_key(|&battery| core::cmp::Reverse(degrees.0[battery]));
+    let free = b.iter().fold(0, |acc, t| acc | t);
+    if order.len() != free.count_ones() as usize {
+        return false;
+    }
+    extend(a, &b, &order, &mut [0; 64], free, &degrees, visit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    // A solution of the original puzzle, with the guaranteed batteries last.
+    fn original() -> Solution {
+        vec![
+            vec![0, 1],
+            vec![2, 3],
+            vec![2, 4],
+            vec![3, 4],
+            vec![0, 5],
+            vec![1, 5],
+            vec![6, 7],
+        ]
+    }
+
+    #[test]
+    fn finds_the_relabeling_between_isomorphic_solutions() {
+        let a = original();
+        let map = [7, 6, 0, 1, 2, 5, 4, 3];
+        let mut b: Solution = a
+            .iter()
+            .rev()
+            .map(|t| t.iter().map(|&x| map[x]).collect())
+            .collect();
+        b.swap(1, 4);
+        assert!(same_solution(&a, &b));
+        let found = isomorphism(&a, &b).expect("the solutions are isomorphic");
+        let identity: Vec<usize> = (0..8).collect();
+        assert_eq!(strategy_key(&a, &found), strategy_key(&b, &identity));
+    }
+
+    #[test]
+    fn tells_apart_solutions_that_are_not_isomorphic() {
+        let a = original();
+        // Same number of tries and batteries, but the triangle on 2, 3 and 4 became a path.
+        let mut b = a.clone();
+        b[3] = vec![4, 6];
+        assert!(!same_solution(&a, &b));
+        assert_eq!(isomorphism(&a, &b), None);
+        assert!(!same_solution(&a, &a[1..]));
+    }
+
+    #[test]
+    fn counts_automorphisms() {
+        // A triangle can be rotated and reflected onto itself.
+        let triangle = [0b011, 0b110, 0b101];
+        let mut automorphisms = 0;
+        relabelings(&triangle, &triangle, &mut |_| {
+            automorphisms += 1;
+            false
+        });
+        assert_eq!(automorphisms, 6);
+    }
+
+    #[test]
+    fn relabels_unused_batteries_in_order() {
+        let a = vec![vec![2, 3]];
+        let b = vec![vec![0, 1]];
+        assert_eq!(isomorphism(&a, &b), Some(vec![2, 3, 0, 1]));
+    }
+}
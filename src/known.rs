@@ -0,0 +1,154 @@
+//! The fewest tries known to always turn on the toy, to cross-check the search against. Such a
+//! strategy is a Turán system: a smallest family of `slots`-sets of the batteries (the tries)
+//! that every `good`-set (the universe) contains one of. Their sizes are known in general for a
+//! few families of puzzles, and for a few more small ones from the literature ([`KNOWN`]).
+//!
+//! ```
+//! use batteries::known::known_fewest_tries;
+//!
+//! assert_eq!(known_fewest_tries(8, 4, 2), Some(7));
+//! assert_eq!(known_fewest_tries(7, 4, 3), Some(12));
+//! assert_eq!(known_fewest_tries(9, 5, 3), None);
+//! ```
+
+use crate::binomial;
+
+/// The fewest tries for puzzles outside the families that [`known_fewest_tries`] knows in
+/// general, as `(total, good, slots, tries)`: the Turán numbers T(n, 4, 3).
+pub const KNOWN: &[(u64, u64, u64, u64)] = &[
+    (5, 4, 3, 3),
+    (6, 4, 3, 6),
+    (7, 4, 3, 12),
+    (8, 4, 3, 20),
+    (9, 4, 3, 30),
+    (10, 4, 3, 45),
+];
+
+/// The fewest tries that always turn on a toy needing `slots` functional batteries, when `good`
+/// of the `total` batteries are functional, if it is known:
+///
+/// - with all batteries functional, any try does;
+/// - when the toy takes as many batteries as are functional, every universe has to be tried;
+/// - with one slot, a battery of every universe has to be tried, i.e. `total - good + 1` of them;
+/// - with two slots, the tries are the edges of a graph on the batteries without an independent
+///   set of `good` of them, the fewest of which by Turán's theorem leave out the edges of the
+///   complete `good - 1`-partite graph with parts as equal as possible;
+/// - the puzzles in [`KNOWN`].
+pub fn known_fewest_tries(total: u64, good: u64, slots: u64) -> Option<u64> {
+    if !(1 <= slots && slots <= good && good <= total && total <= 64) {
+        return None;
+    }
+    if good == total {
+        return Some(1);
+    }
+    if slots == good {
+        return Some(binomial(total, good));
+    }
+    match slots {
+        1 => Some(total - good + 1),
+        2 => {
+            // The edges within the parts: `larger` parts of `size + 1` batteries and the others
+            // of `size`.
+            let parts = good - 1;
+            let (size, larger) = (total / parts, total % parts);
+            Some(larger * binomial(size + 1, 2) + (parts - larger) * binomial(size, 2))
+        }
+        _ => KNOWN
+            .iter()
+            .find(|&&(t, g, s, _)| (t, g, s) == (total, good, slots))
+            .map(|&(.., tries)| tries),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_the_original_puzzle_and_the_families() {
+        assert_eq!(known_fewest_tries(8, 4, 2), Some(7));
+        assert_eq!(known_fewest_tries(6, 6, 3), Some(1));
+        assert_eq!(known_fewest_tries(6, 3, 3), Some(20));
+        assert_eq!(known_fewest_tries(9, 4, 1), Some(6));
+        assert_eq!(known_fewest_tries(10, 3, 2), Some(20));
+        assert_eq!(known_fewest_tries(9, 5, 3), None);
+        assert_eq!(known_fewest_tries(4, 5, 2), None);
+    }
+
+    // Whether some `tries` tries always turn on the toy in the puzzle with `params`, or `None` if
+    // that takes too many candidates to find out in a test.
+    #[cfg(feature = "std")]
+    fn strategy_exists(params: crate::Params, tries: u64) -> Option<bool> {
+        use crate::{checked_binomial, validate, BitSet, CombinationIter, Params, Solver};
+
+        let pairs: Vec<BitSet> = CombinationIter::new(params.total, params.slots).collect();
+        if tries == 0 {
+            // Every puzzle has a universe, which no try turns on the toy in.
+            return Some(false);
+        }
+        if pairs.len() > 64 {
+            return None;
+        }
+        if tries < 3 {
+            // The search needs a free try between the first and the last one.
+            return Some(
+                CombinationIter::<u64>::new(pairs.len() as u64, tries)
+                    .map(|set| set.into_iter().map(|i| pairs[i]).collect::<Vec<_>>())
+                    .any(|set| {
+                        validate::is_valid_strategy(Params { tries, ..params }, &set).is_ok()
+                    }),
+            );
+        }
+        if checked_binomial(pairs.len() as u64 - 1, tries - 1).is_none_or(|n| n > 100_000) {
+            return None;
+        }
+        let mut found = false;
+        // Fixing the first try and skipping relabelings keeps the search exhaustive up to
+        // relabeling, so not finding a strategy proves that there is none.
+        Solver {
+            params: Params { tries, ..params },
+            anchor: Some(BitSet((1 << params.slots) - 1)),
+            first: true,
+            break_symmetry: true,
+            ..Solver::default()
+        }
+        .search(|_| found = true);
+        Some(found)
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn agrees_with_the_search_on_small_puzzles() {
+        let mut checked = 0;
+        for total in 1..=10 {
+            for good in 1..=total {
+                for slots in 1..=good {
+                    let Some(fewest) = known_fewest_tries(total, good, slots) else {
+                        continue;
+                    };
+                    let params = crate::Params {
+                        total,
+                        good,
+                        slots,
+                        tries: fewest,
+                    };
+                    if let Some(exists) = strategy_exists(params, fewest) {
+                        assert!(exists, "no strategy with {fewest} tries for {params:?}");
+                        checked += 1;
+                    }
+                    if let Some(exists) = strategy_exists(params, fewest - 1) {
+                        assert!(
+                            !exists,
+                            "a strategy with {} tries for {params:?}",
+                            fewest - 1
+                        );
+                    }
+                }
+            }
+        }
+        assert!(
+            checked > 40,
+            "only {checked} puzzles were small enough to check"
+        );
+    }
+}
@@ -0,0 +1,2020 @@
+//! Combinatorics and search for the following puzzle:
+//! You are given 8 batteries but only 4 of them are functional. You have a toy that needs 2
+//! functional batteries. You have 7 tries to turn on the toy.
+//!
+//! [`CombinationIter`] and friends enumerate subsets of batteries as [`BitSet`]s,
+//! [`remove_impossible_universes`] applies what a failed try tells us, and [`Solver`] searches
+//! for strategies that always turn on the toy, for these or any other numbers ([`Params`]).
+//! [`group_testing`] solves the puzzle as one configuration of combinatorial group testing, and
+//! [`adaptive`] finds decision trees for it and other identification puzzles.
+//! [`validate`] checks strategies and search states against the rules of the puzzle.
+//! [`isomorphism`] decides whether two solutions are the same up to relabeling batteries.
+//! [`certificate`] writes down and checks proofs that too few tries do not work.
+//! [`batch`] reads the files that list puzzles to run one after the other.
+//! [`optimize`] rewrites solutions to be easier to carry out by hand.
+//! [`known`] has the fewest tries known for small puzzles, to cross-check the search against.
+//! [`rpc`] answers JSON-RPC requests for them, one per line.
+//! [`trace`] is the format that searches are recorded in for replaying them.
+//! [`session`] saves games played with a real toy to resume them later.
+//! [`messages`] has the texts of the interactive commands in every language they speak.
+//! With the `evcxr` feature, [`evcxr`] shows them in evcxr notebooks.
+//!
+//! Everything but [`Solver`] works without the standard library, e.g. on embedded targets, with
+//! only `alloc`: turn off the default `std` feature.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::ops::{BitAnd, BitOr, BitXor, Not, Shl, Shr, Sub};
+
+pub mod adaptive;
+pub mod batch;
+pub mod certificate;
+#[cfg(feature = "evcxr")]
+pub mod evcxr;
+pub mod group_testing;
+pub mod isomorphism;
+#[cfg(feature = "known-values")]
+pub mod known;
+pub mod messages;
+pub mod optimize;
+#[cfg(feature = "std")]
+pub mod rpc;
+pub mod session;
+#[cfg(feature = "std")]
+mod solver;
+#[cfg(feature = "std")]
+pub mod trace;
+pub mod validate;
+
+#[cfg(feature = "std")]
+pub use solver::{
+    fewest_tries, SearchOutcome, SearchReport, Solutions, Solver, SolverError, TraceEvent,
+    TraceOutcome,
+};
+
+/// An unsigned integer used as the bits of a [`BitSet`]: `u64` (the default) or `u128`.
+pub trait Word:
+    Copy
+    + Default
+    + Eq
+    + Ord
+    + core::hash::Hash
+    + fmt::Debug
+    + fmt::Binary
+    + fmt::LowerHex
+    + fmt::UpperHex
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const BITS: u32;
+    fn count_ones(self) -> u32;
+    fn trailing_zeros(self) -> u32;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn wrapping_neg(self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+    fn checked_shr(self, shift: u32) -> Option<Self>;
+    fn from_str_radix(src: &str, radix: u32) -> Result<Self, core::num::ParseIntError>;
+}
+
+macro_rules! impl_word {
+    ($($t:ty)*) => {$(
+        impl Word for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+            const BITS: u32 = <$t>::BITS;
+            fn count_ones(self) -> u32 {
+                <$t>::count_ones(self)
+            }
+            fn trailing_zeros(self) -> u32 {
+                <$t>::trailing_zeros(self)
+            }
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$t>::checked_add(self, other)
+            }
+            fn wrapping_neg(self) -> Self {
+                <$t>::wrapping_neg(self)
+            }
+            fn wrapping_sub(self, other: Self) -> Self {
+                <$t>::wrapping_sub(self, other)
+            }
+            fn checked_shr(self, shift: u32) -> Option<Self> {
+                <$t>::checked_shr(self, shift)
+            }
+            fn from_str_radix(src: &str, radix: u32) -> Result<Self, core::num::ParseIntError> {
+                <$t>::from_str_radix(src, radix)
+            }
+        }
+    )*};
+}
+
+impl_word!(u64 u128);
+
+/// A small set for storing integers 0..W::BITS, by default 0..=63; bit i is set if i is in the
+/// set. Elements passed to its methods have to be in that range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BitSet<W: Word = u64>(pub W);
+
+impl<W: Word> From<W> for BitSet<W> {
+    fn from(val: W) -> Self {
+        BitSet(val)
+    }
+}
+
+impl<W: Word> BitAnd for BitSet<W> {
+    type Output = Self;
+    fn bitand(self, other: Self) -> Self::Output {
+        BitSet(self.0 & other.0)
+    }
+}
+
+impl<W: Word> BitOr for BitSet<W> {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self::Output {
+        BitSet(self.0 | other.0)
+    }
+}
+
+impl<W: Word> BitXor for BitSet<W> {
+    type Output = Self;
+    fn bitxor(self, other: Self) -> Self::Output {
+        BitSet(self.0 ^ other.0)
+    }
+}
+
+/// Set difference.
+impl<W: Word> Sub for BitSet<W> {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self::Output {
+        BitSet(self.0 & !other.0)
+    }
+}
+
+impl<W: Word> BitSet<W> {
+    /// Number of elements in the set.
+    pub fn len(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == W::ZERO
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.0 & W::ONE << i as u32 != W::ZERO
+    }
+
+    /// Adds `i` to the set and returns whether it was missing.
+    pub fn insert(&mut self, i: usize) -> bool {
+        let missing = !self.contains(i);
+        self.0 = self.0 | W::ONE << i as u32;
+        missing
+    }
+
+    /// Takes `i` out of the set and returns whether it was there.
+    pub fn remove(&mut self, i: usize) -> bool {
+        let present = self.contains(i);
+        self.0 = self.0 & !(W::ONE << i as u32);
+        present
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        self | other
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        self & other
+    }
+
+    pub fn difference(self, other: Self) -> Self {
+        self - other
+    }
+
+    pub fn symmetric_difference(self, other: Self) -> Self {
+        self ^ other
+    }
+
+    /// Whether every element of this set is in `other`.
+    pub fn is_subset(self, other: Self) -> bool {
+        self & other == self
+    }
+
+    pub fn is_superset(self, other: Self) -> bool {
+        other.is_subset(self)
+    }
+}
+
+impl<W: Word> FromIterator<usize> for BitSet<W> {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = BitSet::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<W: Word> Extend<usize> for BitSet<W> {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for i in iter {
+            self.insert(i);
+        }
+    }
+}
+
+/// The bits in binary, padded to the width if one is given (`{:08}` shows a set of 0..8 in fixed
+/// width), or with `{:#}` the elements in set notation, e.g. `{0,3,5}`. The bits can also be
+/// formatted like an integer with `{:b}`, `{:x}` and `{:X}`.
+impl<W: Word> fmt::Display for BitSet<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !f.alternate() {
+            return fmt::Binary::fmt(&self.0, f);
+        }
+        let elements: Vec<String> = self.into_iter().map(|e| e.to_string()).collect();
+        f.pad(&format!("{{{}}}", elements.join(",")))
+    }
+}
+
+impl<W: Word> fmt::Binary for BitSet<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Binary::fmt(&self.0, f)
+    }
+}
+
+impl<W: Word> fmt::LowerHex for BitSet<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl<W: Word> fmt::UpperHex for BitSet<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+/// Why a string is not a [`BitSet`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseBitSetError {
+    /// The string is neither set notation nor an integer.
+    Invalid(String),
+    /// An element in set notation is not below the number of bits in the word.
+    ElementTooLarge { element: usize, bits: u32 },
+}
+
+impl fmt::Display for ParseBitSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseBitSetError::Invalid(s) => write!(
+                f,
+                "invalid set `{s}`, expected elements like `{{0,3,5}}` or bits like `0b101001`, \
+                `0x29` or `41`"
+            ),
+            ParseBitSetError::ElementTooLarge { element, bits } => {
+                write!(f, "{element} is too large for a set of 0..{bits}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ParseBitSetError {}
+
+/// Parses the elements in set notation (`{0,3,5}`, like `{:#}` shows a set) or the bits as a
+/// binary (`0b101001`), hexadecimal (`0x29`) or decimal (`41`) integer.
+impl<W: Word> core::str::FromStr for BitSet<W> {
+    type Err = ParseBitSetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseBitSetError::Invalid(s.to_string());
+        let trimmed = s.trim();
+        if let Some(elements) = trimmed
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+        {
+            let mut set = BitSet::default();
+            for element in elements.split(',').map(str::trim) {
+                if element.is_empty() && elements.trim().is_empty() {
+                    break;
+                }
+                let element: usize = element.parse().map_err(|_| invalid())?;
+                if element >= W::BITS as usize {
+                    return Err(ParseBitSetError::ElementTooLarge {
+                        element,
+                        bits: W::BITS,
+                    });
+                }
+                set.insert(element);
+            }
+            return Ok(set);
+        }
+        let (digits, radix) = match trimmed.get(..2) {
+            Some("0b") => (&trimmed[2..], 2),
+            Some("0x") => (&trimmed[2..], 16),
+            _ => (trimmed, 10),
+        };
+        // `from_str_radix` would accept a sign.
+        if digits.starts_with(['+', '-']) {
+            return Err(invalid());
+        }
+        W::from_str_radix(digits, radix)
+            .map(BitSet)
+            .map_err(|_| invalid())
+    }
+}
+
+impl<W: Word> IntoIterator for BitSet<W> {
+    type Item = usize;
+    type IntoIter = BitSetIter<W>;
+    fn into_iter(self) -> Self::IntoIter {
+        BitSetIter(self.0)
+    }
+}
+
+/// The elements of a [`BitSet`] in increasing order.
+pub struct BitSetIter<W: Word = u64>(W);
+
+impl<W: Word> Iterator for BitSetIter<W> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == W::ZERO {
+            return None;
+        }
+        let v = self.0.trailing_zeros();
+        self.0 = self.0 ^ W::ONE << v;
+        Some(v as usize)
+    }
+}
+
+// Once all bits are consumed the state stays 0.
+impl<W: Word> FusedIterator for BitSetIter<W> {}
+
+/// Iterates over the k-subsets of 0..n in colexicographic order, using bit tricks. The subsets
+/// are [`BitSet`]s over `W`, so n can be up to 64 by default and up to 128 with `u128`.
+#[derive(Clone)]
+pub struct CombinationIter<W: Word = u64> {
+    next_val: W,
+    // The combination at rank end - 1, which `next_back` yields next.
+    last_val: W,
+    n: u64,
+    // Position of next_val in the enumeration and the position at which the enumeration stops.
+    rank: u64,
+    end: u64,
+    // For `of_set`, the set whose members the positions 0..n stand for.
+    members: Option<W>,
+}
+
+/// Why a [`CombinationIter`] cannot enumerate the k-subsets of 0..n.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CombinationError {
+    /// k is 0.
+    EmptySubsets,
+    /// k is larger than n.
+    NotEnoughElements { n: u64, k: u64 },
+    /// n is larger than the number of bits in the word.
+    TooManyElements { n: u64, bits: u32 },
+    /// C(n, k) does not fit into u64.
+    TooManySubsets { n: u64, k: u64 },
+}
+
+impl fmt::Display for CombinationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CombinationError::EmptySubsets => write!(f, "subsets must have at least one element"),
+            CombinationError::NotEnoughElements { n, k } => {
+                write!(f, "there are no {k}-element subsets of {n} elements")
+            }
+            CombinationError::TooManyElements { n, bits } => {
+                write!(f, "{n} elements do not fit into {bits} bits")
+            }
+            CombinationError::TooManySubsets { n, k } => {
+                write!(
+                    f,
+                    "there are too many {k}-element subsets of {n} elements to count"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for CombinationError {}
+
+impl<W: Word> CombinationIter<W> {
+    /// All k-subsets of 0..n, for 0 < k <= n <= W::BITS. Panics if that does not hold or if there
+    /// are more than fit into u64; see [`CombinationIter::try_new`].
+    pub fn new(n: u64, k: u64) -> Self {
+        Self::try_new(n, k).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`CombinationIter::new`], but returns why the subsets cannot be enumerated instead of
+    /// panicking.
+    pub fn try_new(n: u64, k: u64) -> Result<Self, CombinationError> {
+        if k == 0 {
+            return Err(CombinationError::EmptySubsets);
+        }
+        if k > n {
+            return Err(CombinationError::NotEnoughElements { n, k });
+        }
+        if n > u64::from(W::BITS) {
+            return Err(CombinationError::TooManyElements { n, bits: W::BITS });
+        }
+        let end = checked_binomial(n, k).ok_or(CombinationError::TooManySubsets { n, k })?;
+
+        let k_trailing_ones = match k == u64::from(W::BITS) {
+            true => !W::ZERO,
+            false => !(!W::ZERO << k as u32),
+        };
+
+        Ok(Self {
+            next_val: k_trailing_ones,
+            last_val: k_trailing_ones << (n - k) as u32,
+            n,
+            rank: 0,
+            end,
+            members: None,
+        })
+    }
+
+    /// All k-subsets of the members of `set`, for 0 < k <= set.len(), in the colexicographic
+    /// order of their positions in the set.
+    pub fn of_set(set: BitSet<W>, k: u64) -> Self {
+        Self {
+            members: Some(set.0),
+            ..Self::new(u64::from(set.len()), k)
+        }
+    }
+
+    // Maps a combination of positions 0..n to the set it stands for.
+    fn to_set(&self, val: W) -> BitSet<W> {
+        match self.members {
+            Some(members) => deposit_bits(val, members).into(),
+            None => val.into(),
+        }
+    }
+
+    fn k(&self) -> u64 {
+        u64::from(self.next_val.count_ones())
+    }
+
+    // The combination after `val`, or 0 after the last one.
+    //
+    // This uses bit tricks: to move from one combination to the next we identify the right-most
+    // cluster of ones and we shift the cluster's leading bit to the left by one and all other
+    // cluster's bits are shifted to least significant positions. For example:
+    // xxxx01110000 has cluster 111 and so next state is xxxx10000011
+    fn successor(val: W, n: u64) -> W {
+        // 1. Get least significant 1-bit (last bit of cluster)
+        let one_bit = val & val.wrapping_neg();
+
+        // 2. By adding the least significant 1-bit to current state we effectively turn all of
+        //    cluster's bits from 1s to 0s, except for the leftmost bit which gets shifted to the
+        //    left by one. If that bit is not within the rightmost N bits, then we ran out of
+        //    combinations. All the other cluster's bits will be moved to rightmost positions in
+        //    next step.
+        match val.checked_add(one_bit) {
+            // 3. x ^ val gives us the cluster of 1s with an extra 1 prepended. We shift if to the
+            //    right and lose 2 1-bits because the cluster was 1-bit larger, and also because we
+            //    only want to right shift all but the leftmost cluster's bit. When the cluster is a
+            //    single bit just below the top of the word, that shift is by all of its bits and
+            //    nothing is left to move.
+            Some(x) if n == u64::from(W::BITS) || x >> n as u32 == W::ZERO => {
+                let rest = (x ^ val).checked_shr(one_bit.trailing_zeros() + 2);
+                x | rest.unwrap_or(W::ZERO)
+            }
+            Some(_) | None => W::ZERO,
+        }
+    }
+
+    // The combination before `val`, which must not be the first one. Taking complements in 0..n
+    // reverses the colexicographic order (the largest element in which two combinations differ
+    // switches sides), so this is the complement of the successor of the complement.
+    fn predecessor(val: W, n: u64) -> W {
+        let full = match n == u64::from(W::BITS) {
+            true => !W::ZERO,
+            false => !(!W::ZERO << n as u32),
+        };
+        !Self::successor(!val & full, n) & full
+    }
+
+    /// Splits the remaining combinations into the ones with rank below `rank` and the rest. Ranks
+    /// count from the first combination of the full enumeration (not from the current position)
+    /// and are clamped to the remaining range, so splitting at multiples of C(n, k) / m shards the
+    /// enumeration into m independent iterators.
+    pub fn split_at(self, rank: u64) -> (Self, Self) {
+        let mid = rank.clamp(self.rank, self.end);
+        let next_val = if mid < self.end {
+            unrank(mid, self.n, self.k()).0
+        } else {
+            self.next_val
+        };
+        let last_val = if mid > self.rank {
+            unrank(mid - 1, self.n, self.k()).0
+        } else {
+            self.last_val
+        };
+        let first = Self {
+            end: mid,
+            last_val,
+            ..self
+        };
+        let second = Self {
+            next_val,
+            rank: mid,
+            ..self
+        };
+        (first, second)
+    }
+}
+
+/// Number of k-element subsets of an n-element set. For n <= 64 the result always fits into u64;
+/// panics if it does not.
+pub fn binomial(n: u64, k: u64) -> u64 {
+    checked_binomial(n, k).unwrap_or_else(|| panic!("C({n}, {k}) does not fit into u64"))
+}
+
+/// Like [`binomial`], but returns `None` if the result does not fit into u64.
+pub fn checked_binomial(n: u64, k: u64) -> Option<u64> {
+    if k > n {
+        return Some(0);
+    }
+    let k = k.min(n - k);
+    // Each partial product is itself a binomial coefficient, so the division is always exact. They
+    // grow up to the result, so once one does not fit into u64 neither does the result, and until
+    // then multiplying by n - i fits into u128.
+    let mut result: u128 = 1;
+    for i in 0..k {
+        result = result * u128::from(n - i) / u128::from(i + 1);
+        if result > u128::from(u64::MAX) {
+            return None;
+        }
+    }
+    Some(result as u64)
+}
+
+/// Position of `combination` in the enumeration order of [`CombinationIter`], which is the same
+/// for all n that the combination fits into.
+//
+// The iterator walks combinations in colexicographic order, so the combination with elements
+// c_1 < c_2 < ... < c_k has rank C(c_1, 1) + C(c_2, 2) + ... + C(c_k, k).
+pub fn rank<W: Word>(combination: BitSet<W>) -> u64 {
+    combination
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| binomial(c as u64, i as u64 + 1))
+        .sum()
+}
+
+/// Inverse of [`rank`]: returns the k-combination of 0..n that [`CombinationIter`] yields at
+/// position `rank`.
+//
+// We recover the elements greedily from the largest one down: c_k is the largest c with
+// C(c, k) <= rank, found by binary search, and so on with what is left of the rank.
+pub fn unrank<W: Word>(mut rank: u64, n: u64, k: u64) -> BitSet<W> {
+    let mut combination = BitSet::default();
+    let mut above = n;
+    for i in (1..=k).rev() {
+        // C(i - 1, i) = 0 <= rank, so the element is in i - 1..above.
+        let (mut low, mut high) = (i - 1, above);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            match checked_binomial(mid, i) {
+                Some(c) if c <= rank => low = mid,
+                _ => high = mid,
+            }
+        }
+        rank -= binomial(low, i);
+        combination.insert(low as usize);
+        above = low;
+    }
+    combination
+}
+
+// The initial value of next_val is 00...01..11 (k trailing 1s) and the iterator steps to the next
+// combination with `successor`.
+impl<W: Word> Iterator for CombinationIter<W> {
+    type Item = BitSet<W>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rank == self.end {
+            return None;
+        }
+
+        let val = self.next_val;
+        self.rank += 1;
+        self.next_val = Self::successor(val, self.n);
+        Some(self.to_set(val))
+    }
+
+    // The number of remaining combinations is known exactly from the rank, so neither of these
+    // has to run the bit tricks above.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.end - self.rank) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+
+    // Jump straight to the requested combination instead of stepping through all the skipped ones;
+    // `unrank` takes O(k log n) binomials.
+    fn nth(&mut self, m: usize) -> Option<Self::Item> {
+        let remaining = self.end - self.rank;
+        match u64::try_from(m) {
+            Ok(skip) if skip < remaining => {
+                self.rank += skip;
+                self.next_val = unrank(self.rank, self.n, self.k()).0;
+                self.next()
+            }
+            Ok(_) | Err(_) => {
+                self.rank = self.end;
+                None
+            }
+        }
+    }
+
+    fn count(self) -> usize {
+        (self.end - self.rank) as usize
+    }
+}
+
+// Once rank reaches end it never moves again (nth clamps to end as well).
+// Steps down from the combination with the largest elements, the 1..1 at the top of 0..n.
+impl<W: Word> DoubleEndedIterator for CombinationIter<W> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.rank == self.end {
+            return None;
+        }
+        let val = self.last_val;
+        self.end -= 1;
+        if self.end > self.rank {
+            self.last_val = Self::predecessor(val, self.n);
+        }
+        Some(self.to_set(val))
+    }
+
+    fn nth_back(&mut self, m: usize) -> Option<Self::Item> {
+        let remaining = self.end - self.rank;
+        match u64::try_from(m) {
+            Ok(skip) if skip < remaining => {
+                self.end -= skip;
+                self.last_val = unrank(self.end - 1, self.n, self.k()).0;
+                self.next_back()
+            }
+            Ok(_) | Err(_) => {
+                self.end = self.rank;
+                None
+            }
+        }
+    }
+}
+
+impl<W: Word> FusedIterator for CombinationIter<W> {}
+
+// The count always fits into u64, so it is exact wherever usize is that large.
+impl<W: Word> ExactSizeIterator for CombinationIter<W> {}
+
+/// Iterates over the k-subsets of 0..n in revolving-door order: each subset differs from the
+/// previous one by swapping a single element for one outside it, so `a ^ b` of consecutive
+/// subsets always has exactly two bits set.
+//
+// This is Algorithm R from Knuth's TAOCP 7.2.1.3, which keeps the elements sorted as
+// c[1] < ... < c[k] with the sentinel c[k + 1] = n (c[0] is unused).
+#[derive(Clone)]
+pub struct RevolvingDoorIter<W: Word = u64> {
+    c: Vec<u64>,
+    next_val: W,
+    remaining: u64,
+}
+
+impl<W: Word> RevolvingDoorIter<W> {
+    /// All k-subsets of 0..n, for 0 < k <= n <= W::BITS. Panics if that does not hold or if there
+    /// are more than fit into u64; see [`RevolvingDoorIter::try_new`].
+    pub fn new(n: u64, k: u64) -> Self {
+        Self::try_new(n, k).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`RevolvingDoorIter::new`], but returns why the subsets cannot be enumerated instead
+    /// of panicking.
+    pub fn try_new(n: u64, k: u64) -> Result<Self, CombinationError> {
+        let first = CombinationIter::<W>::try_new(n, k)?;
+        let mut c: Vec<u64> = (0..=k).map(|j| j.saturating_sub(1)).collect();
+        c.push(n);
+        Ok(Self {
+            c,
+            next_val: first.next_val,
+            remaining: first.end,
+        })
+    }
+
+    // Moves c to the next combination in the order (steps R3 to R5 of the algorithm).
+    fn advance(&mut self) {
+        let c = &mut self.c;
+        let k = c.len() - 2;
+        let mut j = 2;
+        // Whether to try decreasing c[j] (step R4) or increasing it (step R5).
+        let mut decrease = k % 2 == 1;
+        if decrease && c[1] + 1 < c[2] {
+            c[1] += 1;
+            return;
+        }
+        if !decrease && c[1] > 0 {
+            c[1] -= 1;
+            return;
+        }
+        while j <= k {
+            if decrease {
+                // Here c[j] = c[j - 1] + 1.
+                if c[j] >= j as u64 {
+                    c[j] = c[j - 1];
+                    c[j - 1] = j as u64 - 2;
+                    return;
+                }
+            } else {
+                // Here c[j - 1] = j - 2.
+                if c[j] + 1 < c[j + 1] {
+                    c[j - 1] = c[j];
+                    c[j] += 1;
+                    return;
+                }
+            }
+            j += 1;
+            decrease = !decrease;
+        }
+        unreachable!("advanced past the last combination");
+    }
+}
+
+impl<W: Word> Iterator for RevolvingDoorIter<W> {
+    type Item = BitSet<W>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let val = self.next_val;
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.advance();
+            let k = self.c.len() - 2;
+            self.next_val = self.c[1..=k]
+                .iter()
+                .fold(W::ZERO, |set, &x| set | W::ONE << x as u32);
+        }
+        Some(val.into())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl<W: Word> FusedIterator for RevolvingDoorIter<W> {}
+
+impl<W: Word> ExactSizeIterator for RevolvingDoorIter<W> {}
+
+/// Iterates over the k-permutations of 0..n (ordered sequences of k distinct elements) in
+/// lexicographic order, for k <= n <= 64. As an [`Iterator`] it allocates a `Vec` for each of
+/// them; [`PermutationIter::next_slice`] lends them from one buffer instead, for hot loops.
+#[derive(Clone)]
+pub struct PermutationIter {
+    n: u32,
+    // The permutation yielded last, or the first one before any was yielded.
+    current: Vec<usize>,
+    started: bool,
+    done: bool,
+}
+
+impl PermutationIter {
+    /// All k-permutations of 0..n, for k <= n <= 64. Panics if that does not hold; see
+    /// [`PermutationIter::try_new`].
+    pub fn new(n: u64, k: u64) -> Self {
+        Self::try_new(n, k).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`PermutationIter::new`], but returns why the permutations cannot be enumerated
+    /// instead of panicking.
+    pub fn try_new(n: u64, k: u64) -> Result<Self, CombinationError> {
+        if k > n {
+            return Err(CombinationError::NotEnoughElements { n, k });
+        }
+        if n > 64 {
+            return Err(CombinationError::TooManyElements { n, bits: 64 });
+        }
+        Ok(Self {
+            n: n as u32,
+            current: (0..k as usize).collect(),
+            started: false,
+            done: false,
+        })
+    }
+
+    /// The next permutation, like [`Iterator::next`], but in a buffer that is overwritten by the
+    /// next call instead of a `Vec` of its own.
+    pub fn next_slice(&mut self) -> Option<&[usize]> {
+        if self.started && !self.done {
+            self.done = !self.advance();
+        }
+        self.started = true;
+        (!self.done).then_some(&self.current[..])
+    }
+
+    // Turns the current permutation into the next one, if there is one. It keeps the longest
+    // prefix whose last element can be replaced by a larger one not used before it. That element
+    // becomes the smallest such one and the rest of the sequence is filled with the smallest
+    // unused elements in increasing order.
+    fn advance(&mut self) -> bool {
+        let full = BitSet(if self.n == 0 {
+            0
+        } else {
+            u64::MAX >> (64 - self.n)
+        });
+        let val = &mut self.current;
+        let mut used: BitSet = val.iter().copied().collect();
+        for i in (0..val.len()).rev() {
+            used.remove(val[i]);
+            let larger = (full - used).0 & (!0 << val[i] << 1);
+            if larger != 0 {
+                val[i] = larger.trailing_zeros() as usize;
+                used.insert(val[i]);
+                for (slot, unused) in val[i + 1..].iter_mut().zip(full - used) {
+                    *slot = unused;
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for PermutationIter {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_slice().map(<[usize]>::to_vec)
+    }
+}
+
+impl FusedIterator for PermutationIter {}
+
+/// Iterates over the k-multisets of 0..n (k elements of 0..n with repetition, "n multichoose k")
+/// in lexicographic order, each as its elements in non-decreasing order: for n = 3 and k = 2,
+/// `[0, 0]`, `[0, 1]`, `[0, 2]`, `[1, 1]`, `[1, 2]`, `[2, 2]`. There are C(n + k - 1, k) of them.
+/// Like [`PermutationIter`], [`MultisetIter::next_slice`] lends them without allocating.
+#[derive(Clone)]
+pub struct MultisetIter {
+    n: usize,
+    // The multiset yielded last, or the first one before any was yielded.
+    current: Vec<usize>,
+    started: bool,
+    remaining: u64,
+}
+
+impl MultisetIter {
+    /// All k-multisets of 0..n. Panics if there are more than fit into u64.
+    pub fn new(n: u64, k: u64) -> Self {
+        let remaining = match (n, k) {
+            (_, 0) => 1,
+            (0, _) => 0,
+            _ => binomial(n + k - 1, k),
+        };
+        Self {
+            n: n as usize,
+            current: vec![0; k as usize],
+            started: false,
+            remaining,
+        }
+    }
+
+    /// The next multiset, like [`Iterator::next`], but in a buffer that is overwritten by the
+    /// next call instead of a `Vec` of its own.
+    pub fn next_slice(&mut self) -> Option<&[usize]> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // The next multiset increments the last element that is not yet n - 1 and repeats it in
+        // all the positions after it, which is the smallest way to keep the sequence
+        // non-decreasing. The count says that there is one.
+        if self.started {
+            let i = self.current.iter().rposition(|&x| x + 1 < self.n);
+            let i = i.expect("the multisets left are larger");
+            let next = self.current[i] + 1;
+            self.current[i..].fill(next);
+        }
+        self.started = true;
+        self.remaining -= 1;
+        Some(&self.current)
+    }
+}
+
+impl Iterator for MultisetIter {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_slice().map(<[usize]>::to_vec)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl FusedIterator for MultisetIter {}
+
+impl ExactSizeIterator for MultisetIter {}
+
+/// Iterates over all subsets of a [`BitSet`], from the whole set down to the empty one, using
+/// the `(s - 1) & mask` trick.
+#[derive(Clone)]
+pub struct SubsetIter<W: Word = u64> {
+    mask: W,
+    next_val: Option<W>,
+}
+
+impl<W: Word> SubsetIter<W> {
+    pub fn new(set: BitSet<W>) -> Self {
+        Self {
+            mask: set.0,
+            next_val: Some(set.0),
+        }
+    }
+}
+
+impl<W: Word> Iterator for SubsetIter<W> {
+    type Item = BitSet<W>;
+
+    // Subtracting 1 clears the lowest set bit of s and sets all bits below it; masking keeps only
+    // the members of the set among them, which is the next smaller subset.
+    fn next(&mut self) -> Option<Self::Item> {
+        let val = self.next_val?;
+        self.next_val = match val == W::ZERO {
+            true => None,
+            false => Some(val.wrapping_sub(W::ONE) & self.mask),
+        };
+        Some(val.into())
+    }
+}
+
+impl<W: Word> FusedIterator for SubsetIter<W> {}
+
+/// Moves the i-th lowest bit of `bits` to the position of the i-th lowest set bit of `mask` (a
+/// software version of the BMI2 `pdep` instruction). This turns a combination of 0..mask.len()
+/// into a combination of the members of `mask`.
+pub fn deposit_bits<W: Word>(bits: W, mask: W) -> W {
+    let mut result = W::ZERO;
+    for (i, pos) in BitSetIter(mask).enumerate() {
+        if bits & W::ONE << i as u32 != W::ZERO {
+            result = result | W::ONE << pos as u32;
+        }
+    }
+    result
+}
+
+/// Iterates over ordered pairs (a, b) of disjoint k-subsets of 0..n.
+//
+// For each a, the second subset is enumerated as a k-combination of the n - k elements not in a.
+pub struct DisjointPairIter {
+    n: u64,
+    k: u64,
+    first: CombinationIter,
+    current: Option<(BitSet, CombinationIter)>,
+}
+
+impl DisjointPairIter {
+    /// All ordered pairs of disjoint k-subsets of 0..n, for 0 < k <= n <= 64. Panics if that does
+    /// not hold or if there are more k-subsets than fit into u64; see
+    /// [`DisjointPairIter::try_new`].
+    pub fn new(n: u64, k: u64) -> Self {
+        Self::try_new(n, k).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`DisjointPairIter::new`], but returns why the pairs cannot be enumerated instead of
+    /// panicking.
+    pub fn try_new(n: u64, k: u64) -> Result<Self, CombinationError> {
+        let mut first = CombinationIter::try_new(n, k)?;
+        if 2 * k > n {
+            // no two disjoint k-subsets fit into 0..n
+            first.rank = first.end;
+        }
+        Ok(Self {
+            n,
+            k,
+            first,
+            current: None,
+        })
+    }
+}
+
+impl Iterator for DisjointPairIter {
+    type Item = (BitSet, BitSet);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((a, rest)) = &mut self.current {
+                if let Some(b) = rest.next() {
+                    let full = u64::MAX >> (64 - self.n);
+                    return Some((*a, deposit_bits(b.0, full & !a.0).into()));
+                }
+            }
+            let a = self.first.next()?;
+            self.current = Some((a, CombinationIter::new(self.n - self.k, self.k)));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let per_first = binomial(self.n - self.k, self.k) as u128;
+        let in_current = self
+            .current
+            .as_ref()
+            .map_or(0, |(_, rest)| rest.end - rest.rank);
+        let remaining = (self.first.end - self.first.rank) as u128 * per_first + in_current as u128;
+        match usize::try_from(remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl FusedIterator for DisjointPairIter {}
+
+/// Iterates over the k-subsets of 0..n that contain all of `required` and none of `forbidden`.
+//
+// Instead of filtering CombinationIter after the fact, we enumerate combinations of the remaining
+// free elements and deposit them into the free positions, so no rejected combination is ever
+// generated.
+pub struct FilteredCombinationIter {
+    required: u64,
+    free: u64,
+    // None when there is nothing left to choose besides the required elements.
+    inner: Option<CombinationIter>,
+    required_only: bool,
+}
+
+impl FilteredCombinationIter {
+    /// The k-subsets of 0..n with all of `required` and none of `forbidden`, for k <= n <= 64.
+    /// Panics if that does not hold; see [`FilteredCombinationIter::try_new`].
+    pub fn new(n: u64, k: u64, required: BitSet, forbidden: BitSet) -> Self {
+        Self::try_new(n, k, required, forbidden).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`FilteredCombinationIter::new`], but returns why the subsets cannot be enumerated
+    /// instead of panicking.
+    pub fn try_new(
+        n: u64,
+        k: u64,
+        required: BitSet,
+        forbidden: BitSet,
+    ) -> Result<Self, CombinationError> {
+        if k > n {
+            return Err(CombinationError::NotEnoughElements { n, k });
+        }
+        if n > 64 {
+            return Err(CombinationError::TooManyElements { n, bits: 64 });
+        }
+
+        let full = if n == 0 { 0 } else { u64::MAX >> (64 - n) };
+        let free = full & !required.0 & !forbidden.0;
+        let mut iter = Self {
+            required: required.0,
+            free,
+            inner: None,
+            required_only: false,
+        };
+
+        let feasible = required.0 & (forbidden.0 | !full) == 0 && u64::from(required.len()) <= k;
+        if feasible {
+            let to_choose = k - u64::from(required.len());
+            let free_count = u64::from(free.count_ones());
+            if to_choose == 0 {
+                iter.required_only = true;
+            } else if to_choose <= free_count {
+                iter.inner = Some(CombinationIter::new(free_count, to_choose));
+            }
+        }
+        Ok(iter)
+    }
+}
+
+impl Iterator for FilteredCombinationIter {
+    type Item = BitSet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            Some(inner) => inner
+                .next()
+                .map(|c| (deposit_bits(c.0, self.free) | self.required).into()),
+            None if self.required_only => {
+                self.required_only = false;
+                Some(self.required.into())
+            }
+            None => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            Some(inner) => inner.size_hint(),
+            None => {
+                let remaining = usize::from(self.required_only);
+                (remaining, Some(remaining))
+            }
+        }
+    }
+}
+
+impl FusedIterator for FilteredCombinationIter {}
+
+/// A small seedable pseudo-random number generator (SplitMix64). It is only used for sampling, so
+/// statistical quality matters but unpredictability does not.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly random number in 0..bound.
+    //
+    // Values from the incomplete last "bucket" of size bound are rejected, otherwise small results
+    // would be slightly more likely.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        debug_assert!(bound > 0, "bound must be positive");
+        let limit = u64::MAX - u64::MAX % bound;
+        loop {
+            let x = self.next_u64();
+            if x < limit {
+                return x % bound;
+            }
+        }
+    }
+}
+
+/// Uniformly random k-subset of 0..n, for k <= n <= 64. Panics if that does not hold.
+//
+// Floyd's algorithm draws exactly k random numbers, whereas rejection sampling degrades badly when
+// k is close to n.
+pub fn random_combination(n: u64, k: u64, rng: &mut Rng) -> BitSet {
+    assert!(n >= k, "there are no {k}-element subsets of {n} elements");
+    assert!(n <= 64, "{n} elements do not fit into 64 bits");
+
+    let mut set = 0u64;
+    for j in n - k..n {
+        let t = rng.below(j + 1);
+        set |= if set & (1 << t) == 0 { 1 << t } else { 1 << j };
+    }
+    set.into()
+}
+
+/// Drops the universes (sets of functional batteries) in which `pair` would have turned on the
+/// toy, i.e. the ones that a failed try with `pair` rules out.
+pub fn remove_impossible_universes(pair: BitSet, mut universes: Vec<BitSet>) -> Vec<BitSet> {
+    let mut i = 0;
+    while i < universes.len() {
+        if pair.is_subset(universes[i]) {
+            // in this universe both batteries worked
+            universes.swap_remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    universes
+}
+
+/// Finds at most `tries` tries of `slots` batteries such that every universe in `universes`
+/// contains one of them, i.e. tries that are guaranteed to turn on the toy from this point on.
+//
+// Some try has to work in the first universe, so it's enough to branch over the tries made of its
+// batteries.
+pub fn cover(universes: &[BitSet], tries: usize, slots: u64) -> Option<Vec<BitSet>> {
+    let Some(&first) = universes.first() else {
+        return Some(vec![]);
+    };
+    if tries == 0 {
+        return None;
+    }
+    for pair in CombinationIter::new(u64::from(first.len()), slots) {
+        let pair = BitSet(deposit_bits(pair.0, first.0));
+        let rest = remove_impossible_universes(pair, universes.to_vec());
+        if let Some(mut rest_tries) = cover(&rest, tries - 1, slots) {
+            rest_tries.insert(0, pair);
+            return Some(rest_tries);
+        }
+    }
+    None
+}
+
+/// A universe together with the number of failed tries that would have turned on the toy in it,
+/// i.e. how many times the toy must have lied for this to be the real universe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NoisyUniverse {
+    pub universe: BitSet,
+    pub lies: u64,
+}
+
+/// Like [`remove_impossible_universes`] for a toy that may stay off up to `max_lies` times although
+/// its batteries work: a failed try with `pair` costs a lie in the universes where it works, and
+/// only the universes that would then need more than `max_lies` lies are ruled out.
+pub fn remove_impossible_noisy_universes(
+    pair: BitSet,
+    universes: Vec<NoisyUniverse>,
+    max_lies: u64,
+) -> Vec<NoisyUniverse> {
+    universes
+        .into_iter()
+        .filter_map(|u| match pair.is_subset(u.universe) {
+            false => Some(u),
+            true if u.lies < max_lies => Some(NoisyUniverse {
+                lies: u.lies + 1,
+                ..u
+            }),
+            true => None,
+        })
+        .collect()
+}
+
+/// Like [`cover`] for a toy that may stay off up to `max_lies` times although its batteries work:
+/// finds at most `tries` tries such that every universe contains more of them than the lies it has
+/// left, so the toy is guaranteed to turn on even if it lies every time it can.
+//
+// As in `cover`, some try has to work in the first universe. The tries can be made in any order,
+// so the search is over sets of tries and remembers the sets of universes it failed on. It gives
+// up once the remaining tries cannot cover all the lies left even if each works in as many
+// universes as any try does.
+pub fn noisy_cover(
+    universes: &[NoisyUniverse],
+    tries: usize,
+    slots: u64,
+    max_lies: u64,
+) -> Option<Vec<BitSet>> {
+    let mut universes = universes.to_vec();
+    universes.sort_unstable_by_key(|u| (u.universe.0, u.lies));
+    noisy_cover_from(universes, tries, slots, max_lies, &mut BTreeSet::new())
+}
+
+fn noisy_cover_from(
+    universes: Vec<NoisyUniverse>,
+    tries: usize,
+    slots: u64,
+    max_lies: u64,
+    failed: &mut BTreeSet<(Vec<NoisyUniverse>, usize)>,
+) -> Option<Vec<BitSet>> {
+    let Some(&first) = universes.first() else {
+        return Some(vec![]);
+    };
+    let needed: u64 = universes.iter().map(|u| max_lies - u.lies + 1).sum();
+    let batteries = universes
+        .iter()
+        .fold(BitSet::default(), |all, u| all | u.universe);
+    let most = CombinationIter::of_set(batteries, slots)
+        .map(|pair| {
+            let hits = universes.iter().filter(|u| pair.is_subset(u.universe));
+            hits.count() as u64
+        })
+        .max()
+        .unwrap_or(0);
+    if needed > most * tries as u64 || failed.contains(&(universes.clone(), tries)) {
+        return None;
+    }
+    for pair in CombinationIter::of_set(first.universe, slots) {
+        let mut rest = remove_impossible_noisy_universes(pair, universes.clone(), max_lies);
+        rest.sort_unstable_by_key(|u| (u.universe.0, u.lies));
+        if let Some(mut rest_tries) = noisy_cover_from(rest, tries - 1, slots, max_lies, failed) {
+            rest_tries.insert(0, pair);
+            return Some(rest_tries);
+        }
+    }
+    failed.insert((universes, tries));
+    None
+}
+
+/// A universe together with how likely it is, for puzzles where the number of functional
+/// batteries is not fixed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeightedUniverse {
+    pub universe: BitSet,
+    pub probability: f64,
+}
+
+/// Every set of the `total` (at most 63) batteries, with its probability if each battery is
+/// functional independently with probability `p`. Needs the `std` feature for `f64::powi`.
+#[cfg(feature = "std")]
+pub fn independent_universes(total: u64, p: f64) -> Vec<WeightedUniverse> {
+    SubsetIter::new(BitSet((1 << total) - 1))
+        .map(|universe| {
+            let good = i32::try_from(universe.len()).expect("at most 63 batteries");
+            WeightedUniverse {
+                universe,
+                probability: p.powi(good) * (1.0 - p).powi(total as i32 - good),
+            }
+        })
+        .collect()
+}
+
+/// Finds at most `tries` tries of `slots` batteries that turn on the toy with the highest
+/// probability over the weighted `universes`, and returns them with that probability. Unlike
+/// [`cover`] there may be no tries that always work, so every set of tries is a candidate; the
+/// tries are returned with the one most likely to work first.
+//
+// A branch and bound over sets of tries: a try can never turn on the toy in more of the universes
+// left once other tries have failed, so the remaining tries add at most the sum of the largest
+// probabilities with which the candidates work right now. Batteries that are interchangeable in
+// the universes and have not been tried yet are still interchangeable, so the tries that only
+// differ by them lead to the same probabilities and only one of them is branched on.
+pub fn likeliest_tries(
+    universes: &[WeightedUniverse],
+    tries: usize,
+    slots: u64,
+) -> (Vec<BitSet>, f64) {
+    let batteries = universes
+        .iter()
+        .fold(BitSet::default(), |all, u| all | u.universe);
+    let mut search = LikeliestTries {
+        classes: interchangeable_batteries(universes, batteries),
+        best: (vec![], 0.0),
+    };
+    let candidates: Vec<BitSet> = CombinationIter::of_set(batteries, slots).collect();
+    search.branch(&candidates, universes.to_vec(), tries, &mut vec![], 0.0);
+    search.best
+}
+
+// Splits `batteries` into classes such that swapping two batteries of a class maps the weighted
+// universes onto themselves.
+fn interchangeable_batteries(universes: &[WeightedUniverse], batteries: BitSet) -> Vec<BitSet> {
+    let weights: BTreeMap<u64, u64> = universes
+        .iter()
+        .map(|u| (u.universe.0, u.probability.to_bits()))
+        .collect();
+    let swap = |s: u64, i: usize, j: usize| {
+        let (bi, bj) = ((s >> i) & 1, (s >> j) & 1);
+        (s & !(1 << i) & !(1 << j)) | bi << j | bj << i
+    };
+    let mut classes: Vec<BitSet> = vec![];
+    for b in batteries {
+        let class = classes.iter_mut().find(|class| {
+            let a = class.into_iter().next().expect("classes are not empty");
+            universes
+                .iter()
+                .all(|u| weights.get(&swap(u.universe.0, a, b)) == Some(&u.probability.to_bits()))
+        });
+        match class {
+            Some(class) => {
+                class.insert(b);
+            }
+            None => classes.push(BitSet(1 << b)),
+        }
+    }
+    classes
+}
+
+struct LikeliestTries {
+    classes: Vec<BitSet>,
+    best: (Vec<BitSet>, f64),
+}
+
+impl LikeliestTries {
+    // Tries that only differ by swapping untried interchangeable batteries form an orbit,
+    // identified by the tried batteries and how many untried ones of each class they take.
+    fn orbit(&self, pair: BitSet, tried: BitSet) -> (BitSet, Vec<u32>) {
+        let untried = self
+            .classes
+            .iter()
+            .map(|&class| (pair & (class - tried)).len());
+        (pair & tried, untried.collect())
+    }
+
+    // Looks for the best tries that add at most `tries` of the `candidates` to `chosen`, which
+    // turn on the toy with `probability` and leave `universes`. The candidates are closed under
+    // swapping interchangeable batteries that are not in `chosen`.
+    fn branch(
+        &mut self,
+        candidates: &[BitSet],
+        universes: Vec<WeightedUniverse>,
+        tries: usize,
+        chosen: &mut Vec<BitSet>,
+        probability: f64,
+    ) {
+        if probability > self.best.1 {
+            self.best = (chosen.clone(), probability);
+        }
+        if tries == 0 {
+            return;
+        }
+        let tried = chosen.iter().fold(BitSet::default(), |all, &t| all | t);
+        let mut gains: Vec<(BitSet, f64)> = candidates
+            .iter()
+            .map(|&pair| {
+                let works = universes.iter().filter(|u| pair.is_subset(u.universe));
+                (pair, works.map(|u| u.probability).sum())
+            })
+            .filter(|&(_, gain)| gain > 0.0)
+            .collect();
+        gains.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        // Either some try of the first orbit is made, and by symmetry it can be the first one of
+        // them, or none of them is; then the same goes for the next orbit.
+        while let Some(&(pair, gain)) = gains.first() {
+            let bound: f64 = gains.iter().take(tries).map(|&(_, gain)| gain).sum();
+            if probability + bound <= self.best.1 {
+                break;
+            }
+            let key = self.orbit(pair, tried);
+            gains.retain(|&(other, _)| self.orbit(other, tried) != key);
+            let rest: Vec<WeightedUniverse> = universes
+                .iter()
+                .filter(|u| !pair.is_subset(u.universe))
+                .copied()
+                .collect();
+            let mut rest_candidates: Vec<BitSet> = gains.iter().map(|&(c, _)| c).collect();
+            rest_candidates.extend(
+                candidates
+                    .iter()
+                    .filter(|&&c| c != pair && self.orbit(c, tried) == key),
+            );
+            chosen.push(pair);
+            self.branch(
+                &rest_candidates,
+                rest,
+                tries - 1,
+                chosen,
+                probability + gain,
+            );
+            chosen.pop();
+        }
+    }
+}
+
+/// A game against a real toy: keeps track of the universes that are still possible as tries
+/// fail, and plans each next try so that the toy is still guaranteed to turn on in time.
+#[derive(Clone)]
+pub struct Game {
+    params: Params,
+    universes: Vec<BitSet>,
+    tries_left: u64,
+}
+
+impl Game {
+    pub fn new(params: Params) -> Self {
+        Game {
+            params,
+            universes: CombinationIter::new(params.total, params.good).collect(),
+            tries_left: params.tries,
+        }
+    }
+
+    /// The universes consistent with all the failed tries so far. If this is empty, the answers
+    /// contradict each other.
+    pub fn universes(&self) -> &[BitSet] {
+        &self.universes
+    }
+
+    pub fn tries_left(&self) -> u64 {
+        self.tries_left
+    }
+
+    /// A try after which the remaining tries can still cover every universe left, if any.
+    pub fn next_try(&self) -> Option<BitSet> {
+        cover(&self.universes, self.tries_left as usize, self.params.slots)?
+            .first()
+            .copied()
+    }
+
+    /// Records that `batteries` did not turn on the toy.
+    pub fn record_failure(&mut self, batteries: BitSet) {
+        self.universes =
+            remove_impossible_universes(batteries, core::mem::take(&mut self.universes));
+        self.tries_left = self.tries_left.saturating_sub(1);
+    }
+}
+
+/// How often a fixed sequence of tries turns on the toy, over the universes of a puzzle. The
+/// strategy does not have to be a solution: every one of its tries is made, and the success rate
+/// after each prefix of them is known.
+pub struct Evaluation {
+    /// Number of universes the tries were played in.
+    pub universes: u64,
+    /// For each try, in how many of the universes it is the first one that turns on the toy.
+    pub turned_on: Vec<u64>,
+    /// Whether every universe was played, rather than a random sample of them.
+    pub exact: bool,
+}
+
+impl Evaluation {
+    /// Plays the tries in every universe of the puzzle with `params`.
+    pub fn exact(params: Params, tries: &[BitSet]) -> Self {
+        let mut evaluation = Evaluation {
+            universes: 0,
+            turned_on: vec![0; tries.len()],
+            exact: true,
+        };
+        for universe in CombinationIter::new(params.total, params.good) {
+            evaluation.play(tries, universe);
+        }
+        evaluation
+    }
+
+    /// Plays the tries in `samples` universes drawn uniformly at random, for puzzles with too
+    /// many universes to go through.
+    pub fn sampled(params: Params, tries: &[BitSet], samples: u64, rng: &mut Rng) -> Self {
+        let mut evaluation = Evaluation {
+            universes: 0,
+            turned_on: vec![0; tries.len()],
+            exact: false,
+        };
+        for _ in 0..samples {
+            evaluation.play(tries, random_combination(params.total, params.good, rng));
+        }
+        evaluation
+    }
+
+    fn play(&mut self, tries: &[BitSet], universe: BitSet) {
+        self.universes += 1;
+        if let Some(i) = tries.iter().position(|&pair| pair.is_subset(universe)) {
+            self.turned_on[i] += 1;
+        }
+    }
+
+    /// Fraction of the universes in which one of the first `tries` tries turns on the toy.
+    pub fn success_rate(&self, tries: usize) -> f64 {
+        let successes: u64 = self.turned_on.iter().take(tries).sum();
+        successes as f64 / self.universes.max(1) as f64
+    }
+}
+
+/// Which tries rule out which universes in the puzzle with some [`Params`], built once so that
+/// searches can look it up instead of checking universes against tries over and over. The tries
+/// and the universes are numbered in [`CombinationIter`] order, i.e. by their [`rank`].
+pub struct EliminationTable {
+    params: Params,
+    pairs: Vec<BitSet>,
+    universes: Vec<BitSet>,
+    words: usize,
+    // For each try in turn, the set of universes that are still possible after it failed.
+    survivors: Vec<u128>,
+}
+
+impl EliminationTable {
+    pub fn new(params: Params) -> Self {
+        let pairs: Vec<BitSet> = CombinationIter::new(params.total, params.slots).collect();
+        let universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+        // Universe i is bit i % 128 of word i / 128 of a set of universes, so a failed try
+        // removes universes from a set with one AND per word, using the mask of the universes it
+        // survives.
+        let words = universes.len().div_ceil(128);
+        let mut survivors = vec![0u128; pairs.len() * words];
+        for (pair, mask) in pairs.iter().zip(survivors.chunks_mut(words)) {
+            for (i, &universe) in universes.iter().enumerate() {
+                if !pair.is_subset(universe) {
+                    mask[i / 128] |= 1 << (i % 128);
+                }
+            }
+        }
+        EliminationTable {
+            params,
+            pairs,
+            universes,
+            words,
+            survivors,
+        }
+    }
+
+    /// Whether this is the table for the puzzle with `params`, whatever its number of tries.
+    pub fn is_for(&self, params: Params) -> bool {
+        Params {
+            tries: self.params.tries,
+            ..params
+        } == self.params
+    }
+
+    pub fn pairs(&self) -> &[BitSet] {
+        &self.pairs
+    }
+
+    pub fn universes(&self) -> &[BitSet] {
+        &self.universes
+    }
+
+    /// The universes that are still possible after the try with index `pair` failed, with
+    /// universe i as bit i % 128 of word i / 128.
+    pub fn survivors(&self, pair: usize) -> &[u128] {
+        &self.survivors[pair * self.words..][..self.words]
+    }
+
+    /// Whether the try with index `pair` turns on the toy in the universe with index `universe`.
+    pub fn turns_on(&self, pair: usize, universe: usize) -> bool {
+        (self.survivors(pair)[universe / 128] >> (universe % 128)) & 1 == 0
+    }
+}
+
+/// The I-th (1-based) of M slices of the search space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u64,
+    pub count: u64,
+}
+
+/// The numbers in the puzzle: `good` of the `total` batteries are functional, the toy needs
+/// `slots` functional batteries and there are `tries` tries to turn it on. The default is the
+/// original 8, 4, 2 and 7. [`Solver`] handles up to 64 batteries and up to 128 possible tries,
+/// i.e. C(total, slots) <= 128.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Params {
+    pub total: u64,
+    pub good: u64,
+    pub slots: u64,
+    pub tries: u64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            total: 8,
+            good: 4,
+            slots: 2,
+            tries: 7,
+        }
+    }
+}
+
+/// A strategy as printed by the solver: the pairs to try, in order. The last entry holds every
+/// battery that is functional in all the universes the other tries leave, which can be more than
+/// the toy takes; any of them make the last try, see [`solution_tries`].
+pub type Solution = Vec<Vec<usize>>;
+
+/// The tries of a [`Solution`] for a toy with `slots` slots: the entries before the last one as
+/// they are, and the first `slots` of the batteries in the last entry as the last try.
+pub fn solution_tries(solution: &[Vec<usize>], slots: u64) -> Vec<BitSet> {
+    let last = solution.len().saturating_sub(1);
+    solution
+        .iter()
+        .enumerate()
+        .map(|(i, batteries)| {
+            let count = if i == last {
+                slots as usize
+            } else {
+                batteries.len()
+            };
+            batteries.iter().take(count).copied().collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every k-subset of 0..n, in colexicographic order, which for masks is numeric order.
+    fn brute_force_combinations(n: u64, k: u64) -> Vec<BitSet> {
+        (0..1u64 << n)
+            .filter(|mask| u64::from(mask.count_ones()) == k)
+            .map(BitSet)
+            .collect()
+    }
+
+    #[test]
+    fn combinations_match_brute_force() {
+        for n in 1..=10 {
+            for k in 1..=n {
+                let expected = brute_force_combinations(n, k);
+                let iter = CombinationIter::new(n, k);
+                assert_eq!(iter.len(), expected.len(), "C({n}, {k})");
+                assert_eq!(iter.collect::<Vec<_>>(), expected, "C({n}, {k})");
+
+                let mut reversed: Vec<BitSet> = CombinationIter::new(n, k).rev().collect();
+                reversed.reverse();
+                assert_eq!(reversed, expected, "C({n}, {k}) reversed");
+
+                for (i, &combination) in expected.iter().enumerate() {
+                    assert_eq!(rank(combination), i as u64);
+                    assert_eq!(unrank::<u64>(i as u64, n, k), combination);
+                    assert_eq!(CombinationIter::new(n, k).nth(i), Some(combination));
+                    assert_eq!(
+                        CombinationIter::new(n, k).nth_back(expected.len() - 1 - i),
+                        Some(combination)
+                    );
+                }
+                assert_eq!(CombinationIter::<u64>::new(n, k).nth(expected.len()), None);
+                assert_eq!(
+                    CombinationIter::<u64>::new(n, k).nth_back(expected.len()),
+                    None
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn combinations_mix_both_ends_and_splits() {
+        let expected = brute_force_combinations(9, 4);
+        let mut iter = CombinationIter::new(9, 4);
+        assert_eq!(iter.next(), Some(expected[0]));
+        assert_eq!(iter.next_back(), Some(expected[expected.len() - 1]));
+        assert_eq!(iter.nth(3), Some(expected[4]));
+        assert_eq!(iter.nth_back(5), Some(expected[expected.len() - 7]));
+        assert_eq!(iter.len(), expected.len() - 12);
+        assert_eq!(iter.collect::<Vec<_>>(), expected[5..expected.len() - 7]);
+
+        for mid in [0, 1, 50, 125, 126, 200] {
+            let (low, high) = CombinationIter::new(9, 4).split_at(mid);
+            let mid = (mid as usize).min(expected.len());
+            assert_eq!(low.collect::<Vec<_>>(), expected[..mid]);
+            assert_eq!(high.rev().collect::<Vec<_>>(), {
+                let mut tail = expected[mid..].to_vec();
+                tail.reverse();
+                tail
+            });
+        }
+    }
+
+    #[test]
+    fn iterators_stay_done_once_they_are_done() {
+        let set: BitSet = BitSet(0b1011);
+        let mut bits = set.into_iter();
+        assert_eq!(bits.by_ref().count(), 3);
+        let mut combinations: CombinationIter = CombinationIter::new(5, 2);
+        assert_eq!(combinations.by_ref().count(), 10);
+        for _ in 0..3 {
+            assert_eq!(bits.next(), None);
+            assert_eq!(combinations.next(), None);
+        }
+        // Skipping past the end does not start over either.
+        assert_eq!(combinations.nth(3), None);
+        assert_eq!(combinations.next(), None);
+    }
+
+    #[test]
+    fn combinations_of_a_set_are_its_subsets() {
+        let set = BitSet(0b1011_0110_0101);
+        let expected: Vec<BitSet> = brute_force_combinations(12, 3)
+            .into_iter()
+            .filter(|c| c.is_subset(set))
+            .collect();
+        assert_eq!(
+            CombinationIter::of_set(set, 3).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn combinations_reject_what_they_cannot_enumerate() {
+        assert_eq!(
+            CombinationIter::<u64>::try_new(5, 0).err(),
+            Some(CombinationError::EmptySubsets)
+        );
+        assert_eq!(
+            CombinationIter::<u64>::try_new(3, 4).err(),
+            Some(CombinationError::NotEnoughElements { n: 3, k: 4 })
+        );
+        assert_eq!(
+            CombinationIter::<u64>::try_new(65, 2).err(),
+            Some(CombinationError::TooManyElements { n: 65, bits: 64 })
+        );
+        assert_eq!(
+            CombinationIter::<u128>::try_new(128, 64).err(),
+            Some(CombinationError::TooManySubsets { n: 128, k: 64 })
+        );
+    }
+
+    #[test]
+    fn bit_sets_behave_like_sets() {
+        let a: BitSet = [0, 2, 5, 63].into_iter().collect();
+        let b: BitSet = [2, 3, 63].into_iter().collect();
+        assert_eq!(a.len(), 4);
+        assert!(a.contains(63) && !a.contains(1));
+        assert_eq!(a.union(b), a | b);
+        assert_eq!(a | b, [0, 2, 3, 5, 63].into_iter().collect());
+        assert_eq!(a & b, [2, 63].into_iter().collect());
+        assert_eq!(a.intersection(b), a & b);
+        assert_eq!(a - b, [0, 5].into_iter().collect());
+        assert_eq!(a.difference(b), a - b);
+        assert_eq!(a ^ b, [0, 3, 5].into_iter().collect());
+        assert_eq!(a.symmetric_difference(b), a ^ b);
+        assert!((a & b).is_subset(a) && a.is_superset(a & b) && !a.is_subset(b));
+        assert_eq!(a.into_iter().collect::<Vec<_>>(), [0, 2, 5, 63]);
+
+        let mut c = BitSet::<u128>::default();
+        assert!(c.is_empty());
+        assert!(c.insert(127) && !c.insert(127));
+        assert!(c.remove(127) && !c.remove(127));
+        assert!(c.is_empty());
+
+        assert_eq!(format!("{:08}", BitSet(0b101u64)), "00000101");
+        assert_eq!(format!("{a:#}"), "{0,2,5,63}");
+        assert_eq!("{0, 2, 5, 63}".parse::<BitSet>(), Ok(a));
+        assert_eq!("0b101".parse::<BitSet>(), Ok(BitSet(5)));
+        assert_eq!(
+            "{64}".parse::<BitSet>(),
+            Err(ParseBitSetError::ElementTooLarge {
+                element: 64,
+                bits: 64
+            })
+        );
+    }
+
+    #[test]
+    fn combinations_reach_the_top_bit_of_the_word() {
+        let singletons: Vec<BitSet> = CombinationIter::new(64, 1).collect();
+        assert_eq!(singletons.len(), 64);
+        assert_eq!(singletons.last(), Some(&BitSet(1 << 63)));
+        assert!(singletons.iter().all(|s| s.len() == 1));
+
+        let singletons: Vec<BitSet<u128>> = CombinationIter::new(128, 1).collect();
+        assert_eq!(singletons.len(), 128);
+        assert_eq!(singletons.last(), Some(&BitSet(1 << 127)));
+        assert!(singletons.iter().all(|s| s.len() == 1));
+
+        let pairs: Vec<BitSet> = CombinationIter::new(64, 2).collect();
+        assert_eq!(pairs.len() as u64, binomial(64, 2));
+        assert_eq!(pairs.last(), Some(&BitSet(0b11 << 62)));
+        assert!(pairs.iter().all(|s| s.len() == 2));
+        assert!(pairs.windows(2).all(|w| rank(w[0]) + 1 == rank(w[1])));
+
+        let pairs: Vec<BitSet<u128>> = CombinationIter::new(128, 2).collect();
+        assert_eq!(pairs.len() as u64, binomial(128, 2));
+        assert_eq!(pairs.last(), Some(&BitSet(0b11 << 126)));
+        assert!(pairs.iter().all(|s| s.len() == 2));
+    }
+
+    #[test]
+    fn iterators_reject_what_they_cannot_enumerate() {
+        assert_eq!(
+            RevolvingDoorIter::<u64>::try_new(4, 0).err(),
+            Some(CombinationError::EmptySubsets)
+        );
+        assert_eq!(
+            RevolvingDoorIter::<u64>::try_new(65, 1).err(),
+            Some(CombinationError::TooManyElements { n: 65, bits: 64 })
+        );
+        assert_eq!(
+            PermutationIter::try_new(3, 4).err(),
+            Some(CombinationError::NotEnoughElements { n: 3, k: 4 })
+        );
+        assert_eq!(
+            PermutationIter::try_new(65, 1).err(),
+            Some(CombinationError::TooManyElements { n: 65, bits: 64 })
+        );
+        let none = BitSet::default();
+        assert_eq!(
+            FilteredCombinationIter::try_new(2, 3, none, none).err(),
+            Some(CombinationError::NotEnoughElements { n: 2, k: 3 })
+        );
+        assert_eq!(
+            DisjointPairIter::try_new(0, 0).err(),
+            Some(CombinationError::EmptySubsets)
+        );
+        assert_eq!(DisjointPairIter::new(5, 3).count(), 0);
+
+        // A subset that fills the whole word.
+        let door: Vec<BitSet> = RevolvingDoorIter::new(64, 64).collect();
+        assert_eq!(door, [BitSet(u64::MAX)]);
+        assert_eq!(PermutationIter::new(64, 1).count(), 64);
+        // The empty sequence is the only one of no elements.
+        assert_eq!(PermutationIter::new(3, 0).collect::<Vec<_>>(), [vec![]]);
+        assert_eq!(MultisetIter::new(0, 0).collect::<Vec<_>>(), [vec![]]);
+        assert_eq!(MultisetIter::new(0, 2).next_slice(), None);
+    }
+
+    #[test]
+    fn random_iterators_match_brute_force() {
+        let mut rng = Rng::new(305);
+        for _ in 0..300 {
+            let n = 1 + rng.below(9);
+            let k = 1 + rng.below(n);
+            let expected = brute_force_combinations(n, k);
+
+            let mut door: Vec<BitSet> = RevolvingDoorIter::new(n, k).collect();
+            assert!(door.windows(2).all(|w| (w[0] ^ w[1]).len() == 2));
+            door.sort_unstable();
+            assert_eq!(door, expected, "revolving door C({n}, {k})");
+
+            let permutations: Vec<Vec<usize>> = PermutationIter::new(n, k).collect();
+            let count = (n - k + 1..=n).product::<u64>();
+            assert_eq!(permutations.len() as u64, count, "P({n}, {k})");
+            assert!(permutations.windows(2).all(|w| w[0] < w[1]));
+            for permutation in &permutations {
+                let set: BitSet = permutation.iter().copied().collect();
+                assert_eq!(u64::from(set.len()), k);
+                assert!(permutation.iter().all(|&e| (e as u64) < n));
+            }
+
+            let mut lent = PermutationIter::new(n, k);
+            for permutation in &permutations {
+                assert_eq!(lent.next_slice(), Some(&permutation[..]));
+            }
+            assert_eq!(lent.next_slice(), None);
+
+            let multisets: Vec<Vec<usize>> = MultisetIter::new(n, k).collect();
+            let mut lent = MultisetIter::new(n, k);
+            for multiset in &multisets {
+                assert_eq!(lent.next_slice(), Some(&multiset[..]));
+            }
+            assert_eq!(lent.next_slice(), None);
+            assert_eq!(multisets.len() as u64, binomial(n + k - 1, k));
+            assert!(multisets.windows(2).all(|w| w[0] < w[1]));
+            assert!(multisets
+                .iter()
+                .all(|m| m.windows(2).all(|w| w[0] <= w[1]) && m.iter().all(|&e| (e as u64) < n)));
+
+            let full = (1u64 << n) - 1;
+            let required = BitSet(rng.next_u64() & rng.next_u64() & full);
+            let forbidden = BitSet(rng.next_u64() & rng.next_u64() & full) - required;
+            let filtered: Vec<BitSet> = expected
+                .iter()
+                .copied()
+                .filter(|&c| required.is_subset(c) && (c & forbidden).is_empty())
+                .collect();
+            let iter = FilteredCombinationIter::new(n, k, required, forbidden);
+            assert_eq!(iter.size_hint().0, filtered.len());
+            assert_eq!(iter.collect::<Vec<_>>(), filtered);
+
+            let set = BitSet(rng.next_u64() & full);
+            let subsets: Vec<BitSet> = SubsetIter::new(set).collect();
+            assert_eq!(subsets.len(), 1 << set.len());
+            assert!(subsets.windows(2).all(|w| w[0] > w[1]));
+            assert!(subsets.iter().all(|s| s.is_subset(set)));
+
+            let disjoint: Vec<(BitSet, BitSet)> = DisjointPairIter::new(n, k).collect();
+            let pairs: Vec<(BitSet, BitSet)> = expected
+                .iter()
+                .flat_map(|&a| expected.iter().map(move |&b| (a, b)))
+                .filter(|&(a, b)| (a & b).is_empty())
+                .collect();
+            assert_eq!(DisjointPairIter::new(n, k).size_hint().0, pairs.len());
+            assert_eq!(disjoint, pairs);
+
+            let combination = random_combination(n, k, &mut rng);
+            assert!(expected.contains(&combination));
+        }
+    }
+
+    #[test]
+    fn random_covers_match_brute_force() {
+        let mut rng = Rng::new(305);
+        let mut checked = 0;
+        while checked < 100 {
+            let total = 1 + rng.below(7);
+            let good = 1 + rng.below(total);
+            let slots = 1 + rng.below(good);
+            let pairs: Vec<BitSet> = CombinationIter::new(total, slots).collect();
+            if pairs.len() > 16 {
+                continue;
+            }
+            checked += 1;
+            let universes: Vec<BitSet> = CombinationIter::new(total, good).collect();
+            // For each universe, the set of indices of the tries that turn on the toy in it.
+            let turned_on_by: Vec<u64> = universes
+                .iter()
+                .map(|&u| {
+                    (0..pairs.len())
+                        .filter(|&p| pairs[p].is_subset(u))
+                        .fold(0, |m, p| m | 1 << p)
+                })
+                .collect();
+            let fewest = (0..1u64 << pairs.len())
+                .filter(|&tries| turned_on_by.iter().all(|&on| on & tries != 0))
+                .map(u64::count_ones)
+                .min()
+                .expect("all tries together always turn on the toy")
+                as usize;
+
+            let found = cover(&universes, fewest, slots).expect("the fewest tries are enough");
+            assert!(found.len() <= fewest);
+            let params = Params {
+                total,
+                good,
+                slots,
+                tries: fewest as u64,
+            };
+            assert_eq!(validate::is_valid_strategy(params, &found), Ok(()));
+            if fewest > 0 {
+                assert_eq!(
+                    cover(&universes, fewest - 1, slots),
+                    None,
+                    "{total} {good} {slots}"
+                );
+            }
+        }
+    }
+}
@@ -1,212 +1,5154 @@
-// This code solves the following puzzle:
-// You are given 8 batteries but only 4 of them are functional. You have a toy that needs 2
-// functional batteries. You have 7 tries to turn on the toy.
+// Command line interface of the battery puzzle solver; see the library for the puzzle itself.
 
-use std::fmt;
-use std::ops::BitAnd;
+use batteries::adaptive::{
+    adaptive, adaptive_expected, BatteryPuzzle, CoinPuzzle, CountingBatteryPuzzle, DecisionTree,
+    Puzzle,
+};
+use batteries::batch::parse_batch;
+use batteries::certificate::{self, Certificate, Flaw};
+use batteries::group_testing::{class_selections, Goal, GroupTesting};
+use batteries::isomorphism::{isomorphism, relabelings, same_solution, strategy_key};
+use batteries::messages::{Language, Message};
+use batteries::optimize::{minimize, minimize_swaps, pareto_front};
+use batteries::rpc;
+use batteries::session::Session;
+use batteries::trace;
+use batteries::validate::{self, Violation};
+use batteries::{
+    binomial, checked_binomial, cover, fewest_tries, independent_universes, likeliest_tries,
+    noisy_cover, random_combination, remove_impossible_universes, solution_tries, unrank, BitSet,
+    CombinationIter, EliminationTable, Evaluation, Game, NoisyUniverse, Params, Rng, SearchOutcome,
+    SearchReport, Shard, Solution, Solver, TraceEvent, TraceOutcome,
+};
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-// A small set for storing integers 0..=63
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct BitSet(u64);
+// How often `--progress` says how far the search got.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
 
-impl From<u64> for BitSet {
-    fn from(val: u64) -> BitSet {
-        BitSet(val)
+// Everything printed goes through `write_stdout` instead of the std macros, which panic once
+// stdout is closed, e.g. by `batteries --all | head -1`. The reader is gone then, so the program
+// stops quietly with a clean exit instead.
+macro_rules! print {
+    ($($arg:tt)*) => {
+        write_stdout(format_args!($($arg)*))
+    };
+}
+
+macro_rules! println {
+    () => {
+        write_stdout(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        write_stdout(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+fn write_stdout(args: std::fmt::Arguments) {
+    if let Err(err) = std::io::Write::write_fmt(&mut std::io::stdout(), args) {
+        if err.kind() == std::io::ErrorKind::BrokenPipe {
+            std::process::exit(0);
+        }
+        panic!("failed printing to stdout: {err}");
+    }
+}
+
+const USAGE: &str = "\
+Usage: batteries [solve] [OPTIONS]
+       batteries [solve] --batch FILE [--jobs N]
+       batteries --stdio [--metrics ADDRESS]
+       batteries --socket PATH [--metrics ADDRESS]
+       batteries merge FILE...
+       batteries graphml [FILE]
+       batteries dump RANK [--anchor A,B | --no-wlog] [--break-symmetry]
+       batteries classify [--anchor A,B | --no-wlog]
+       batteries isomorphic FILE FILE
+       batteries verify FILE [--total N] [--good N] [--slots N] [--tries N]
+       batteries evaluate FILE [--total N] [--good N] [--slots N] [--tries N] [--trials N]
+       batteries count [--total N] [--good N] [--slots N] [--tries N] [--anchor A,B | --no-wlog]
+       batteries analyze [--total N] [--good N] [--slots N] [--tries N]
+       batteries frontier [--total N] [--good N] [--slots N] [--check-known]
+       batteries bench [--total N] [--good N] [--slots N] [--tries N] [--anchor A,B | --no-wlog]
+       batteries quiz [--lang L]
+       batteries play [--adversarial | --session FILE] [--tries N] [--lang L]
+       batteries simulate FILE... [--failure-rate P] [--trials N | --trace] [--seed S]
+       batteries replay FILE [--summary | --csv] [--outcome O]
+       batteries puzzle battery|coins|group-testing
+
+Commands:
+  solve         find all strategies that always turn on the toy (the default)
+  merge         combine the output of several `--shard` runs (\"-\" reads stdin)
+  graphml       write the graph of which tries eliminate which universes as GraphML
+                (to stdout if no FILE is given)
+  classify      count the solutions up to relabeling batteries, with the smallest
+                solution found in each class as its representative
+  isomorphic    decide whether two strategies (files holding a solution line as printed
+                by the solver) are the same up to relabeling batteries and reordering
+                tries; exits with 1 if they are not
+  verify        check that the strategy in a file (a solution line or a decision tree as
+                printed by `--adaptive`) turns on the toy within `--tries` tries in every
+                universe, printing a universe where it fails and exiting with 1 otherwise;
+                a certificate written with `--certificate` is checked instead
+  evaluate      print in what fraction of the universes the first `--tries` tries of the
+                strategy in a file turn on the toy, also when that is not all of them;
+                puzzles with more than a million universes are sampled
+  count         search like `solve` without printing the solutions: count them, the ones that
+                are unique up to relabeling batteries and the candidates examined and pruned
+  analyze       print the size of the puzzle and simple bounds on the number of tries it
+                needs, without searching
+  frontier      find the fewest tries that always turn on the toy for every puzzle with up
+                to `--total` batteries and up to `--good` functional ones, by a binary
+                search between the bounds of `analyze`, and print them as a table; with
+                `--time-limit` the ones not found in time are shown as ranges
+  bench         time the phases of the search (enumerating the tries and the universes,
+                building the table of which tries rule out which universes, and going
+                through the candidates), each repeated for about a second, on one thread
+                unless `--threads` is given
+  quiz          practice: propose the tries yourself and learn whether each one still
+                leaves a way to always turn on the toy
+  play          play against the real toy: get told which batteries to try, answer whether
+                the toy turned on, and get the next try until it does; with `--adversarial`
+                propose the tries yourself against a toy that stays off as long as it can
+  simulate      play the strategies in the given files on a flaky toy that sometimes stays
+                off even with two functional batteries, and compare how often they succeed
+  puzzle        solve the battery puzzle, the 12 coins puzzle or a group testing puzzle
+                with the general adaptive engine and print the decision tree
+  dump          show the solver state for the candidate tries with the given rank, for
+                debugging the search
+  replay        print the search trace recorded with `--trace FILE`: each candidate examined
+                or range of candidates pruned, with the tries, the outcome and how many
+                universes were left
+
+Options:
+  --batch FILE  run each `[[puzzle]]` of a TOML file as its own command, e.g.
+                  format = \"json\"        # options for all puzzles come first
+                  [[puzzle]]
+                  total = 9              # --total 9
+                  tries = 6
+                  break_symmetry = true  # --break-symmetry
+                  command = \"count\"      # instead of `solve`
+                  output = \"9-6.txt\"     # instead of stdout
+  --jobs N      with `--batch`, run N puzzles at the same time (default: 1)
+  --stdio       answer JSON-RPC 2.0 requests, one per line of stdin, with one response per
+                line of stdout: `solve`, `verify` (of a `strategy` as `solve` returns it),
+                `recommend-next-test` and `apply-outcome` (with the `try` and whether it
+                `turned_on`), all taking `total`, `good`, `slots` and `tries` as parameters
+  --socket PATH answer the requests of `--stdio` on a unix socket created at PATH, one
+                connection at a time, keeping the tables `solve` builds between them; every
+                connection starts without a game
+  --metrics ADDRESS
+                with `--stdio` or `--socket`: serve counters of the requests, the strategies
+                found and the reuse of tables, and a histogram of how long `solve` takes, at
+                http://ADDRESS/metrics for Prometheus, e.g. `--metrics 127.0.0.1:9100`
+  --total N     number of batteries (default: 8)
+  --good N      number of functional batteries (default: 4)
+  --slots N     number of functional batteries the toy needs (default: 2)
+  --tries N     number of tries to turn on the toy (default: 7)
+  --anchor A,B  assume WLOG that the first try is batteries A and B, one for each slot
+                (default: the first `--slots` batteries, 0,1); the try can also be given
+                as a set like `{A,B}` or as bits like `0b11`
+  --no-wlog     do not fix the first try; search over all of them instead
+  --break-symmetry
+                also skip the candidates that are relabelings of others, so that far fewer
+                are searched; most relabelings of each solution are then not found
+  --verify-wlog check that the unanchored search finds exactly the relabelings of the
+                anchored solutions
+  --groups S,.. the batteries come in interchangeable packs of the given sizes (e.g. 4,4
+                for 0-3 and 4-7); print one solution per way to use the packs, naming the
+                batteries A1, A2, ..., B1, ... by pack
+  --first       stop at the first strategy found and print only that one
+  --min-tries   search with fewer and fewer tries, down from `--tries`, until no strategy is
+                left, and print one strategy for each number of tries that works
+  --certificate FILE
+                with `--min-tries`: write a certificate that the most tries found to be too
+                few really are to FILE, which `verify FILE` checks without searching
+  --check-known with `frontier` or `--min-tries`: compare the fewest tries found with the
+                ones known for small puzzles, and report any difference as a bug
+  --adaptive    search for a decision tree of tries instead, where each try may depend on
+                how the earlier ones went, with the fewest tries in the worst case
+  --expected    with `--adaptive`: find the decision tree with the fewest tries on average
+                instead, with all universes equally likely, among the ones within `--tries`
+                in the worst case
+  --counting    with `--adaptive`: each try tells how many of its batteries are functional,
+                like a battery tester, rather than only whether the toy turns on
+  --lies L      the toy may stay off up to L times although its batteries work; find the
+                fewest tries, up to `--tries`, that turn it on even then
+  --probability P
+                instead of exactly `--good` of them, each battery is functional on its own
+                with probability P; find the `--tries` tries most likely to turn on the toy
+  --engine E    `exhaustive` (default) enumerates all solutions, `anneal` looks for a
+                single strategy by simulated annealing, `portfolio` runs several search
+                orders and heuristics at once and stops at the first strategy found
+  --shard I/M   only search the I-th of M equally sized slices of the candidate tries
+  --threads N   search on N threads (default: one per CPU)
+  --time-limit T
+                stop searching after T (e.g. 30s, 2m) and report what was found so far
+  --heartbeat T write a one-line JSON status record to stderr every T
+  --progress    write how much of the search is done, about how long the rest will take
+                and how many strategies were found so far to stderr every few seconds
+  --unique      print one solution per class of solutions that are the same up to relabeling
+                batteries, and how many solutions found each class has (the default)
+  --all         print every solution found, including relabelings of each other
+  --sample N    print a uniform random sample of N solutions instead of all unique ones
+  --max-solutions N
+                for `solve` without `--sample` and for `classify`: stop the search once
+                N solutions (or classes of them) are held, to bound the memory they take,
+                with a warning that there may be more (default: 1000000)
+  --sort-by M   order the printed solutions by `lex` (lexicographic), `handling`
+                (fewest battery insertions), `diversity` (most distinct batteries
+                tried) or `guaranteed` (most batteries guaranteed to work at the end)
+  --format F    print solutions as `text` (default), as a `report` with each try on its
+                own line and the guaranteed last try highlighted, as step-by-step
+                `instructions` where the batteries are called A, B, C, ..., as a written
+                `proof` (`proof-md` for Markdown) that the strategy always works, or as one
+                `json` document with the tries and guaranteed batteries of each solution
+  --no-color    print the `report` without colors, which are otherwise used on terminals
+                unless NO_COLOR is set
+  --plain       print ASCII text only, without colors, e.g. for screen readers and dumb
+                terminals
+  --explain     after each printed solution, list the universes each failed try rules out
+                and how many are left, and why the last try then always works
+  --dot FILE    also write the printed strategies (or the `--adaptive` decision tree) to
+                FILE as Graphviz decision trees, with an edge for each outcome of a try
+  --pareto      instead of the solutions, print the orderings of their tries that are
+                Pareto-optimal for worst-case and expected tries, the most insertions of
+                a single battery and the total number of insertions
+  --adversarial with `play`: make the tries yourself, against an adversary that picks the
+                functional batteries as late as possible and keeps the toy off whenever
+                some choice of them still allows it, to test your strategy's worst case;
+                the game is lost once `--tries` tries failed
+  --session FILE
+                with `play`: save the game to FILE after every answer, and resume the game
+                saved there, with its puzzle, if FILE exists
+  --lang L      for `quiz` and `play`: talk in `en` (English) or `de` (German); by default
+                in the language of LC_ALL, LC_MESSAGES or LANG if it is one of them, or else
+                in English
+  --labels L,.. name the batteries in the output of `solve`, `quiz` and `play`, e.g.
+                `--labels red,green,blue,...`, with a label for each battery;
+                `--labels @FILE` reads them from a file, one per line
+  --min-swaps   reorder the tries of each solution to need as few battery swaps as
+                possible; each try is printed in slot order
+  --minimize    drop the tries of each solution that the other tries make redundant, so
+                that none of the tries left can be dropped, before printing it
+  --failure-rate P
+                for `simulate`: probability that the toy stays off although both batteries
+                work (default: 0.1)
+  --trials N    for `simulate`: number of games played with each strategy, for `evaluate`:
+                number of universes sampled instead of trying all of them (default: 100000)
+  --trace       for `simulate`: play a single game with secretly chosen functional batteries
+                instead, printing each try and whether the toy turned on
+  --trace FILE  for `solve` and `count`: record what the search does with each candidate,
+                and why it prunes the ones it prunes, to FILE in a compact binary format
+                for `replay`
+  --summary     for `replay`: count the candidates by outcome instead of listing them
+  --csv         for `replay`: list the trace as CSV
+  --outcome O   for `replay`: only show `repeats-anchor`, `not-canonical` or `always-on`
+                (the reasons for pruning), `solution` or `failed` candidates
+  --seed S      seed for `--sample`, `simulate`, `--engine anneal` and `--engine portfolio`
+                (defaults to a time-based seed)
+  --quiet       for `solve`: print only the solutions, one per line, or the `--format json`
+                document, and leave the outcome to the exit status
+  -h, --help    print this help
+
+Exit status:
+  0             a strategy was found, or a command other than `solve` succeeded, or the
+                reader of the output went away (e.g. `batteries --all | head -1`)
+  1             an error, or a check failed (`verify`, `isomorphic`, `--verify-wlog`)
+  2             the search proved that there is no strategy (with `--shard`, in that shard)
+  3             the search stopped at the time limit, or `--engine anneal` gave up, before
+                finding a strategy";
+
+#[derive(Default)]
+enum Command {
+    #[default]
+    Solve,
+    Merge {
+        files: Vec<String>,
+    },
+    Graphml {
+        file: Option<String>,
+    },
+    Dump {
+        rank: u64,
+    },
+    Classify,
+    Quiz,
+    Play,
+    Simulate {
+        files: Vec<String>,
+    },
+    Puzzle {
+        name: String,
+    },
+    Isomorphic {
+        a: String,
+        b: String,
+    },
+    Evaluate {
+        file: String,
+    },
+    Verify {
+        file: String,
+    },
+    Analyze,
+    Count,
+    Frontier,
+    Bench,
+    Replay {
+        file: String,
+    },
+}
+
+#[derive(Default, PartialEq)]
+enum Engine {
+    #[default]
+    Exhaustive,
+    Anneal,
+    Portfolio,
+}
+
+#[derive(Default)]
+enum Format {
+    #[default]
+    Text,
+    Report,
+    Instructions,
+    Proof,
+    ProofMarkdown,
+    Json,
+}
+
+// Order in which solutions are printed; see `sort_solutions`.
+#[derive(Clone, Copy)]
+enum SortBy {
+    Lexicographic,
+    Handling,
+    Diversity,
+    Guaranteed,
+}
+
+#[derive(Default)]
+struct Options {
+    command: Command,
+    help: bool,
+    anchor: Option<BitSet>,
+    no_wlog: bool,
+    verify_wlog: bool,
+    engine: Engine,
+    shard: Option<Shard>,
+    threads: Option<NonZeroUsize>,
+    time_limit: Option<Duration>,
+    heartbeat: Option<Duration>,
+    progress: bool,
+    sample: Option<usize>,
+    seed: Option<u64>,
+    sort_by: Option<SortBy>,
+    max_solutions: Option<NonZeroUsize>,
+    min_swaps: bool,
+    minimize: bool,
+    labels: Labels,
+    pareto: bool,
+    all: bool,
+    unique: bool,
+    format: Format,
+    no_color: bool,
+    plain: bool,
+    failure_rate: Option<f64>,
+    trials: Option<u64>,
+    trace: bool,
+    explain: bool,
+    groups: Option<Vec<usize>>,
+    params: Params,
+    adaptive: bool,
+    expected: bool,
+    counting: bool,
+    adversarial: bool,
+    session: Option<String>,
+    trace_file: Option<String>,
+    summary: bool,
+    csv: bool,
+    outcome: Option<TraceOutcome>,
+    quiet: bool,
+    lies: Option<u64>,
+    probability: Option<f64>,
+    min_tries: bool,
+    check_known: bool,
+    first: bool,
+    break_symmetry: bool,
+    dot: Option<String>,
+    certificate: Option<String>,
+    batch: Option<String>,
+    jobs: Option<NonZeroUsize>,
+    stdio: bool,
+    socket: Option<String>,
+    metrics: Option<String>,
+    language: Option<Language>,
+}
+
+impl Options {
+    // The first try fixed by the WLOG assumption, if any.
+    fn anchor_or_default(&self) -> Option<BitSet> {
+        match self.anchor {
+            _ if self.no_wlog => None,
+            Some(anchor) => Some(anchor),
+            // A single try is the last one, so there is no first try to fix.
+            None if self.params.tries < 2 => None,
+            None => Some(BitSet((1 << self.params.slots) - 1)),
+        }
+    }
+
+    // The exhaustive search that `solve` and `count` run.
+    fn solver(&self) -> Solver<'static> {
+        Solver {
+            params: self.params,
+            anchor: self.anchor_or_default(),
+            shard: self.shard,
+            deadline: self.time_limit.map(|limit| Instant::now() + limit),
+            heartbeat: self.heartbeat,
+            progress: self.progress.then_some(PROGRESS_INTERVAL),
+            cancel: None,
+            first: self.first,
+            table: None,
+            break_symmetry: self.break_symmetry,
+            threads: self
+                .threads
+                .or_else(|| std::thread::available_parallelism().ok()),
+            trace: None,
+        }
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    fn value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+        let value = value.ok_or_else(|| format!("`{flag}` needs a value"))?;
+        value
+            .parse()
+            .map_err(|_| format!("invalid value `{value}` for `{flag}`"))
+    }
+
+    let mut options = Options::default();
+    let mut args = args.peekable();
+    let command = args.next_if(|arg| {
+        [
+            "merge",
+            "graphml",
+            "dump",
+            "classify",
+            "isomorphic",
+            "verify",
+            "evaluate",
+            "analyze",
+            "count",
+            "frontier",
+            "bench",
+            "quiz",
+            "simulate",
+            "puzzle",
+            "solve",
+            "play",
+            "replay",
+        ]
+        .contains(&arg.as_str())
+    });
+    let mut positional = vec![];
+    // The batteries of `--anchor`, which can only be parsed once `--slots` is known.
+    let mut anchor = None;
+    // Whether there are arguments that `--batch` leaves to the file, and any besides `--stdio`
+    // or `--socket` and their `--metrics`.
+    let mut per_puzzle = false;
+    let mut besides_stdio = command.is_some();
+    let mut besides_socket = command.is_some();
+
+    while let Some(arg) = args.next() {
+        per_puzzle |= !matches!(arg.as_str(), "--batch" | "--jobs");
+        besides_stdio |= !matches!(arg.as_str(), "--stdio" | "--metrics");
+        besides_socket |= !matches!(arg.as_str(), "--socket" | "--metrics");
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "--anchor" => anchor = Some(value::<String>(&arg, args.next())?),
+            "--no-wlog" => options.no_wlog = true,
+            "--verify-wlog" => options.verify_wlog = true,
+            "--engine" => {
+                options.engine = match value::<String>(&arg, args.next())?.as_str() {
+                    "exhaustive" => Engine::Exhaustive,
+                    "anneal" => Engine::Anneal,
+                    "portfolio" => Engine::Portfolio,
+                    other => return Err(format!("unknown engine `{other}`")),
+                }
+            }
+            "--adaptive" => options.adaptive = true,
+            "--expected" => options.expected = true,
+            "--counting" => options.counting = true,
+            "--adversarial" => options.adversarial = true,
+            "--session" => options.session = Some(value(&arg, args.next())?),
+            "--lies" => options.lies = Some(value(&arg, args.next())?),
+            "--probability" => options.probability = Some(value(&arg, args.next())?),
+            "--groups" => {
+                let value: String = value(&arg, args.next())?;
+                options.groups = Some(parse_groups(&value)?);
+            }
+            "--shard" => {
+                let value: String = value(&arg, args.next())?;
+                options.shard = Some(parse_shard(&value)?);
+            }
+            "--time-limit" => {
+                let value: String = value(&arg, args.next())?;
+                options.time_limit = Some(parse_duration(&value)?);
+            }
+            "--heartbeat" => {
+                let value: String = value(&arg, args.next())?;
+                options.heartbeat = Some(parse_duration(&value)?);
+            }
+            "--threads" => options.threads = Some(value(&arg, args.next())?),
+            "--sample" => options.sample = Some(value(&arg, args.next())?),
+            "--max-solutions" => options.max_solutions = Some(value(&arg, args.next())?),
+            "--dot" => options.dot = Some(value(&arg, args.next())?),
+            "--certificate" => options.certificate = Some(value(&arg, args.next())?),
+            "--seed" => options.seed = Some(value(&arg, args.next())?),
+            "--batch" => options.batch = Some(value(&arg, args.next())?),
+            "--stdio" => options.stdio = true,
+            "--socket" => options.socket = Some(value(&arg, args.next())?),
+            "--metrics" => options.metrics = Some(value(&arg, args.next())?),
+            "--jobs" => options.jobs = Some(value(&arg, args.next())?),
+            "--total" => options.params.total = value(&arg, args.next())?,
+            "--good" => options.params.good = value(&arg, args.next())?,
+            "--slots" => options.params.slots = value(&arg, args.next())?,
+            "--tries" => options.params.tries = value(&arg, args.next())?,
+            "--failure-rate" => options.failure_rate = Some(value(&arg, args.next())?),
+            "--trials" => options.trials = Some(value(&arg, args.next())?),
+            "--trace" if command.as_deref() == Some("simulate") => options.trace = true,
+            "--trace" => options.trace_file = Some(value(&arg, args.next())?),
+            "--summary" => options.summary = true,
+            "--quiet" => options.quiet = true,
+            "--csv" => options.csv = true,
+            "--outcome" => {
+                let value: String = value(&arg, args.next())?;
+                options.outcome = Some(
+                    trace::OUTCOMES
+                        .into_iter()
+                        .find(|&outcome| outcome_name(outcome) == value)
+                        .ok_or_else(|| format!("unknown outcome `{value}`"))?,
+                )
+            }
+            "--explain" => options.explain = true,
+            "--no-color" => options.no_color = true,
+            "--plain" => options.plain = true,
+            "--min-swaps" => options.min_swaps = true,
+            "--minimize" => options.minimize = true,
+            "--lang" => {
+                let value: String = value(&arg, args.next())?;
+                options.language =
+                    Some(Language::parse(&value).ok_or(format!("unknown language `{value}`"))?);
+            }
+            "--labels" => {
+                let value: String = value(&arg, args.next())?;
+                options.labels = Labels(Some(parse_labels(&value)?));
+            }
+            "--pareto" => options.pareto = true,
+            "--all" => options.all = true,
+            "--min-tries" => options.min_tries = true,
+            "--check-known" => options.check_known = true,
+            "--first" => options.first = true,
+            "--progress" => options.progress = true,
+            "--break-symmetry" => options.break_symmetry = true,
+            "--unique" => options.unique = true,
+            "--format" => {
+                options.format = match value::<String>(&arg, args.next())?.as_str() {
+                    "text" => Format::Text,
+                    "report" => Format::Report,
+                    "instructions" => Format::Instructions,
+                    "proof" => Format::Proof,
+                    "proof-md" => Format::ProofMarkdown,
+                    "json" => Format::Json,
+                    other => return Err(format!("unknown format `{other}`")),
+                }
+            }
+            "--sort-by" => {
+                options.sort_by = Some(match value::<String>(&arg, args.next())?.as_str() {
+                    "lex" => SortBy::Lexicographic,
+                    "handling" => SortBy::Handling,
+                    "diversity" => SortBy::Diversity,
+                    "guaranteed" => SortBy::Guaranteed,
+                    other => return Err(format!("unknown sort metric `{other}`")),
+                })
+            }
+            _ if command.is_some() && (arg == "-" || !arg.starts_with('-')) => positional.push(arg),
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+    if options.help {
+        return Ok(options);
+    }
+    if options.batch.is_some() {
+        if cfg!(target_os = "wasi") {
+            return Err(
+                "`--batch` runs each puzzle as a process of its own, which WASI cannot start"
+                    .to_string(),
+            );
+        }
+        if per_puzzle || command.is_some_and(|command| command != "solve") {
+            return Err(
+                "`--batch` reads the options of each puzzle from the file, so it can only be \
+                combined with `--jobs`"
+                    .to_string(),
+            );
+        }
+        return Ok(options);
+    }
+    if options.jobs.is_some() {
+        return Err("`--jobs` needs `--batch`".to_string());
+    }
+    if options.metrics.is_some() && !options.stdio && options.socket.is_none() {
+        return Err("`--metrics` needs `--stdio` or `--socket`".to_string());
+    }
+    if options.metrics.is_some() && cfg!(target_os = "wasi") {
+        return Err(
+            "`--metrics` serves the metrics on a thread, which WASI does not have".to_string(),
+        );
+    }
+    if options.stdio {
+        if besides_stdio {
+            return Err(
+                "`--stdio` takes everything from the requests, so it takes no other arguments \
+                but `--metrics`"
+                    .to_string(),
+            );
+        }
+        return Ok(options);
+    }
+    if options.socket.is_some() {
+        if besides_socket {
+            return Err(
+                "`--socket` takes everything from the requests, so it takes no other arguments \
+                but `--metrics`"
+                    .to_string(),
+            );
+        }
+        return Ok(options);
+    }
+    options.command = match command.as_deref() {
+        None => Command::Solve,
+        Some("solve") if !positional.is_empty() => {
+            return Err("`solve` takes no arguments".to_string())
+        }
+        Some("solve") => Command::Solve,
+        Some("merge") if positional.is_empty() => {
+            return Err("`merge` needs at least one file".to_string())
+        }
+        Some("merge") => Command::Merge { files: positional },
+        Some("graphml") if positional.len() > 1 => {
+            return Err("`graphml` takes at most one file".to_string())
+        }
+        Some("graphml") => Command::Graphml {
+            file: positional.pop(),
+        },
+        Some("classify") if !positional.is_empty() => {
+            return Err("`classify` takes no arguments".to_string())
+        }
+        Some("classify") => Command::Classify,
+        Some("analyze") if !positional.is_empty() => {
+            return Err("`analyze` takes no arguments".to_string())
+        }
+        Some("analyze") => Command::Analyze,
+        Some("count") if !positional.is_empty() => {
+            return Err("`count` takes no arguments".to_string())
+        }
+        Some("count") => Command::Count,
+        Some("frontier") if !positional.is_empty() => {
+            return Err("`frontier` takes no arguments".to_string())
+        }
+        Some("frontier") => Command::Frontier,
+        Some("bench") if !positional.is_empty() => {
+            return Err("`bench` takes no arguments".to_string())
+        }
+        Some("bench") => Command::Bench,
+        Some("quiz") if !positional.is_empty() => {
+            return Err("`quiz` takes no arguments".to_string())
+        }
+        Some("quiz") => Command::Quiz,
+        Some("play") if !positional.is_empty() => {
+            return Err("`play` takes no arguments".to_string())
+        }
+        Some("play") => Command::Play,
+        Some("simulate") if positional.is_empty() => {
+            return Err("`simulate` needs at least one strategy file".to_string())
+        }
+        Some("simulate") => Command::Simulate { files: positional },
+        Some("puzzle") => match <[String; 1]>::try_from(positional) {
+            Ok([name]) => Command::Puzzle { name },
+            Err(_) => return Err("`puzzle` needs exactly one puzzle name".to_string()),
+        },
+        Some("isomorphic") => match <[String; 2]>::try_from(positional) {
+            Ok([a, b]) => Command::Isomorphic { a, b },
+            Err(_) => return Err("`isomorphic` needs exactly two strategy files".to_string()),
+        },
+        Some("evaluate") => match <[String; 1]>::try_from(positional) {
+            Ok([file]) => Command::Evaluate { file },
+            Err(_) => return Err("`evaluate` needs exactly one strategy file".to_string()),
+        },
+        Some("verify") => match <[String; 1]>::try_from(positional) {
+            Ok([file]) => Command::Verify { file },
+            Err(_) => return Err("`verify` needs exactly one strategy file".to_string()),
+        },
+        Some("replay") => match <[String; 1]>::try_from(positional) {
+            Ok([file]) => Command::Replay { file },
+            Err(_) => return Err("`replay` needs exactly one trace file".to_string()),
+        },
+        Some(_) => match &positional[..] {
+            [rank] => Command::Dump {
+                rank: rank
+                    .parse()
+                    .map_err(|_| format!("invalid candidate rank `{rank}`"))?,
+            },
+            _ => return Err("`dump` needs exactly one candidate rank".to_string()),
+        },
+    };
+    if let Some(p) = options.probability {
+        if options.params.good != Params::default().good {
+            return Err("`--probability` replaces `--good`".to_string());
+        }
+        if !(0.0..=1.0).contains(&p) {
+            return Err("`--probability` must be between 0 and 1".to_string());
+        }
+        // Every battery may be functional, and the search goes through all sets of them.
+        if options.params.total > 20 {
+            return Err("`--probability` supports up to 20 batteries".to_string());
+        }
+        options.params.good = options.params.total;
+    }
+    let Params {
+        total,
+        good,
+        slots,
+        tries,
+    } = options.params;
+    if !(1 <= slots && slots <= good && good <= total && total <= 64) {
+        return Err("the puzzle needs 1 <= `--slots` <= `--good` <= `--total` <= 64".to_string());
+    }
+    if let Some(anchor) = anchor {
+        options.anchor =
+            Some(parse_try(&anchor, slots).map_err(|err| format!("invalid `--anchor`: {err}"))?);
+    }
+    if options.check_known {
+        if !cfg!(feature = "known-values") {
+            return Err("`--check-known` needs the `known-values` feature".to_string());
+        }
+        let min_tries = matches!(options.command, Command::Solve)
+            && options.min_tries
+            && options.lies.is_none()
+            && options.probability.is_none();
+        if !matches!(options.command, Command::Frontier) && !min_tries {
+            return Err("`--check-known` only applies to `frontier` and `--min-tries`".to_string());
+        }
+    }
+    if matches!(options.command, Command::Frontier) {
+        if tries != Params::default().tries {
+            return Err(
+                "`frontier` finds the fewest tries itself, so it takes no `--tries`".into(),
+            );
+        }
+        if binomial(total, slots) > 128 {
+            return Err(format!(
+                "`frontier` supports up to 128 possible tries, not the {} that `--total` and \
+                `--slots` give",
+                binomial(total, slots)
+            ));
+        }
+        if options.anchor.is_some()
+            || options.no_wlog
+            || options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.min_tries
+            || options.lies.is_some()
+            || options.probability.is_some()
+            || options.shard.is_some()
+            || options.heartbeat.is_some()
+            || options.progress
+            || options.sample.is_some()
+            || options.all
+            || options.unique
+            || options.first
+            || options.break_symmetry
+            || options.explain
+            || options.pareto
+            || options.min_swaps
+            || options.minimize
+            || options.sort_by.is_some()
+            || options.dot.is_some()
+            || !matches!(options.format, Format::Text)
+        {
+            return Err(
+                "`frontier` only supports `--total`, `--good`, `--slots`, `--threads`, \
+                `--time-limit` and `--check-known`"
+                    .to_string(),
+            );
+        }
+        return Ok(options);
+    }
+    // The search takes a try for the anchor, if there is one, besides the last one (see
+    // `Solver::check`), though never more than there are.
+    let pairs = binomial(total, slots);
+    let fewest = match options.command {
+        Command::Evaluate { .. } | Command::Verify { .. } => 1,
+        _ if options.anchor.is_some() => 2,
+        _ => 1,
+    }
+    .min(pairs);
+    if tries < fewest || tries > pairs {
+        return Err(format!(
+            "`--tries` must be between {fewest} and the {pairs} possible tries"
+        ));
+    }
+    if (options.anchor.is_some() || options.verify_wlog) && tries < 2 {
+        return Err(
+            "`--anchor` and `--verify-wlog` fix the first try, so they take at least 2 tries"
+                .to_string(),
+        );
+    }
+    if matches!(
+        options.command,
+        Command::Solve | Command::Count | Command::Bench | Command::Dump { .. } | Command::Classify
+    ) && !options.adaptive
+        && options.engine != Engine::Anneal
+    {
+        // The candidates are counted in u64 and are sets of at most 128 pair indices.
+        if pairs > 128 {
+            return Err(format!(
+                "the search supports up to 128 possible tries, not the {pairs} that `--total` \
+                and `--slots` give"
+            ));
+        }
+        if checked_binomial(pairs, tries - 1).is_none() {
+            return Err(
+                "with these parameters there are too many candidates to search".to_string(),
+            );
+        }
+    }
+    if options.engine != Engine::Exhaustive {
+        // Annealing and the greedy engine keep every try and every universe in memory and go
+        // over the universes at each step.
+        let fits = |k| checked_binomial(total, k).is_some_and(|count| count <= 1_000_000);
+        if !fits(slots) || !fits(good) {
+            return Err(
+                "with these parameters there are too many tries or universes for this engine"
+                    .to_string(),
+            );
+        }
+    }
+    if options.engine == Engine::Portfolio && cfg!(target_os = "wasi") {
+        return Err(
+            "`--engine portfolio` runs its engines on threads, which WASI does not have"
+                .to_string(),
+        );
+    }
+    // The shuffled engine of the portfolio ranks whole candidates, the anchor included, in u64.
+    if options.engine == Engine::Portfolio && checked_binomial(pairs, tries).is_none() {
+        return Err("with these parameters there are too many candidates to search".to_string());
+    }
+    if options.params != Params::default()
+        && matches!(
+            options.command,
+            Command::Merge { .. }
+                | Command::Isomorphic { .. }
+                | Command::Replay { .. }
+                | Command::Puzzle { .. }
+        )
+    {
+        return Err(
+            "`--total`, `--good`, `--slots` and `--tries` do not apply to `merge`, `isomorphic`, \
+            `replay` and `puzzle`"
+                .to_string(),
+        );
+    }
+    if (options.pareto || options.min_swaps) && tries > 8 {
+        return Err(
+            "`--pareto` and `--min-swaps` go through every order of the tries, so they support up \
+            to 8 tries"
+                .to_string(),
+        );
+    }
+    if options.min_swaps && slots > 3 {
+        return Err(
+            "`--min-swaps` goes through every order of the batteries of each try, so it supports \
+            up to 3 slots"
+                .to_string(),
+        );
+    }
+    // The number of relabelings of the batteries has to fit into a u128.
+    if options.verify_wlog && total > 34 {
+        return Err("`--verify-wlog` supports up to 34 batteries".to_string());
+    }
+    if options.anchor.is_some_and(|anchor| anchor.0 >> total != 0) {
+        return Err(format!("`--anchor` batteries must be below {total}"));
+    }
+    if options.no_wlog && (options.anchor.is_some() || options.verify_wlog) {
+        return Err(
+            "`--no-wlog` cannot be combined with `--anchor` or `--verify-wlog`".to_string(),
+        );
+    }
+    if options.engine != Engine::Exhaustive
+        && (options.anchor.is_some()
+            || options.no_wlog
+            || options.verify_wlog
+            || options.shard.is_some()
+            || options.threads.is_some()
+            || options.sample.is_some()
+            || options.groups.is_some())
+    {
+        let engine = match options.engine {
+            Engine::Anneal => "anneal",
+            _ => "portfolio",
+        };
+        return Err(format!("`--engine {engine}` only supports `--seed`"));
+    }
+    if options
+        .failure_rate
+        .is_some_and(|p| !(0.0..=1.0).contains(&p))
+    {
+        return Err("`--failure-rate` must be between 0 and 1".to_string());
+    }
+    if let Some(groups) = &options.groups {
+        let sum: usize = groups.iter().sum();
+        if sum as u64 != total {
+            return Err(format!(
+                "the `--groups` sizes add up to {sum}, not the {total} batteries"
+            ));
+        }
+    }
+    if options.groups.is_some()
+        && (options.anchor.is_some()
+            || options.no_wlog
+            || options.verify_wlog
+            || options.shard.is_some()
+            || options.threads.is_some()
+            || options.time_limit.is_some()
+            || options.heartbeat.is_some()
+            || options.progress
+            || options.sample.is_some()
+            || options.pareto
+            || options.min_swaps
+            || !matches!(options.format, Format::Text))
+    {
+        return Err(
+            "`--groups` can only be combined with `--sort-by` among the search options".to_string(),
+        );
+    }
+    if options.adaptive
+        && (options.anchor.is_some()
+            || options.no_wlog
+            || options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.shard.is_some()
+            || options.threads.is_some()
+            || options.time_limit.is_some()
+            || options.heartbeat.is_some()
+            || options.progress
+            || options.sample.is_some()
+            || options.pareto
+            || options.min_swaps
+            || options.sort_by.is_some()
+            || !matches!(options.format, Format::Text))
+    {
+        return Err(
+            "`--adaptive` only supports `--total`, `--good`, `--slots`, `--tries`, `--expected`, \
+            `--counting` and `--dot`"
+                .to_string(),
+        );
+    }
+    if options.break_symmetry
+        && (!matches!(
+            options.command,
+            Command::Solve | Command::Count | Command::Dump { .. }
+        ) || options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.lies.is_some()
+            || options.probability.is_some()
+            || options.sample.is_some()
+            || options.all)
+    {
+        return Err(
+            "`--break-symmetry` only applies to `solve`, `count` and `dump` and cannot be combined with \
+            `--verify-wlog`, `--engine`, `--groups`, `--adaptive`, `--lies`, `--probability`, \
+            `--sample` or `--all`"
+                .to_string(),
+        );
+    }
+    if options.first
+        && (!matches!(options.command, Command::Solve)
+            || options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.min_tries
+            || options.lies.is_some()
+            || options.probability.is_some()
+            || options.sample.is_some()
+            || options.all
+            || options.unique
+            || options.pareto)
+    {
+        return Err(
+            "`--first` only applies to `solve` and cannot be combined with `--verify-wlog`, \
+            `--engine`, `--groups`, `--adaptive`, `--min-tries`, `--lies`, `--probability`, \
+            `--sample`, `--all`, `--unique` or `--pareto`"
+                .to_string(),
+        );
+    }
+    if options.certificate.is_some() && !options.min_tries {
+        return Err("`--certificate` needs `--min-tries`".to_string());
+    }
+    if options.min_tries
+        && (options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.shard.is_some()
+            || options.sample.is_some()
+            || options.all
+            || options.unique
+            || options.pareto
+            || options.min_swaps
+            || options.sort_by.is_some()
+            || !matches!(options.format, Format::Text))
+    {
+        return Err(
+            "`--min-tries` only supports the puzzle parameters, `--anchor`, `--no-wlog`, \
+            `--threads`, `--time-limit`, `--heartbeat` and `--progress`"
+                .to_string(),
+        );
+    }
+    if matches!(options.format, Format::Json)
+        && (options.engine != Engine::Exhaustive
+            || options.verify_wlog
+            || options.pareto
+            || options.min_swaps)
+    {
+        return Err(
+            "`--format json` cannot be combined with `--engine`, `--verify-wlog`, `--pareto` or \
+            `--min-swaps`"
+                .to_string(),
+        );
+    }
+    if options.dot.is_some()
+        && (!matches!(options.command, Command::Solve)
+            || options.engine != Engine::Exhaustive
+            || options.verify_wlog
+            || options.groups.is_some()
+            || options.min_tries
+            || options.pareto
+            || options.min_swaps)
+    {
+        return Err(
+            "`--dot` only applies to `solve` and cannot be combined with `--engine`, \
+            `--verify-wlog`, `--groups`, `--min-tries`, `--pareto` or `--min-swaps`"
+                .to_string(),
+        );
+    }
+    if options.explain
+        && (!matches!(options.command, Command::Solve)
+            || !matches!(options.format, Format::Text | Format::Report)
+            || options.engine != Engine::Exhaustive
+            || options.verify_wlog
+            || options.groups.is_some()
+            || options.min_tries
+            || options.adaptive
+            || options.lies.is_some()
+            || options.probability.is_some()
+            || options.pareto)
+    {
+        return Err(
+            "`--explain` only applies to the text and report output of `solve` and cannot be combined with \
+            `--engine`, `--verify-wlog`, `--groups`, `--min-tries`, `--adaptive`, `--lies`, \
+            `--probability` or `--pareto`"
+                .to_string(),
+        );
+    }
+    if options.minimize
+        && (!matches!(options.command, Command::Solve)
+            || options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.min_tries
+            || options.lies.is_some()
+            || options.probability.is_some())
+    {
+        return Err(
+            "`--minimize` only applies to `solve` and cannot be combined with `--verify-wlog`, \
+            `--engine`, `--groups`, `--adaptive`, `--min-tries`, `--lies` or `--probability`"
+                .to_string(),
+        );
+    }
+    if let Labels(Some(labels)) = &options.labels {
+        if !matches!(
+            options.command,
+            Command::Solve | Command::Quiz | Command::Play
+        ) || options.engine != Engine::Exhaustive
+            || options.verify_wlog
+            || options.groups.is_some()
+            || options.adaptive
+            || options.lies.is_some()
+            || options.probability.is_some()
+            || options.pareto
+            || matches!(options.format, Format::Json)
+        {
+            return Err(
+                "`--labels` only applies to `solve`, `quiz` and `play` and cannot be combined \
+                with `--engine`, `--verify-wlog`, `--groups`, `--adaptive`, `--lies`, \
+                `--probability`, `--pareto` or `--format json`"
+                    .to_string(),
+            );
+        }
+        if labels.len() as u64 != options.params.total {
+            return Err(format!(
+                "`--labels` needs a label for each of the {} batteries, not {}",
+                options.params.total,
+                labels.len()
+            ));
+        }
+    }
+    if options.trace && options.trials.is_some() {
+        return Err(
+            "`--trace` plays a single game, so it cannot be combined with `--trials`".to_string(),
+        );
+    }
+    let variant = match (options.lies, options.probability) {
+        (Some(_), Some(_)) => {
+            return Err("`--lies` cannot be combined with `--probability`".to_string())
+        }
+        (Some(_), None) => Some("--lies"),
+        (None, Some(_)) => Some("--probability"),
+        (None, None) => None,
+    };
+    if let Some(variant) = variant.filter(|_| {
+        !matches!(options.command, Command::Solve)
+            || options.anchor.is_some()
+            || options.no_wlog
+            || options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.min_tries
+            || options.shard.is_some()
+            || options.threads.is_some()
+            || options.time_limit.is_some()
+            || options.heartbeat.is_some()
+            || options.progress
+            || options.sample.is_some()
+            || options.all
+            || options.unique
+            || options.pareto
+            || options.min_swaps
+            || options.sort_by.is_some()
+            || options.dot.is_some()
+            || !matches!(options.format, Format::Text)
+    }) {
+        return Err(format!("`{variant}` only supports the puzzle parameters"));
+    }
+    if matches!(options.command, Command::Count)
+        && (options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.min_tries
+            || options.sample.is_some()
+            || options.all
+            || options.unique
+            || options.pareto
+            || options.min_swaps
+            || options.minimize
+            || options.sort_by.is_some()
+            || options.dot.is_some()
+            || !matches!(options.format, Format::Text))
+    {
+        return Err(
+            "`count` only supports the puzzle parameters, `--anchor`, `--no-wlog`, `--shard`, \
+            `--threads`, `--time-limit`, `--heartbeat` and `--progress`"
+                .to_string(),
+        );
+    }
+    if matches!(options.command, Command::Bench)
+        && (options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.min_tries
+            || options.shard.is_some()
+            || options.time_limit.is_some()
+            || options.heartbeat.is_some()
+            || options.progress
+            || options.sample.is_some()
+            || options.all
+            || options.unique
+            || options.first
+            || options.break_symmetry
+            || options.pareto
+            || options.min_swaps
+            || options.minimize
+            || options.sort_by.is_some()
+            || options.dot.is_some()
+            || !matches!(options.format, Format::Text))
+    {
+        return Err(
+            "`bench` only supports the puzzle parameters, `--anchor`, `--no-wlog` and `--threads`"
+                .to_string(),
+        );
+    }
+    if options.no_color && !matches!(options.format, Format::Report) {
+        return Err("`--no-color` needs `--format report`".to_string());
+    }
+    if options.expected && !options.adaptive {
+        return Err("`--expected` needs `--adaptive`".to_string());
+    }
+    if options.counting && !options.adaptive {
+        return Err("`--counting` needs `--adaptive`".to_string());
+    }
+    if options.adversarial && !matches!(options.command, Command::Play) {
+        return Err("`--adversarial` only applies to `play`".to_string());
+    }
+    if options.session.is_some()
+        && (!matches!(options.command, Command::Play) || options.adversarial)
+    {
+        return Err("`--session` only applies to `play` without `--adversarial`".to_string());
+    }
+    if options.language.is_some() && !matches!(options.command, Command::Quiz | Command::Play) {
+        return Err("`--lang` only applies to `quiz` and `play`".to_string());
+    }
+    if options.trace_file.is_some()
+        && (!matches!(options.command, Command::Solve | Command::Count)
+            || options.verify_wlog
+            || options.engine != Engine::Exhaustive
+            || options.groups.is_some()
+            || options.adaptive
+            || options.min_tries
+            || options.lies.is_some()
+            || options.probability.is_some())
+    {
+        return Err(
+            "`--trace FILE` only applies to `solve` and `count` and cannot be combined with \
+            `--verify-wlog`, `--engine`, `--groups`, `--adaptive`, `--min-tries`, `--lies` or \
+            `--probability`"
+                .to_string(),
+        );
+    }
+    if (options.summary || options.csv || options.outcome.is_some())
+        && !matches!(options.command, Command::Replay { .. })
+    {
+        return Err("`--summary`, `--csv` and `--outcome` only apply to `replay`".to_string());
+    }
+    if options.quiet
+        && (!matches!(options.command, Command::Solve)
+            || !matches!(options.format, Format::Text | Format::Json)
+            || options.engine != Engine::Exhaustive
+            || options.verify_wlog
+            || options.groups.is_some()
+            || options.adaptive
+            || options.min_tries
+            || options.lies.is_some()
+            || options.probability.is_some()
+            || options.pareto
+            || options.explain
+            || options.min_swaps)
+    {
+        return Err(
+            "`--quiet` only applies to the text and JSON output of `solve` and cannot be \
+            combined with `--engine`, `--verify-wlog`, `--groups`, `--adaptive`, `--min-tries`, \
+            `--lies`, `--probability`, `--pareto`, `--explain` or `--min-swaps`"
+                .to_string(),
+        );
+    }
+    if options.summary && options.csv {
+        return Err("`--summary` cannot be combined with `--csv`".to_string());
+    }
+    if options.all && options.unique {
+        return Err("`--all` cannot be combined with `--unique`".to_string());
+    }
+    if (options.all || options.unique)
+        && (options.sample.is_some()
+            || options.groups.is_some()
+            || options.adaptive
+            || options.engine != Engine::Exhaustive)
+    {
+        return Err(format!(
+            "`{}` cannot be combined with `--sample`, `--groups`, `--adaptive` or `--engine`",
+            if options.all { "--all" } else { "--unique" }
+        ));
+    }
+    if options.max_solutions.is_some()
+        && !(matches!(options.command, Command::Classify)
+            || matches!(options.command, Command::Solve)
+                && options.sample.is_none()
+                && options.engine == Engine::Exhaustive
+                && options.groups.is_none()
+                && options.lies.is_none()
+                && options.probability.is_none()
+                && !options.adaptive
+                && !options.verify_wlog
+                && !options.min_tries)
+    {
+        return Err(
+            "`--max-solutions` only applies to `classify` and to `solve` printing the solutions \
+            it finds, without `--sample`"
+                .to_string(),
+        );
+    }
+    if options.pareto && options.sample.is_some() {
+        return Err("`--pareto` cannot be combined with `--sample`".to_string());
+    }
+    if options.shard.is_some() && (options.sample.is_some() || options.verify_wlog) {
+        return Err("`--shard` cannot be combined with `--sample` or `--verify-wlog`".to_string());
+    }
+    Ok(options)
+}
+
+// Parses a duration like "30s", "500ms", "2m" or "1h"; a bare number means seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration `{value}`");
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+// Parses the sizes of the battery packs, e.g. "4,4"; that they add up to `--total` is checked
+// once all the options are known.
+fn parse_groups(value: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("invalid groups `{value}`, expected pack sizes like 4,4");
+    let sizes = value
+        .split(',')
+        .map(|size| size.trim().parse().map_err(|_| invalid()))
+        .collect::<Result<Vec<usize>, _>>()?;
+    if sizes.contains(&0) {
+        return Err(invalid());
+    }
+    Ok(sizes)
+}
+
+// Parses a 1-based shard index and shard count, e.g. "2/8".
+fn parse_shard(value: &str) -> Result<Shard, String> {
+    let invalid = || format!("invalid shard `{value}`, expected I/M with 1 <= I <= M");
+    let (index, count) = value.split_once('/').ok_or_else(invalid)?;
+    let index: u64 = index.trim().parse().map_err(|_| invalid())?;
+    let count: u64 = count.trim().parse().map_err(|_| invalid())?;
+    if index == 0 || index > count {
+        return Err(invalid());
+    }
+    Ok(Shard { index, count })
+}
+
+// Parses a solution line as printed by the solver, e.g. "[[0, 1], [2, 3], [4, 5]]".
+fn parse_solution(line: &str) -> Option<Vec<Vec<usize>>> {
+    let inner = line.trim().strip_prefix("[[")?.strip_suffix("]]")?;
+    inner
+        .split("], [")
+        .map(|entry| {
+            entry
+                .split(", ")
+                .map(|battery| battery.parse().ok().filter(|&b| b < 64))
+                .collect()
+        })
+        .collect()
+}
+
+// Parses distinct battery indices separated by commas, e.g. "2,5", or a set of them as `BitSet`
+// parses it, e.g. "{2,5}" or "0b100100", into a try of `slots` batteries.
+fn parse_try(value: &str, slots: u64) -> Result<BitSet, String> {
+    let invalid = || format!("invalid try `{value}`, expected {slots} distinct batteries");
+    let batteries: BitSet = if value.contains(',') && !value.trim().starts_with('{') {
+        let mut batteries = BitSet::default();
+        for battery in value.split(',') {
+            let battery: usize = battery.trim().parse().map_err(|_| invalid())?;
+            if battery >= 64 || batteries.contains(battery) {
+                return Err(invalid());
+            }
+            batteries.insert(battery);
+        }
+        batteries
+    } else {
+        value
+            .parse()
+            .map_err(|err| format!("{}: {err}", invalid()))?
+    };
+    match u64::from(batteries.len()) == slots {
+        true => Ok(batteries),
+        false => Err(invalid()),
+    }
+}
+
+// The decision tree as a Graphviz graph: a node for each query, with an edge labelled by each
+// feedback to what comes next, which is another query or a leaf labelled like in `print_tree`.
+fn dot_tree<P: Puzzle>(states: &[P::State], tree: &DecisionTree<P>) -> String {
+    use std::fmt::Write;
+
+    // Writes the node for `tree` and everything below it, returning the node's id.
+    fn node<P: Puzzle>(
+        out: &mut String,
+        states: &[P::State],
+        tree: &DecisionTree<P>,
+        nodes: &mut usize,
+    ) -> usize {
+        let id = *nodes;
+        *nodes += 1;
+        match tree {
+            DecisionTree::Done(left) if left.len() == 1 => writeln!(
+                out,
+                "  n{id} [label=\"{}\", shape=ellipse];",
+                states[left[0]]
+            )
+            .unwrap(),
+            DecisionTree::Done(_) => {
+                writeln!(out, "  n{id} [label=\"done\", shape=ellipse];").unwrap()
+            }
+            DecisionTree::Ask { query, branches } => {
+                writeln!(out, "  n{id} [label=\"{query}\"];").unwrap();
+                for (feedback, branch) in branches {
+                    let child = node(out, states, branch, nodes);
+                    writeln!(out, "  n{id} -> n{child} [label=\"{feedback}\"];").unwrap();
+                }
+            }
+        }
+        id
+    }
+
+    let mut out = String::from("digraph strategy {\n  node [shape=box];\n");
+    node(&mut out, states, tree, &mut 0);
+    out.push_str("}\n");
+    out
+}
+
+// Strategies as Graphviz decision trees, one graph per strategy: each try is a node with an `On`
+// edge to a leaf naming the batteries that turned on the toy and an `Off` edge to the next try.
+fn dot_sequences(solutions: &[Solution], labels: &Labels, slots: u64) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    for (number, solution) in solutions.iter().enumerate() {
+        writeln!(out, "digraph strategy_{} {{", number + 1).unwrap();
+        writeln!(out, "  node [shape=box];").unwrap();
+        for (i, pair) in solution_tries(solution, slots).into_iter().enumerate() {
+            let pair = labels.list(pair);
+            writeln!(out, "  try{i} [label=\"{pair}\"];").unwrap();
+            writeln!(out, "  on{i} [label=\"{pair} work\", shape=ellipse];").unwrap();
+            writeln!(out, "  try{i} -> on{i} [label=\"On\"];").unwrap();
+            if i + 1 < solution.len() {
+                writeln!(out, "  try{i} -> try{} [label=\"Off\"];", i + 1).unwrap();
+            }
+        }
+        writeln!(out, "}}").unwrap();
+    }
+    out
+}
+
+fn write_file(file: &str, contents: &str) -> Result<(), String> {
+    std::fs::write(file, contents).map_err(|err| format!("cannot write `{file}`: {err}"))
+}
+
+fn print_tree<P: Puzzle>(states: &[P::State], tree: &DecisionTree<P>, indent: usize) {
+    let DecisionTree::Ask { query, branches } = tree else {
+        return;
+    };
+    println!("{:indent$}{query}", "");
+    for (feedback, branch) in branches {
+        match branch {
+            DecisionTree::Done(left) if left.len() == 1 => {
+                println!("{:indent$}  {feedback}: {}", "", states[left[0]])
+            }
+            DecisionTree::Done(_) => println!("{:indent$}  {feedback}: done", ""),
+            DecisionTree::Ask { .. } => {
+                println!("{:indent$}  {feedback}:", "");
+                print_tree(states, branch, indent + 4);
+            }
+        }
+    }
+}
+
+// Looks for a decision tree of tries with the fewest tries in the worst case, where each try may
+// depend on how the earlier ones went, and prints it. Returns whether the toy can always be turned
+// on within the allowed tries.
+//
+// With this toy the only feedback that lets the game go on is "off", so every decision tree is a
+// sequence of tries in disguise and can do no better than the exhaustive search. What the tree
+// search adds is the proof that no strategy at all needs fewer tries. With `counting` the tries
+// tell how many of their batteries are functional instead, and the tree really branches.
+fn solve_adaptive(params: Params, counting: bool, expected: bool, dot: Option<&str>) -> bool {
+    match counting {
+        false => print_adaptive(&BatteryPuzzle::new(params), params, expected, dot),
+        true => print_adaptive(&CountingBatteryPuzzle { params }, params, expected, dot),
+    }
+}
+
+fn print_adaptive<P: Puzzle>(
+    puzzle: &P,
+    params: Params,
+    expected: bool,
+    dot: Option<&str>,
+) -> bool {
+    let budget = params.tries as usize;
+    let found = match expected {
+        false => adaptive(puzzle, budget).map(|(tries, states, tree)| {
+            let header = format!(
+                "The toy can always be turned on within {}, and not within fewer:",
+                count_tries(tries)
+            );
+            (header, states, tree)
+        }),
+        true => adaptive_expected(puzzle, budget).map(|(cost, states, tree)| {
+            let header = format!(
+                "The fewest tries on average is {:.3} ({cost} tries over all {} universes), \
+                within {} in the worst case:",
+                cost as f64 / states.len() as f64,
+                states.len(),
+                count_tries(budget)
+            );
+            (header, states, tree)
+        }),
+    };
+    match found {
+        Some((header, states, tree)) => {
+            println!("{header}");
+            print_tree(&states, &tree, 0);
+            if let Some(file) = dot {
+                if let Err(err) = write_file(file, &dot_tree(&states, &tree)) {
+                    eprintln!("error: {err}");
+                    std::process::exit(1);
+                }
+            }
+            true
+        }
+        None => {
+            println!(
+                "No adaptive strategy always turns on the toy within {}.",
+                count_tries(params.tries as usize)
+            );
+            false
+        }
+    }
+}
+
+// Looks for the fewest tries, up to the allowed ones, that turn on the toy even if it stays off up
+// to `lies` times although both batteries work, and prints them. Returns whether there are such
+// tries.
+//
+// The only feedback that lets the game go on is still "off", so a strategy is a sequence of tries
+// again, and the worst case is that the toy lies whenever it can: every universe has to contain
+// more of the tries than there are lies.
+fn solve_with_lies(params: Params, lies: u64) -> bool {
+    let universes: Vec<NoisyUniverse> = CombinationIter::new(params.total, params.good)
+        .map(|universe| NoisyUniverse { universe, lies: 0 })
+        .collect();
+    let lie_count = match lies {
+        1 => "1 lie".to_string(),
+        _ => format!("{lies} lies"),
+    };
+    for tries in 1..=params.tries as usize {
+        if let Some(strategy) = noisy_cover(&universes, tries, params.slots, lies) {
+            let strategy: Vec<Vec<usize>> =
+                strategy.iter().map(|t| t.into_iter().collect()).collect();
+            println!(
+                "With up to {lie_count} the toy can always be turned on within {}, and not \
+                within fewer:",
+                count_tries(tries)
+            );
+            println!("{strategy:?}");
+            return true;
+        }
+    }
+    println!(
+        "No strategy always turns on the toy within {} despite up to {lie_count}.",
+        count_tries(params.tries as usize)
+    );
+    false
+}
+
+// Finds the tries most likely to turn on the toy if each battery is functional independently
+// with probability `p`, and prints them with the probability of turning on the toy after each of
+// them. There may be no tries that always work, so this maximizes the chance instead.
+fn solve_with_probability(params: Params, p: f64) {
+    let universes = independent_universes(params.total, p);
+    let (tries, probability) = likeliest_tries(&universes, params.tries as usize, params.slots);
+    println!(
+        "If each battery is functional with probability {p}, these tries turn on the toy within \
+        {} with probability {probability:.6}:",
+        count_tries(params.tries as usize)
+    );
+    let strategy: Vec<Vec<usize>> = tries.iter().map(|t| t.into_iter().collect()).collect();
+    println!("{strategy:?}");
+    for n in 1..=tries.len() {
+        let works = universes
+            .iter()
+            .filter(|u| tries[..n].iter().any(|&t| t.is_subset(u.universe)));
+        let probability: f64 = works.map(|u| u.probability).sum();
+        println!("  after {}: {probability:.6}", count_tries(n));
+    }
+}
+
+// Solves one of the built-in puzzles with the generic adaptive engine and prints the decision
+// tree: each line is a query, followed by what to do for each feedback.
+fn solve_puzzle(name: &str) -> Result<(), String> {
+    fn report<P: Puzzle>(description: &str, puzzle: &P, max_queries: usize) {
+        println!("{description}");
+        match adaptive(puzzle, max_queries) {
+            Some((queries, states, tree)) => {
+                println!("Solvable with {queries} queries in the worst case:");
+                print_tree(&states, &tree, 0);
+            }
+            None => println!("Not solvable with {max_queries} queries."),
+        }
+    }
+
+    match name {
+        "battery" => report(
+            "8 batteries, 4 of them functional, a toy that needs 2 functional batteries.",
+            &BatteryPuzzle::new(Params::default()),
+            7,
+        ),
+        "coins" => report(
+            "12 coins, one of them heavier or lighter than the others, and a balance scale.",
+            &CoinPuzzle { coins: 12 },
+            3,
+        ),
+        "group-testing" => report(
+            "8 items, 2 of them defective, and tests telling whether a pool has a defective item.",
+            &GroupTesting {
+                items: 8,
+                defectives: 2,
+                pool_sizes: 1..=8,
+                goal: Goal::Identify,
+            },
+            8,
+        ),
+        _ => return Err(format!("unknown puzzle `{name}`")),
+    }
+    Ok(())
+}
+
+// Number of batteries that have to be put into the toy when going through the tries in order;
+// a battery that stays in the toy between consecutive tries is not counted again.
+fn handling_count(solution: &[Vec<usize>], slots: u64) -> usize {
+    let mut previous = BitSet::default();
+    let mut count = 0;
+    for pair in solution_tries(solution, slots) {
+        count += (pair - previous).len() as usize;
+        previous = pair;
+    }
+    count
+}
+
+fn print_pareto_front(solutions: &[Solution], params: Params) {
+    let front = pareto_front(solutions, params);
+    println!("Pareto front ({} strategies):", front.len());
+    println!("worst  expected  max/battery  insertions  strategy");
+    for (o, strategy) in front {
+        println!(
+            "{:>5}  {:>8.3}  {:>11}  {:>10}  {strategy:?}",
+            o.worst_tries, o.expected_tries, o.max_insertions_per_battery, o.insertions
+        );
+    }
+}
+
+// Batteries are called A, B, C, ... in instructions meant for people.
+fn battery_name(battery: usize) -> char {
+    char::from(b'A' + battery as u8)
+}
+
+// The names of the batteries given with `--labels`, which output meant for people uses instead of
+// the battery numbers. Without them the batteries are numbered from 0, and lettered from A in
+// instructions.
+#[derive(Default)]
+struct Labels(Option<Vec<String>>);
+
+impl Labels {
+    fn name(&self, battery: usize) -> String {
+        match &self.0 {
+            Some(labels) => labels[battery].clone(),
+            None => battery.to_string(),
+        }
+    }
+
+    // Like `name`, for the names in instructions.
+    fn letter(&self, battery: usize) -> String {
+        match &self.0 {
+            Some(labels) => labels[battery].clone(),
+            None => battery_name(battery).to_string(),
+        }
+    }
+
+    // The batteries as a list like `{:?}` prints their numbers, e.g. "[0, 1]".
+    fn list(&self, batteries: impl IntoIterator<Item = usize>) -> String {
+        let names: Vec<String> = batteries.into_iter().map(|b| self.name(b)).collect();
+        format!("[{}]", names.join(", "))
+    }
+
+    // A solution like `{:?}` prints it, e.g. "[[0, 1], [2, 3]]".
+    fn solution(&self, solution: &[Vec<usize>]) -> String {
+        let tries: Vec<String> = solution
+            .iter()
+            .map(|t| self.list(t.iter().copied()))
+            .collect();
+        format!("[{}]", tries.join(", "))
+    }
+
+    // A set of batteries like `{:#}` prints it, e.g. "{0,1}".
+    fn set(&self, set: BitSet) -> String {
+        let names: Vec<String> = set.into_iter().map(|b| self.name(b)).collect();
+        format!("{{{}}}", names.join(","))
+    }
+
+    // A try of `slots` distinct batteries by their labels separated by commas, or as `parse_try`
+    // reads it without labels.
+    fn parse_try(&self, value: &str, slots: u64) -> Result<BitSet, String> {
+        let Some(labels) = &self.0 else {
+            return parse_try(value, slots);
+        };
+        let invalid = || format!("invalid try `{value}`, expected {slots} distinct batteries");
+        let mut batteries = BitSet::default();
+        for name in value.split(',') {
+            match labels.iter().position(|label| label == name.trim()) {
+                Some(battery) if !batteries.contains(battery) => {
+                    batteries.insert(battery);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        match u64::from(batteries.len()) == slots {
+            true => Ok(batteries),
+            false => Err(invalid()),
+        }
+    }
+}
+
+// Reads the labels of `--labels`: separated by commas, or one per line in the file after `@`.
+fn parse_labels(value: &str) -> Result<Vec<String>, String> {
+    let labels: Vec<String> = match value.strip_prefix('@') {
+        Some(file) => std::fs::read_to_string(file)
+            .map_err(|err| format!("cannot read `{file}`: {err}"))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+        None => value
+            .split(',')
+            .map(|label| label.trim().to_string())
+            .collect(),
+    };
+    for (i, label) in labels.iter().enumerate() {
+        if label.is_empty() || label.contains(|c: char| ",[]{}\"`".contains(c)) {
+            return Err(format!("invalid battery label `{label}`"));
+        }
+        if labels[..i].contains(label) {
+            return Err(format!("the battery label `{label}` is given twice"));
+        }
+    }
+    Ok(labels)
+}
+
+// Turns a solution into numbered steps for someone at the workbench, for a toy with
+// `slot_count` slots; if more batteries are guaranteed to work at the end, the first ones are
+// used. A battery that is needed by two consecutive tries stays in its slot, which is the fewest
+// swaps possible for the given order of tries.
+fn instructions(solution: &[Vec<usize>], labels: &Labels, slot_count: usize) -> Vec<String> {
+    let mut slots: Vec<Option<usize>> = vec![None; slot_count];
+    let mut steps = vec![];
+    for (i, batteries) in solution.iter().enumerate() {
+        let batteries = &batteries[..slot_count];
+        let mut next = vec![None; slot_count];
+        for (slot, battery) in slots.iter().enumerate() {
+            if battery.is_some_and(|b| batteries.contains(&b)) {
+                next[slot] = *battery;
+            }
+        }
+        for &battery in batteries {
+            if !next.contains(&Some(battery)) {
+                let free = next
+                    .iter()
+                    .position(Option::is_none)
+                    .expect("a slot is free");
+                next[free] = Some(battery);
+            }
+        }
+
+        let mut step = format!("{}.", i + 1);
+        for slot in 0..slot_count {
+            if slots[slot] != next[slot] {
+                let battery = labels.letter(next[slot].expect("both slots are filled"));
+                step.push_str(&match slots[slot] {
+                    Some(old) => format!(
+                        " Remove battery {} from slot {} and insert battery {battery}.",
+                        labels.letter(old),
+                        slot + 1
+                    ),
+                    None => format!(" Insert battery {battery} in slot {}.", slot + 1),
+                });
+            }
+        }
+        step.push_str(" Press the power button.");
+        if i + 1 < solution.len() {
+            step.push_str(" If the toy turns on, you are done.");
+        } else {
+            step.push_str(" The toy will turn on.");
+        }
+        steps.push(step);
+        slots = next;
+    }
+    steps
+}
+
+// Prints the `number`-th (1-based) solution, or its swap-minimizing reordering with
+// `--min-swaps`, in the format chosen with `--format`.
+fn print_solution(number: usize, solution: &[Vec<usize>], options: &Options) {
+    let (solution, swaps) = if options.min_swaps {
+        let (reordered, swaps) = minimize_swaps(solution, options.params.slots as usize);
+        (reordered, Some(swaps))
+    } else {
+        (solution.to_vec(), None)
+    };
+    match options.format {
+        Format::Text => {
+            println!("{}", options.labels.solution(&solution));
+            if let Some(swaps) = swaps {
+                println!("  {swaps} battery swaps");
+            }
+            if options.explain {
+                explain(&solution, options.params, &options.labels);
+            }
+        }
+        Format::Report => {
+            println!();
+            println!("Strategy {number}:");
+            print!(
+                "{}",
+                report(&solution, options.params, &options.labels, color(options))
+            );
+            if let Some(swaps) = swaps {
+                println!("  {swaps} battery swaps");
+            }
+            if options.explain {
+                explain(&solution, options.params, &options.labels);
+            }
+        }
+        Format::Instructions => {
+            println!();
+            println!("Strategy {number}:");
+            for step in instructions(&solution, &options.labels, options.params.slots as usize) {
+                println!("  {step}");
+            }
+        }
+        Format::Proof => {
+            println!();
+            println!("Strategy {number}:");
+            print!(
+                "{}",
+                proof(&solution, options.params, false, &options.labels)
+            );
+        }
+        Format::ProofMarkdown => {
+            println!();
+            println!("## Strategy {number}");
+            println!();
+            print!(
+                "{}",
+                proof(&solution, options.params, true, &options.labels)
+            );
+        }
+        Format::Json => unreachable!("JSON output is printed all at once by `print_json`"),
+    }
+}
+
+// Whether the report is printed with colors: on a terminal, unless `--no-color` or `--plain` is
+// given or the NO_COLOR environment variable is set.
+fn color(options: &Options) -> bool {
+    use std::io::IsTerminal;
+
+    !options.no_color
+        && !options.plain
+        && std::env::var_os("NO_COLOR").is_none()
+        && std::io::stdout().is_terminal()
+}
+
+// Formats a measured time with two decimals in the largest unit it has a whole one of, like
+// `{:.2?}` does, but in ASCII with `--plain`.
+fn format_elapsed(elapsed: Duration, plain: bool) -> String {
+    let nanos = elapsed.as_nanos() as f64;
+    let (value, unit) = match elapsed.as_nanos() {
+        n if n >= 1_000_000_000 => (nanos / 1e9, "s"),
+        n if n >= 1_000_000 => (nanos / 1e6, "ms"),
+        n if n >= 1_000 => (nanos / 1e3, if plain { "us" } else { "µs" }),
+        n => return format!("{n}ns"),
+    };
+    format!("{value:.2}{unit}")
+}
+
+// Lays out a solution for `--format report`: one line per try with the try numbers and the
+// batteries in aligned columns, and at the last try the batteries that are functional in every
+// universe the other tries leave, which is why it always works. The last try is the first
+// `params.slots` of those. With `color` the batteries are cyan and the last try is bold green.
+fn report(solution: &[Vec<usize>], params: Params, labels: &Labels, color: bool) -> String {
+    const BATTERY: &str = "\x1b[36m";
+    const GUARANTEED: &str = "\x1b[1;32m";
+    const RESET: &str = "\x1b[0m";
+
+    let paint = |text: String, style: &str| {
+        if color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text
+        }
+    };
+    let (_, tries) = solution.split_last().expect("solution should not be empty");
+    let mut universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+    for pair in tries {
+        universes = remove_impossible_universes(pair.iter().copied().collect(), universes);
+    }
+    let intersection = universes.iter().copied().reduce(|a, b| a & b);
+    let intersection = intersection.expect("a solution leaves a universe for the last try");
+
+    let lists: Vec<String> = solution_tries(solution, params.slots)
+        .into_iter()
+        .map(|t| labels.list(t))
+        .collect();
+    let number_width = solution.len().to_string().len();
+    let list_width = lists.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let mut out = String::new();
+    for (i, batteries) in solution.iter().enumerate() {
+        let plain = lists[i].clone();
+        let padding = " ".repeat(list_width - plain.chars().count());
+        let last = i + 1 == solution.len();
+        let list = if last {
+            paint(plain, GUARANTEED)
+        } else {
+            let names: Vec<String> = batteries
+                .iter()
+                .map(|&b| paint(labels.name(b), BATTERY))
+                .collect();
+            format!("[{}]", names.join(", "))
+        };
+        out += &format!("  try {:>number_width$}  {list}", i + 1);
+        if last {
+            let note = match universes.len() {
+                1 => format!(
+                    "guaranteed: the one universe left is {}",
+                    labels.set(intersection)
+                ),
+                left => format!(
+                    "guaranteed: all {left} universes left contain {}",
+                    labels.set(intersection)
+                ),
+            };
+            out += &format!("{padding}  {}", paint(note, GUARANTEED));
+        }
+        out += "\n";
+    }
+    out
+}
+
+// Writes the solutions to the file given with `--dot`, if any, exiting if that fails.
+fn write_dot(file: Option<&str>, solutions: &[Solution], labels: &Labels, slots: u64) {
+    if let Some(file) = file {
+        if let Err(err) = write_file(file, &dot_sequences(solutions, labels, slots)) {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+// Prints the solutions of a search as one JSON document for `--format json`. Each solution is
+// split into its tries before the last one and the batteries that are functional in every
+// universe those tries leave, any of which make the last try; `class_sizes` gives how many
+// solutions found each one stands for.
+fn print_json(
+    anchor: Option<BitSet>,
+    solutions: &[Solution],
+    class_sizes: Option<&[u64]>,
+    report: &SearchReport,
+) {
+    println!("{{");
+    match anchor {
+        Some(anchor) => println!(
+            "  \"anchor\": {:?},",
+            anchor.into_iter().collect::<Vec<_>>()
+        ),
+        None => println!("  \"anchor\": null,"),
+    }
+    println!("  \"solutions\": [");
+    for (i, solution) in solutions.iter().enumerate() {
+        let (guaranteed, tries) = solution.split_last().expect("solution should not be empty");
+        print!("    {{\"tries\": {tries:?}, \"guaranteed\": {guaranteed:?}");
+        if let Some(sizes) = class_sizes {
+            print!(", \"class_size\": {}", sizes[i]);
+        }
+        println!("}}{}", if i + 1 < solutions.len() { "," } else { "" });
+    }
+    println!("  ],");
+    println!("  \"count\": {},", report.found);
+    println!("  \"examined\": {},", report.examined);
+    println!("  \"total\": {},", report.total);
+    println!("  \"timed_out\": {}", report.timed_out);
+    println!("}}");
+}
+
+// Prints, for each try of a solution but the last, the universes its failure rules out and how
+// many are left, and then why the last try works in all of them. Long lists of universes are cut
+// short.
+fn explain(solution: &[Vec<usize>], params: Params, labels: &Labels) {
+    const SHOWN: usize = 20;
+
+    let (last, tries) = solution.split_last().expect("solution should not be empty");
+    let name = |set: BitSet| labels.set(set);
+    let list = |universes: &[BitSet]| {
+        let mut names: Vec<String> = universes.iter().take(SHOWN).map(|&u| name(u)).collect();
+        if universes.len() > SHOWN {
+            names.push(format!("and {} more", universes.len() - SHOWN));
+        }
+        names.join(", ")
+    };
+    let mut universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+    println!("  {} universes at the start", universes.len());
+    for pair in tries {
+        let pair: BitSet = pair.iter().copied().collect();
+        let (ruled_out, left): (Vec<BitSet>, Vec<BitSet>) =
+            universes.iter().partition(|&&u| pair.is_subset(u));
+        universes = left;
+        println!(
+            "  {} fails: rules out {} ({}), {} left",
+            name(pair),
+            ruled_out.len(),
+            list(&ruled_out),
+            universes.len()
+        );
+    }
+    let intersection = universes.iter().copied().reduce(|a, b| a & b);
+    let intersection = intersection.expect("a solution leaves a universe for the last try");
+    let last: BitSet = last.iter().take(params.slots as usize).copied().collect();
+    let left = match universes.len() {
+        1 => format!("the one universe left ({}) contains", list(&universes)),
+        count => format!("all {count} universes left ({}) contain", list(&universes)),
+    };
+    println!(
+        "  {} works: {left} {}, so its batteries are functional whichever universe is the real \
+        one",
+        name(last),
+        name(intersection)
+    );
+    debug_assert!(last.is_subset(intersection));
+}
+
+// Writes up why a solution always turns on the toy as a case analysis over the possible sets of
+// `params.good` functional batteries ("configurations"): every failed try rules out the
+// configurations in which all of its batteries are functional, and the final try is made of
+// batteries that are functional in every configuration that is left (the first `params.slots`
+// of them, if there are more).
+fn proof(solution: &[Vec<usize>], params: Params, markdown: bool, labels: &Labels) -> String {
+    use std::fmt::Write;
+
+    let (last, tries) = solution.split_last().expect("solution should not be empty");
+    let to_set = |batteries: &Vec<usize>| batteries.iter().copied().collect::<BitSet>();
+    let names = |set: BitSet| match markdown {
+        true => format!("`{}`", labels.set(set)),
+        false => labels.set(set),
+    };
+
+    let mut out = String::new();
+    let mut configurations: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+    let all = if params.slots == 2 { "both" } else { "all" };
+    writeln!(
+        out,
+        "Any {} of the {} batteries may be the functional ones, so {} configurations are possible \
+        at the start. We show that one of the {} tries turns on the toy in each of them.",
+        params.good,
+        params.total,
+        configurations.len(),
+        solution.len()
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+
+    for (i, pair) in tries.iter().enumerate() {
+        let pair = to_set(pair);
+        let before = configurations.len();
+        configurations = remove_impossible_universes(pair, configurations);
+        writeln!(
+            out,
+            "{}. Try {}. If the toy turns on we are done. Otherwise the batteries {} are not \
+            {all} functional, which rules out {} configurations and leaves {}.",
+            i + 1,
+            names(pair),
+            names(pair),
+            before - configurations.len(),
+            configurations.len()
+        )
+        .unwrap();
+        if configurations.len() <= 10 {
+            let mut left = configurations.clone();
+            left.sort_by_key(|c| c.0);
+            let left: Vec<String> = left.into_iter().map(names).collect();
+            writeln!(out, "   Left: {}.", left.join(", ")).unwrap();
+        }
+    }
+
+    let guaranteed = to_set(last);
+    let last: BitSet = last.iter().take(params.slots as usize).copied().collect();
+    let left = match configurations.len() {
+        1 => "The one configuration left contains".to_string(),
+        count => format!("Each of the {count} configurations left contains"),
+    };
+    let among = match last == guaranteed {
+        true => String::new(),
+        false => format!(", {} among them,", names(last)),
+    };
+    writeln!(
+        out,
+        "{}. Try {}. {left} all of {}, so these batteries{among} are functional no matter which \
+        configuration is the real one, and the toy turns on.",
+        solution.len(),
+        names(last),
+        names(guaranteed)
+    )
+    .unwrap();
+    debug_assert!(configurations.iter().all(|&c| guaranteed.is_subset(c)));
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "Hence the toy turns on within {} tries whichever batteries are functional.",
+        solution.len()
+    )
+    .unwrap();
+    out
+}
+
+// Stable sort, so solutions that tie keep the order in which the search found them.
+fn sort_solutions(solutions: &mut [Solution], sort_by: SortBy, slots: u64) {
+    match sort_by {
+        SortBy::Lexicographic => solutions.sort(),
+        SortBy::Handling => solutions.sort_by_key(|s| handling_count(s, slots)),
+        SortBy::Diversity => solutions.sort_by_key(|s| {
+            let (_, tries) = s.split_last().expect("solution should not be empty");
+            let tried = tries.iter().flatten().fold(0u64, |acc, &b| acc | 1 << b);
+            std::cmp::Reverse(tried.count_ones())
+        }),
+        SortBy::Guaranteed => {
+            solutions.sort_by_key(|s| std::cmp::Reverse(s.last().map_or(0, Vec::len)))
+        }
+    }
+}
+
+// The pack of each battery and its 1-based position in the pack, for `--groups`.
+fn group_of(battery: usize, groups: &[usize]) -> (usize, usize) {
+    let mut first = 0;
+    for (group, &size) in groups.iter().enumerate() {
+        if battery < first + size {
+            return (group, battery - first + 1);
+        }
+        first += size;
+    }
+    panic!("battery {battery} is in no group");
+}
+
+// All relabelings of the batteries that only swap batteries within the same pack.
+fn group_relabelings(groups: &[usize]) -> Vec<Vec<usize>> {
+    let mut maps = vec![(0..groups.iter().sum()).collect::<Vec<_>>()];
+    let mut first = 0;
+    for &size in groups {
+        let pack = first..first + size;
+        maps = maps
+            .into_iter()
+            .flat_map(|mut map| {
+                let mut relabelings = vec![map.clone()];
+                while permute(&mut map[pack.clone()]) {
+                    relabelings.push(map.clone());
+                }
+                relabelings
+            })
+            .collect();
+        first += size;
+    }
+    maps
+}
+
+// Solves the puzzle for batteries that come in interchangeable packs, printing one solution for
+// each class of solutions that only differ by swapping batteries within a pack.
+//
+// Fixing the first try to [0, 1] is no longer WLOG once relabelings have to respect the packs.
+// Instead, every solution can be relabeled within the packs so that one of its tries is made of
+// the first batteries of some of the packs, so searching with each of those as the first try
+// finds all the classes. If there are too many of them, a single search without any assumption
+// is cheaper.
+fn solve_in_groups(groups: &[usize], params: Params, sort_by: Option<SortBy>) {
+    let first = |group: usize| groups[..group].iter().sum::<usize>();
+    let packs: Vec<Vec<usize>> = (0..groups.len())
+        .map(|g| (first(g)..first(g) + groups[g]).collect())
+        .collect();
+    let slots = params.slots as usize;
+    let mut anchors: Vec<Option<BitSet>> = class_selections(&packs, slots..=slots)
+        .into_iter()
+        .map(|batteries| Some(batteries.into_iter().collect()))
+        .collect();
+    anchors.sort_unstable();
+    let pairs = binomial(params.total, params.slots);
+    let candidates = |n, k| checked_binomial(n, k).map_or(u128::MAX, u128::from);
+    let anchored = (anchors.len() as u128)
+        .saturating_mul(candidates(pairs - 1, params.tries.saturating_sub(2)));
+    // A single try is the last one, which no anchor can fix.
+    if params.tries < 2 || anchored >= candidates(pairs, params.tries - 1) {
+        anchors = vec![None];
+    }
+
+    let relabelings = group_relabelings(groups);
+    let mut seen = HashSet::new();
+    let mut solutions = vec![];
+    for anchor in anchors {
+        let config = Solver {
+            params,
+            anchor,
+            ..Solver::default()
+        };
+        config.search(|solution| {
+            let key = relabelings
+                .iter()
+                .map(|map| strategy_key(&solution, map))
+                .min()
+                .unwrap();
+            if seen.insert(key) {
+                solutions.push(solution);
+            }
+        });
+    }
+    if let Some(sort_by) = sort_by {
+        sort_solutions(&mut solutions, sort_by, params.slots);
+    }
+
+    let name = |battery: usize| {
+        let (group, position) = group_of(battery, groups);
+        format!("{}{position}", battery_name(group))
+    };
+    for (g, &size) in groups.iter().enumerate() {
+        println!(
+            "Pack {}: batteries {}..={}",
+            battery_name(g),
+            first(g),
+            first(g) + size - 1
+        );
+    }
+    println!("Solutions up to swapping batteries within a pack:");
+    for solution in &solutions {
+        let tries: Vec<String> = solution
+            .iter()
+            .map(|batteries| {
+                let names: Vec<String> = batteries.iter().map(|&b| name(b)).collect();
+                format!("[{}]", names.join(", "))
+            })
+            .collect();
+        println!("[{}]", tries.join(", "));
+    }
+}
+
+// Tells how many of the solutions found each printed solution stands for, in the printed order.
+fn print_class_sizes(sizes: &[u64]) {
+    let found: u64 = sizes.iter().sum();
+    let sizes: Vec<String> = sizes.iter().map(u64::to_string).collect();
+    match &sizes[..] {
+        [] => println!("No solutions found."),
+        [_] => println!(
+            "1 solution up to relabeling batteries, standing for all {found} solutions found."
+        ),
+        [rest @ .., last] => println!(
+            "{} solutions up to relabeling batteries, standing for {} and {last} of the {found} \
+            solutions found.",
+            sizes.len(),
+            rest.join(", ")
+        ),
+    }
+}
+
+// Tells which part of the search space the search covers.
+fn print_assumptions(options: &Options) {
+    match options.anchor_or_default() {
+        Some(anchor) => println!(
+            "Assuming WLOG that the first try is {}.",
+            options.labels.list(anchor)
+        ),
+        None => println!("No WLOG assumption: all first tries are searched."),
+    }
+    if let Some(Shard { index, count }) = options.shard {
+        println!("Searching shard {index}/{count}; combine the outputs with `batteries merge`.");
+    }
+}
+
+// Exits with the status the usage gives for how the search of `solve` ended, unless it found a
+// strategy.
+fn exit_for(outcome: SearchOutcome) {
+    match outcome {
+        SearchOutcome::Found => {}
+        SearchOutcome::NoneExist => std::process::exit(2),
+        SearchOutcome::Unfinished => std::process::exit(3),
+    }
+}
+
+// Tells the user how much of the search space a search that ran out of time covered.
+// The most solutions, or classes of them, that `solve` and `classify` hold before they stop the
+// search, with `--max-solutions`.
+fn max_solutions(options: &Options) -> usize {
+    options.max_solutions.map_or(1_000_000, NonZeroUsize::get)
+}
+
+fn warn_max_solutions(limit: usize) {
+    eprintln!(
+        "warning: stopped the search after {limit} solutions, the most `--max-solutions` allows \
+        to hold; there may be more"
+    );
+}
+
+fn print_timeout(report: &SearchReport) {
+    if !report.timed_out {
+        return;
+    }
+    println!(
+        "Time limit reached after examining {} of {} candidates ({:.1}%); the solutions above \
+        are the ones found so far.",
+        report.examined,
+        report.total,
+        100.0 * report.examined as f64 / report.total.max(1) as f64
+    );
+    if let Some(fewest) = report.fewest_remaining {
+        println!(
+            "Closest miss so far: {fewest} universes left after all tries but the last failed, \
+            without enough batteries functional in all of them."
+        );
+    }
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            std::process::exit(1);
+        }
+    };
+    if options.help {
+        println!("{USAGE}");
+        return;
+    }
+    if let Some(file) = &options.batch {
+        let jobs = options.jobs.map_or(1, NonZeroUsize::get);
+        match run_batch(file, jobs) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if options.stdio {
+        if let Err(err) = serve_stdio(options.metrics.as_deref()) {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(path) = &options.socket {
+        if let Err(err) = serve_socket(path, options.metrics.as_deref()) {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let seed = options.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    });
+
+    let result = match &options.command {
+        Command::Solve => Ok(()),
+        Command::Merge { files } => merge(files),
+        Command::Graphml { file } => write_graphml(file.as_deref(), options.params),
+        Command::Dump { rank } => dump_candidate(
+            *rank,
+            options.params,
+            options.anchor_or_default(),
+            options.break_symmetry,
+        ),
+        Command::Classify => classify(
+            options.params,
+            options.anchor_or_default(),
+            max_solutions(&options),
+        ),
+        Command::Isomorphic { a, b } => isomorphic(a, b),
+        Command::Verify { file } => verify(file, options.params),
+        Command::Replay { file } => replay(file, &options),
+        Command::Evaluate { file } => {
+            evaluate(file, options.params, options.trials, seed, options.plain)
+        }
+        Command::Analyze => {
+            analyze(options.params);
+            Ok(())
+        }
+        Command::Count => {
+            count(&options);
+            Ok(())
+        }
+        Command::Frontier => {
+            frontier(&options);
+            Ok(())
+        }
+        Command::Bench => {
+            bench(&options);
+            Ok(())
+        }
+        Command::Quiz => quiz(options.params, &options.labels, language(&options)),
+        Command::Play if options.adversarial => {
+            play_adversary(options.params, &options.labels, language(&options))
+        }
+        Command::Play => play(
+            options.params,
+            &options.labels,
+            options.session.as_deref(),
+            language(&options),
+        ),
+        Command::Puzzle { name } => solve_puzzle(name),
+        Command::Simulate { files } if options.trace => trace_game(
+            files,
+            options.params,
+            options.failure_rate.unwrap_or(0.1),
+            seed,
+        ),
+        Command::Simulate { files } => simulate(
+            files,
+            options.params,
+            options.failure_rate.unwrap_or(0.1),
+            options.trials.unwrap_or(100_000),
+            seed,
+        ),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+    if !matches!(options.command, Command::Solve) {
+        return;
+    }
+
+    // The searches below can take hours on puzzles that counting alone shows to have no strategy.
+    let covers_every_universe = !options.adaptive
+        && options.lies.is_none()
+        && options.probability.is_none()
+        && !options.verify_wlog;
+    // A certificate needs the candidates even then.
+    if covers_every_universe && options.certificate.is_none() && !feasible(&options) {
+        exit_for(SearchOutcome::NoneExist);
+    }
+
+    if options.engine == Engine::Anneal {
+        let mut rng = Rng::new(seed);
+        match anneal(options.params, &mut rng, None) {
+            Ok(strategy) => {
+                println!("Found a valid strategy by simulated annealing (seed {seed}):");
+                println!("{strategy:?}");
+            }
+            Err((strategy, failing)) => {
+                println!(
+                    "No valid strategy found by simulated annealing (seed {seed}); the best one \
+                    fails in {failing} universes:"
+                );
+                println!("{strategy:?}");
+                exit_for(SearchOutcome::Unfinished);
+            }
+        }
+        return;
+    }
+
+    if options.engine == Engine::Portfolio {
+        let started = Instant::now();
+        match portfolio(options.params, seed) {
+            Some((engine, strategy)) => {
+                println!(
+                    "Found a valid strategy with the {engine} engine after {} (seed {seed}):",
+                    format_elapsed(started.elapsed(), options.plain)
+                );
+                print_solution(1, &strategy, &options);
+            }
+            None => {
+                println!(
+                    "No strategy always turns on the toy within {}.",
+                    count_tries(options.params.tries as usize)
+                );
+                print_adversary_hint(options.params);
+                exit_for(SearchOutcome::NoneExist);
+            }
+        }
+        return;
+    }
+
+    if let Some(groups) = &options.groups {
+        solve_in_groups(groups, options.params, options.sort_by);
+        return;
+    }
+
+    if let Some(lies) = options.lies {
+        if !solve_with_lies(options.params, lies) {
+            exit_for(SearchOutcome::NoneExist);
+        }
+        return;
+    }
+
+    if let Some(p) = options.probability {
+        solve_with_probability(options.params, p);
+        return;
+    }
+
+    if options.adaptive {
+        if !solve_adaptive(
+            options.params,
+            options.counting,
+            options.expected,
+            options.dot.as_deref(),
+        ) {
+            exit_for(SearchOutcome::NoneExist);
+        }
+        return;
+    }
+
+    if options.verify_wlog {
+        let anchor = options
+            .anchor_or_default()
+            .expect("`--verify-wlog` cannot be combined with `--no-wlog`");
+        if !verify_wlog(options.params, anchor) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let anchor = options.anchor_or_default();
+    let json = matches!(options.format, Format::Json);
+    let trace = open_trace(&options);
+    if !json && !options.quiet {
+        print_assumptions(&options);
+    }
+
+    let record: &(dyn Fn(TraceEvent) + Sync) = &|event| trace.as_ref().unwrap().record(&event);
+    let config = Solver {
+        trace: trace.is_some().then_some(record),
+        ..options.solver()
+    };
+    if options.min_tries {
+        exit_for(min_tries(
+            config,
+            &options.labels,
+            options.certificate.as_deref(),
+            options.check_known,
+        ));
+        return;
+    }
+    match options.sample {
+        None => {
+            // Each class of solutions that are the same up to relabeling batteries, as the first
+            // solution found in it and how many solutions were found in it; with `--all` every
+            // solution is a class of its own.
+            let mut classes: Vec<(Solution, u64)> = vec![];
+            let limit = max_solutions(&options);
+            let full = AtomicBool::new(false);
+            let config = Solver {
+                cancel: Some(&full),
+                ..config
+            };
+            let report = config.search(|solution| {
+                if full.load(Ordering::Relaxed) {
+                    return;
+                }
+                let solution = match options.minimize {
+                    true => minimize(&solution, options.params, anchor.is_some()),
+                    false => solution,
+                };
+                let held = classes.len();
+                let class = match options.all {
+                    // Different solutions may lose different tries and end up the same.
+                    true if options.minimize => classes.iter_mut().find(|(s, _)| *s == solution),
+                    true => None,
+                    false => classes
+                        .iter_mut()
+                        .find(|(s, _)| same_solution(&s[..], &solution[..])),
+                };
+                match class {
+                    Some((_, size)) => *size += 1,
+                    None if held == limit => full.store(true, Ordering::Relaxed),
+                    None => classes.push((solution, 1)),
+                }
+            });
+            close_trace(trace.as_ref());
+            if full.load(Ordering::Relaxed) {
+                warn_max_solutions(limit);
+            }
+            let mut solutions: Vec<Solution> = classes.iter().map(|(s, _)| s.clone()).collect();
+            if options.pareto {
+                print_pareto_front(&solutions, options.params);
+                print_timeout(&report);
+                exit_for(report.outcome());
+                return;
+            }
+            if let Some(sort_by) = options.sort_by {
+                sort_solutions(&mut solutions, sort_by, options.params.slots);
+            }
+            let sizes: Vec<u64> = solutions
+                .iter()
+                .map(|solution| classes.iter().find(|(s, _)| s == solution).unwrap().1)
+                .collect();
+            write_dot(
+                options.dot.as_deref(),
+                &solutions,
+                &options.labels,
+                options.params.slots,
+            );
+            if json {
+                let sizes = (!options.all).then_some(&sizes[..]);
+                print_json(anchor, &solutions, sizes, &report);
+                exit_for(report.outcome());
+                return;
+            }
+            if !options.quiet {
+                println!("Solutions:");
+            }
+            for (i, solution) in solutions.iter().enumerate() {
+                print_solution(i + 1, solution, &options);
+            }
+            if !options.quiet {
+                if options.first && solutions.is_empty() {
+                    println!("No solutions found.");
+                } else if !options.all && !options.first {
+                    print_class_sizes(&sizes);
+                }
+                print_timeout(&report);
+                if report.outcome() == SearchOutcome::NoneExist && options.shard.is_none() {
+                    print_adversary_hint(options.params);
+                }
+            }
+            exit_for(report.outcome());
+        }
+        Some(sample_size) => {
+            let mut rng = Rng::new(seed);
+
+            // Reservoir sampling: after seeing i solutions, each of them is in the sample with
+            // probability sample_size / i, without ever holding more than sample_size of them.
+            let mut sample = Vec::with_capacity(sample_size);
+            let mut seen = 0u64;
+            let report = config.search(|solution| {
+                let solution = match options.minimize {
+                    true => minimize(&solution, options.params, anchor.is_some()),
+                    false => solution,
+                };
+                seen += 1;
+                if sample.len() < sample_size {
+                    sample.push(solution);
+                } else {
+                    let j = rng.below(seen);
+                    if j < sample_size as u64 {
+                        sample[j as usize] = solution;
+                    }
+                }
+            });
+            close_trace(trace.as_ref());
+            if let Some(sort_by) = options.sort_by {
+                sort_solutions(&mut sample, sort_by, options.params.slots);
+            }
+            write_dot(
+                options.dot.as_deref(),
+                &sample,
+                &options.labels,
+                options.params.slots,
+            );
+            if json {
+                print_json(anchor, &sample, None, &report);
+                exit_for(report.outcome());
+                return;
+            }
+            if !options.quiet {
+                println!(
+                    "Sampled {} of {seen} solutions (seed {seed}):",
+                    sample.len()
+                );
+            }
+            for (i, solution) in sample.iter().enumerate() {
+                print_solution(i + 1, solution, &options);
+            }
+            if !options.quiet {
+                print_timeout(&report);
+            }
+            exit_for(report.outcome());
+        }
+    }
+}
+
+// How `--outcome` and the output of `replay` call an outcome.
+fn outcome_name(outcome: TraceOutcome) -> &'static str {
+    match outcome {
+        TraceOutcome::RepeatsAnchor => "repeats-anchor",
+        TraceOutcome::NotCanonical => "not-canonical",
+        TraceOutcome::AlwaysOn => "always-on",
+        TraceOutcome::Solution => "solution",
+        TraceOutcome::Failed => "failed",
+    }
+}
+
+// The file given with `--trace FILE`, which the search writes a record to for each candidate it
+// examines and each range of candidates it prunes, in the format of `batteries::trace`. The first
+// error writing the file is kept and reported once the search is done.
+struct TraceLog {
+    file: String,
+    out: Mutex<(std::io::BufWriter<std::fs::File>, Option<std::io::Error>)>,
+}
+
+impl TraceLog {
+    fn create(file: &str, params: Params, anchor: Option<BitSet>) -> Result<Self, String> {
+        let error = |err: std::io::Error| format!("cannot write `{file}`: {err}");
+        let mut out = std::io::BufWriter::new(std::fs::File::create(file).map_err(error)?);
+        let mut header = vec![];
+        trace::write_header(&mut header, params, anchor);
+        std::io::Write::write_all(&mut out, &header).map_err(error)?;
+        Ok(TraceLog {
+            file: file.to_string(),
+            out: Mutex::new((out, None)),
+        })
+    }
+
+    fn record(&self, event: &TraceEvent) {
+        let mut record = vec![];
+        trace::write_event(&mut record, event);
+        let mut out = self.out.lock().unwrap();
+        let (file, error) = &mut *out;
+        if error.is_none() {
+            *error = std::io::Write::write_all(file, &record).err();
+        }
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        let mut out = self.out.lock().unwrap();
+        let (file, error) = &mut *out;
+        let result = match error.take() {
+            Some(err) => Err(err),
+            None => std::io::Write::flush(file),
+        };
+        result.map_err(|err| format!("cannot write `{}`: {err}", self.file))
+    }
+}
+
+// Creates the trace file for `--trace FILE`, if it is given, exiting if that fails.
+fn open_trace(options: &Options) -> Option<TraceLog> {
+    let file = options.trace_file.as_deref()?;
+    match TraceLog::create(file, options.params, options.anchor_or_default()) {
+        Ok(trace) => Some(trace),
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+// Writes out the rest of the trace, if there is one, exiting if that fails.
+fn close_trace(trace: Option<&TraceLog>) {
+    if let Err(err) = trace.map_or(Ok(()), TraceLog::finish) {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+// Reads the trace in `file` as written for `--trace FILE`: the parameters of the puzzle, the
+// anchor and the records.
+fn read_trace(file: &str) -> Result<(Params, Option<BitSet>, Vec<TraceEvent>), String> {
+    let contents = std::fs::read(file).map_err(|err| format!("cannot read `{file}`: {err}"))?;
+    trace::read_trace(&contents)
+        .ok_or_else(|| format!("`{file}` is not a complete trace written by `--trace`"))
+}
+
+// Prints the trace recorded with `--trace FILE` in the order of the ranks, which is the order of
+// the search on one thread: a line per record, a CSV row per record with `--csv`, or how many
+// records and candidates there are of each outcome with `--summary`. `--outcome` only keeps the
+// records of that outcome. The whole trace is checked before anything is printed, and a reader
+// that stops early, like `head`, ends the listing without an error.
+fn replay(file: &str, options: &Options) -> Result<(), String> {
+    use std::io::Write;
+
+    let (params, anchor, mut events) = read_trace(file)?;
+    if params.total > 64 || params.slots == 0 || params.slots > params.total {
+        return Err(format!(
+            "`{file}` is not a trace of a puzzle this program solves"
+        ));
+    }
+    events.retain(|event| {
+        options
+            .outcome
+            .is_none_or(|outcome| event.outcome == outcome)
+    });
+    events.sort_by_key(|event| event.ranks.start);
+
+    let pairs: Vec<BitSet> = CombinationIter::new(params.total, params.slots).collect();
+    let tries = |event: &TraceEvent| -> Result<Solution, String> {
+        let mut tries: Solution = anchor
+            .map(|a| a.into_iter().collect())
+            .into_iter()
+            .collect();
+        for &index in event.tries.iter().rev() {
+            let pair = pairs
+                .get(index)
+                .ok_or_else(|| format!("`{file}` has a try that is not in the puzzle"))?;
+            tries.push(pair.into_iter().collect());
+        }
+        Ok(tries)
+    };
+
+    let listed = events
+        .iter()
+        .map(|event| Ok((event, tries(event)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    if options.summary {
+        println!(
+            "Trace of the puzzle with {} batteries, {} functional, {} slots and {}:",
+            params.total,
+            params.good,
+            params.slots,
+            count_tries(params.tries as usize)
+        );
+        println!("outcome          records   candidates");
+        for outcome in trace::OUTCOMES {
+            let of_outcome = events.iter().filter(|event| event.outcome == outcome);
+            let candidates: u64 = of_outcome
+                .clone()
+                .map(|event| event.ranks.end - event.ranks.start)
+                .sum();
+            println!(
+                "{:<15} {:>8} {:>12}",
+                outcome_name(outcome),
+                of_outcome.count(),
+                candidates
+            );
+        }
+        let candidates: u64 = events.iter().map(|e| e.ranks.end - e.ranks.start).sum();
+        println!("{:<15} {:>8} {:>12}", "total", events.len(), candidates);
+        let failed = events.iter().filter(|e| e.outcome == TraceOutcome::Failed);
+        if let Some(fewest) = failed.map(|e| e.universes_left).min() {
+            println!("The closest failed candidate leaves {fewest} universes.");
+        }
+        return Ok(());
+    }
+    let mut out = std::io::stdout().lock();
+    let written = (|| -> std::io::Result<()> {
+        if options.csv {
+            writeln!(out, "first_rank,candidates,outcome,universes_left,tries")?;
+        }
+        for (event, tries) in &listed {
+            let candidates = event.ranks.end - event.ranks.start;
+            if options.csv {
+                let tries: Vec<String> = tries
+                    .iter()
+                    .map(|t| {
+                        t.iter()
+                            .map(|b| b.to_string())
+                            .collect::<Vec<_>>()
+                            .join("-")
+                    })
+                    .collect();
+                writeln!(
+                    out,
+                    "{},{candidates},{},{},{}",
+                    event.ranks.start,
+                    outcome_name(event.outcome),
+                    event.universes_left,
+                    tries.join(" ")
+                )?;
+            } else {
+                let ranks = match candidates {
+                    1 => event.ranks.start.to_string(),
+                    _ => format!("{}..{}", event.ranks.start, event.ranks.end),
+                };
+                writeln!(
+                    out,
+                    "{ranks} {tries:?}: {}, {} universes left",
+                    outcome_name(event.outcome),
+                    event.universes_left
+                )?;
+            }
+        }
+        out.flush()
+    })();
+    match written {
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        written => written.map_err(|err| format!("cannot write the trace: {err}")),
+    }
+}
+
+// Runs the search of `solve` and prints how many solutions it finds, how many of them are unique
+// up to relabeling batteries and how the candidates were dealt with, without the solutions.
+fn count(options: &Options) {
+    let trace = open_trace(options);
+    print_assumptions(options);
+    let record: &(dyn Fn(TraceEvent) + Sync) = &|event| trace.as_ref().unwrap().record(&event);
+    let solver = Solver {
+        trace: trace.is_some().then_some(record),
+        ..options.solver()
+    };
+    let mut classes: Vec<Solution> = vec![];
+    let report = solver.search(|solution| {
+        if !classes.iter().any(|s| same_solution(s, &solution)) {
+            classes.push(solution);
+        }
+    });
+    close_trace(trace.as_ref());
+    println!(
+        "{} found, {} of them unique up to relabeling batteries.",
+        match report.found {
+            1 => "1 solution".to_string(),
+            found => format!("{found} solutions"),
+        },
+        classes.len()
+    );
+    println!(
+        "Examined {} of {} candidates: {} pruned along with others that share tries with them, \
+        {} checked for a last try that always works.",
+        report.examined,
+        report.total,
+        report.pruned,
+        report.examined - report.pruned
+    );
+    if report.timed_out {
+        println!("Time limit reached; the counts are the ones of the candidates examined so far.");
+    }
+}
+
+// Serves the `metrics` of `--metrics` at http://address/metrics, on a thread of its own, one
+// request at a time.
+fn serve_metrics(address: &str, metrics: std::sync::Arc<rpc::Metrics>) -> Result<(), String> {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind(address)
+        .map_err(|err| format!("cannot serve metrics at `{address}`: {err}"))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let answer = |stream: std::net::TcpStream| -> std::io::Result<()> {
+                let mut lines = BufReader::new(stream.try_clone()?).lines();
+                let request = lines.next().transpose()?.unwrap_or_default();
+                // The headers are of no use, but a client may wait until they are read.
+                for line in lines.by_ref() {
+                    if line?.is_empty() {
+                        break;
+                    }
+                }
+                let (status, body) = match request.split(' ').take(2).collect::<Vec<_>>()[..] {
+                    ["GET", "/metrics"] => ("200 OK", metrics.to_string()),
+                    _ => ("404 Not Found", "only /metrics is served\n".to_string()),
+                };
+                write!(
+                    &stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            };
+            if let Err(err) = stream.and_then(answer) {
+                eprintln!("warning: dropped a metrics request: {err}");
+            }
+        }
+    });
+    Ok(())
+}
+
+// Answers the JSON-RPC requests of `--stdio`, one per line of stdin, until stdin ends, serving
+// the metrics at `metrics` if it is given.
+fn serve_stdio(metrics: Option<&str>) -> Result<(), String> {
+    use std::io::BufRead;
+
+    let mut server = rpc::Server::default();
+    if let Some(address) = metrics {
+        serve_metrics(address, server.metrics())?;
+    }
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|err| format!("cannot read stdin: {err}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = server.handle(&line) {
+            println!("{response}");
+        }
+    }
+    Ok(())
+}
+
+// Answers the JSON-RPC requests of `--socket` on a unix socket created at `path`, one connection
+// at a time, until killed, serving the metrics at `metrics` if it is given. A connection that
+// breaks is dropped with a warning.
+#[cfg(unix)]
+fn serve_socket(path: &str, metrics: Option<&str>) -> Result<(), String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // A socket that nothing listens on any more is left over from a daemon that was killed.
+    let listener = match UnixListener::bind(path) {
+        Err(err)
+            if err.kind() == std::io::ErrorKind::AddrInUse
+                && UnixStream::connect(path).is_err() =>
+        {
+            std::fs::remove_file(path).and_then(|()| UnixListener::bind(path))
+        }
+        listener => listener,
+    }
+    .map_err(|err| format!("cannot listen on `{path}`: {err}"))?;
+    let mut server = rpc::Server::default();
+    if let Some(address) = metrics {
+        serve_metrics(address, server.metrics())?;
+    }
+    let mut serve = |stream: UnixStream| -> std::io::Result<()> {
+        server.end_game();
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = server.handle(&line) {
+                writeln!(writer, "{response}")?;
+            }
+        }
+        Ok(())
+    };
+    for stream in listener.incoming() {
+        if let Err(err) = stream.and_then(&mut serve) {
+            eprintln!("warning: dropped a connection: {err}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve_socket(_: &str, _: Option<&str>) -> Result<(), String> {
+    Err("`--socket` needs unix sockets, which this system does not have".to_string())
+}
+
+// Runs the puzzles of a batch file, up to `jobs` of them at a time, each as its own process with
+// the arguments of its entry. Every puzzle is checked before any of them runs. Their outputs are
+// printed in the order of the file, each after a header with its command line, or written to the
+// file the entry names. Returns whether all of them succeeded.
+fn run_batch(file: &str, jobs: usize) -> Result<bool, String> {
+    let contents =
+        std::fs::read_to_string(file).map_err(|err| format!("cannot read `{file}`: {err}"))?;
+    let entries = parse_batch(&contents).map_err(|err| format!("{file}: {err}"))?;
+    if entries.is_empty() {
+        return Err(format!("`{file}` has no `[[puzzle]]`"));
+    }
+    for entry in &entries {
+        let options = parse_args(entry.args.iter().cloned())
+            .map_err(|err| format!("{file}:{}: {err}", entry.line))?;
+        if options.batch.is_some() || options.help {
+            return Err(format!(
+                "{file}:{}: a puzzle cannot use `batch` or `help`",
+                entry.line
+            ));
+        }
+    }
+    let exe = std::env::current_exe().map_err(|err| format!("cannot run the puzzles: {err}"))?;
+
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut succeeded = true;
+    std::thread::scope(|scope| -> Result<(), String> {
+        for _ in 0..jobs.min(entries.len()) {
+            let sender = sender.clone();
+            let (next, entries, exe) = (&next, &entries, &exe);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(entry) = entries.get(i) else {
+                    break;
+                };
+                let output = std::process::Command::new(exe).args(&entry.args).output();
+                if sender.send((i, output)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Holds the outputs that are done before the ones before them.
+        let mut done: Vec<Option<std::process::Output>> = entries.iter().map(|_| None).collect();
+        let mut printed = 0;
+        for (i, output) in receiver {
+            let entry = &entries[i];
+            done[i] = Some(output.map_err(|err| {
+                next.store(entries.len(), Ordering::Relaxed);
+                format!("{file}:{}: cannot run the puzzle: {err}", entry.line)
+            })?);
+            while let Some(output) = done.get_mut(printed).and_then(Option::take) {
+                let entry = &entries[printed];
+                let command = std::iter::once("batteries")
+                    .chain(entry.args.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                match &entry.output {
+                    Some(path) => {
+                        std::fs::write(path, &output.stdout)
+                            .map_err(|err| format!("cannot write `{path}`: {err}"))?;
+                        println!("== {file}:{}: {command} > {path}", entry.line);
+                    }
+                    None => {
+                        println!("== {file}:{}: {command}", entry.line);
+                        print!("{}", String::from_utf8_lossy(&output.stdout));
+                    }
+                }
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+                if !output.status.success() {
+                    println!("(exited with {})", output.status);
+                    succeeded = false;
+                }
+                printed += 1;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(succeeded)
+}
+
+// Times the phases of the search with `options` and prints how long one run of each takes. Each
+// phase is repeated until about a second has passed, so the cheap ones are measured over many
+// runs; the candidate loop reuses the table built once, like repeated searches would.
+fn bench(options: &Options) {
+    const PHASE_TIME: Duration = Duration::from_secs(1);
+    fn time(mut run: impl FnMut()) -> (u32, Duration) {
+        let started = Instant::now();
+        let mut runs = 0;
+        while runs == 0 || started.elapsed() < PHASE_TIME {
+            run();
+            runs += 1;
+        }
+        (runs, started.elapsed() / runs)
+    }
+    let row = |phase: &str, size: String, (runs, per_run): (u32, Duration)| {
+        let per_run = format_elapsed(per_run, options.plain);
+        println!("{phase:<20} {size:>18} {runs:>8} {per_run:>12}");
+    };
+
+    let Params {
+        total, good, slots, ..
+    } = options.params;
+    print_assumptions(options);
+    println!(
+        "{:<20} {:>18} {:>8} {:>12}",
+        "Phase", "Size", "Runs", "Per run"
+    );
+    let pairs = binomial(total, slots);
+    row(
+        "pair enumeration",
+        format!("{pairs} tries"),
+        time(|| {
+            std::hint::black_box(CombinationIter::<u64>::new(total, slots).collect::<Vec<_>>());
+        }),
+    );
+    let universes = binomial(total, good);
+    row(
+        "universe enumeration",
+        format!("{universes} universes"),
+        time(|| {
+            std::hint::black_box(CombinationIter::<u64>::new(total, good).collect::<Vec<_>>());
+        }),
+    );
+    row(
+        "universe filtering",
+        format!("{} eliminations", pairs * universes),
+        time(|| {
+            std::hint::black_box(EliminationTable::new(options.params));
+        }),
+    );
+    let table = EliminationTable::new(options.params);
+    let solver = Solver {
+        table: Some(&table),
+        threads: options.threads,
+        ..options.solver()
+    };
+    let mut report = None;
+    let timing = time(|| report = Some(solver.search(|_| {})));
+    let examined = report.map_or(0, |report| report.examined);
+    row("candidate loop", format!("{examined} candidates"), timing);
+    println!(
+        "The candidate loop examined {:.1} million candidates per second.",
+        examined as f64 / timing.1.as_secs_f64() / 1e6
+    );
+}
+
+// Searches with one try fewer at a time with `Solver::min_tries`, starting at the budget of
+// `config`, printing the first strategy found for each budget. The search with the anchored first
+// try examines every candidate, so not finding a strategy proves that there is none. Returns
+// whether that found a strategy within the budget, proved that there is none or ran out of time
+// first. The proof for the first budget without a strategy is written
+// to `certificate`, if it is given. With `check_known`, the fewest tries found are compared with
+// the known ones, exiting with 1 if they differ.
+fn min_tries(
+    config: Solver,
+    labels: &Labels,
+    certificate: Option<&str>,
+    check_known: bool,
+) -> SearchOutcome {
+    let budget = config.params.tries;
+    let mut timed_out = false;
+    let fewest = config.min_tries(|config, first, report| {
+        let tries = config.params.tries;
+        match first {
+            Some(solution) => println!(
+                "{}: {}",
+                count_tries(tries as usize),
+                labels.solution(solution)
+            ),
+            None if report.fewer_tries_suffice => println!(
+                "{}: none that needs all of them, fewer are enough.",
+                count_tries(tries as usize)
+            ),
+            None if report.timed_out => {
+                println!(
+                    "{}: unknown, the time limit was reached after examining {} of {} candidates.",
+                    count_tries(tries as usize),
+                    report.examined,
+                    report.total
+                );
+                timed_out = true;
+            }
+            None => {
+                print!(
+                    "{}: impossible, none of the {} candidates works",
+                    count_tries(tries as usize),
+                    report.total
+                );
+                match report.fewest_remaining {
+                    Some(fewest) => println!(
+                        "; the closest leaves {fewest} universes without enough batteries \
+                        functional in all of them."
+                    ),
+                    None => println!("."),
+                }
+                if let Some(file) = certificate {
+                    match write_certificate(file, config.params, config.anchor) {
+                        Ok(lines) => println!(
+                            "Wrote a certificate that {} {} not enough to `{file}`, with \
+                            {lines} lines of universes.",
+                            count_tries(tries as usize),
+                            if tries == 1 { "is" } else { "are" }
+                        ),
+                        Err(err) => {
+                            eprintln!("error: {err}");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    match fewest {
+        Some(tries) if timed_out => println!(
+            "The toy can always be turned on within {}; whether fewer are enough is unknown.",
+            count_tries(tries as usize)
+        ),
+        Some(tries) => println!(
+            "The toy can always be turned on within {}, and not within fewer.",
+            count_tries(tries as usize)
+        ),
+        None => {
+            println!("No strategy found within {}.", count_tries(budget as usize));
+            if !timed_out {
+                print_adversary_hint(Params {
+                    tries: budget,
+                    ..config.params
+                });
+            }
+        }
+    }
+    if check_known {
+        // The range the fewest tries were narrowed down to, without an upper end if they are
+        // more than the budget.
+        let (lowest, highest) = match fewest {
+            Some(tries) if !timed_out => (tries, Some(tries)),
+            Some(tries) => (1, Some(tries)),
+            None if timed_out => (1, None),
+            None => (budget + 1, None),
+        };
+        match known_discrepancy(config.params, lowest, highest) {
+            Ok(Some(known)) => println!(
+                "This agrees with the {} known to be the fewest.",
+                count_tries(known as usize)
+            ),
+            Ok(None) => println!("The fewest tries for this puzzle are not known."),
+            Err(err) => {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    match fewest {
+        Some(_) => SearchOutcome::Found,
+        None if timed_out => SearchOutcome::Unfinished,
+        None => SearchOutcome::NoneExist,
+    }
+}
+
+// Prints the fewest tries that always turn on the toy for each puzzle with up to `--total`
+// batteries, `--slots` to `--good` of them functional, as a table with a row per number of
+// batteries. Each is found by a binary search between the bounds of `analyze`, where the search
+// for each number of tries stops at the first strategy and skips relabelings, which keeps it
+// exhaustive up to relabeling, so not finding one proves there is none. Within a time limit for
+// the whole table, the numbers not found in time, or too big to search for, are shown as the
+// range they are known to be in. With `--check-known`, each number is compared with the
+// known one, exiting with 1 after the table if any differ.
+fn frontier(options: &Options) {
+    let Params {
+        total, good, slots, ..
+    } = options.params;
+    let deadline = options.time_limit.map(|limit| Instant::now() + limit);
+    let mut unknown = false;
+    // How many of the numbers are known, and how the search contradicts the known ones.
+    let mut known = 0;
+    let mut discrepancies = vec![];
+    let columns: Vec<u64> = (slots..=good).collect();
+
+    println!("Fewest tries that always turn on a toy needing {slots} functional batteries:");
+    print!("{:>15}", "total \\ good");
+    for good in &columns {
+        print!(" {good:>7}");
+    }
+    println!();
+    for total in slots..=total {
+        print!("{total:>15}");
+        for &good in columns.iter().filter(|&&good| good <= total) {
+            let params = Params {
+                total,
+                good,
+                slots,
+                tries: 0,
+            };
+            let Bounds {
+                counting,
+                pigeonhole,
+                ..
+            } = bounds(params);
+            let threads = options
+                .threads
+                .or_else(|| std::thread::available_parallelism().ok());
+            let (fewest, most) = fewest_tries(params, (counting, pigeonhole), deadline, threads);
+            unknown |= fewest != most;
+            if options.check_known {
+                match known_discrepancy(params, fewest, Some(most)) {
+                    Ok(Some(_)) => known += 1,
+                    Ok(None) => {}
+                    Err(err) => discrepancies.push(err),
+                }
+            }
+            match fewest == most {
+                true => print!(" {fewest:>7}"),
+                false => print!(" {:>7}", format!("{fewest}-{most}")),
+            }
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+        }
+        println!();
+    }
+    if unknown {
+        println!(
+            "A range means that the time limit was reached, or that there were too many \
+            candidates to search, before it was narrowed down to a single number of tries."
+        );
+    }
+    if options.check_known {
+        if discrepancies.is_empty() {
+            println!("{known} of the numbers are known, and the table agrees with all of them.");
+        }
+        for err in &discrepancies {
+            eprintln!("error: {err}");
+        }
+        if !discrepancies.is_empty() {
+            std::process::exit(1);
+        }
     }
 }
 
-impl BitAnd for BitSet {
-    type Output = BitSet;
-    fn bitand(self, other: BitSet) -> Self::Output {
-        BitSet(self.0 & other.0)
+// Compares the range `lowest..=highest` that a search narrowed the fewest tries for `params`
+// down to, without an upper end if `highest` is `None`, with the fewest tries known for the
+// puzzle. Returns the known number if it is in the range, and says why the search must have a bug
+// if it is not.
+fn known_discrepancy(
+    params: Params,
+    lowest: u64,
+    highest: Option<u64>,
+) -> Result<Option<u64>, String> {
+    #[cfg(feature = "known-values")]
+    let known = batteries::known::known_fewest_tries(params.total, params.good, params.slots);
+    #[cfg(not(feature = "known-values"))]
+    let known = None;
+    let Some(known) = known else {
+        return Ok(None);
+    };
+    if lowest <= known && highest.is_none_or(|highest| known <= highest) {
+        return Ok(Some(known));
     }
+    let found = match highest {
+        Some(highest) if highest == lowest => count_tries(lowest as usize),
+        Some(highest) => format!("{lowest} to {highest} tries"),
+        None => format!("more than {}", count_tries(lowest as usize - 1)),
+    };
+    Err(format!(
+        "the search found that the fewest tries that always turn on a toy needing {} of {} \
+        batteries, {} of them functional, are {found}, but they are known to be {known}; this is \
+        a bug",
+        params.slots, params.total, params.good
+    ))
 }
 
-impl BitSet {
-    fn len(&self) -> u32 {
-        self.0.count_ones()
+// Looks for `params.tries` tries that turn on the toy in every universe by simulated annealing.
+// The cost of a strategy is the number of universes in which none of its tries contains only
+// functional batteries; a strategy with cost 0 is valid. Each step replaces one try by a random
+// one that is not in the strategy yet. On failure the best strategy seen and its cost are
+// returned.
+fn anneal(
+    params: Params,
+    rng: &mut Rng,
+    cancel: Option<&AtomicBool>,
+) -> Result<Solution, (Solution, usize)> {
+    const RESTARTS: u32 = 50;
+    const STEPS_PER_RESTART: u32 = 20_000;
+    const START_TEMPERATURE: f64 = 2.0;
+    const END_TEMPERATURE: f64 = 0.05;
+
+    let all_battery_pairs: Vec<_> = CombinationIter::new(params.total, params.slots).collect();
+    let all_battery_universes: Vec<_> = CombinationIter::new(params.total, params.good).collect();
+    let works = |pair: usize, universe: &BitSet| {
+        *universe & all_battery_pairs[pair] == all_battery_pairs[pair]
+    };
+    let to_solution = |strategy: &[usize]| -> Solution {
+        strategy
+            .iter()
+            .map(|&pair| all_battery_pairs[pair].into_iter().collect())
+            .collect()
+    };
+
+    let cooling = (END_TEMPERATURE / START_TEMPERATURE).powf(1.0 / f64::from(STEPS_PER_RESTART));
+    let mut best: (Vec<usize>, usize) = (vec![], usize::MAX);
+    for _ in 0..RESTARTS {
+        if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+            break;
+        }
+        let mut strategy = distinct_indices(all_battery_pairs.len() as u64, params.tries, rng);
+        // For each universe, the number of tries in the strategy that work in it.
+        let mut working: Vec<u32> = all_battery_universes
+            .iter()
+            .map(|u| strategy.iter().filter(|&&pair| works(pair, u)).count() as u32)
+            .collect();
+        let mut cost = working.iter().filter(|&&w| w == 0).count();
+
+        let mut temperature = START_TEMPERATURE;
+        for _ in 0..STEPS_PER_RESTART {
+            if cost < best.1 {
+                best = (strategy.clone(), cost);
+            }
+            if cost == 0 {
+                return Ok(to_solution(&strategy));
+            }
+
+            let slot = rng.below(params.tries) as usize;
+            let old = strategy[slot];
+            let new = rng.below(all_battery_pairs.len() as u64) as usize;
+            if strategy.contains(&new) {
+                continue;
+            }
+            let delta: isize = all_battery_universes
+                .iter()
+                .zip(&working)
+                .map(|(u, &w)| {
+                    let w_new = w - u32::from(works(old, u)) + u32::from(works(new, u));
+                    isize::from(w_new == 0) - isize::from(w == 0)
+                })
+                .sum();
+
+            let accept = delta <= 0
+                || (rng.next_u64() as f64 / u64::MAX as f64)
+                    < (-(delta as f64) / temperature).exp();
+            if accept {
+                for (u, w) in all_battery_universes.iter().zip(working.iter_mut()) {
+                    *w = *w - u32::from(works(old, u)) + u32::from(works(new, u));
+                }
+                strategy[slot] = new;
+                cost = cost.wrapping_add_signed(delta);
+            }
+            temperature *= cooling;
+        }
     }
+    Err((to_solution(&best.0), best.1))
 }
 
-impl fmt::Display for BitSet {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:b}", self.0)
+// Picks `k` distinct indices below `n` uniformly at random, in increasing order. It draws the
+// same numbers as `random_combination`, which only works for `n` up to 64, and so picks the same
+// indices where both work.
+fn distinct_indices(n: u64, k: u64, rng: &mut Rng) -> Vec<usize> {
+    let mut indices = Vec::with_capacity(k as usize);
+    for j in n - k..n {
+        let t = rng.below(j + 1) as usize;
+        let pick = if indices.contains(&t) { j as usize } else { t };
+        indices.push(pick);
     }
+    indices.sort_unstable();
+    indices
+}
+
+// Runs several engines on their own threads and returns the name of the one that found a valid
+// strategy first, together with the strategy; the others are cancelled as soon as that happens.
+// The lexicographic engine is exhaustive, so if it finishes without a strategy there is none and
+// the others are cancelled too.
+fn portfolio(params: Params, seed: u64) -> Option<(&'static str, Solution)> {
+    let cancel = AtomicBool::new(false);
+    let winner = Mutex::new(None);
+    let finish = |engine: &'static str, strategy: Option<Solution>| {
+        let mut winner = winner.lock().unwrap();
+        if let (None, Some(strategy)) = (&*winner, strategy) {
+            *winner = Some((engine, strategy));
+            cancel.store(true, Ordering::Relaxed);
+        }
+    };
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut first = None;
+            let config = Solver {
+                params,
+                anchor: (params.tries >= 2).then(|| BitSet((1 << params.slots) - 1)),
+                cancel: Some(&cancel),
+                ..Solver::default()
+            };
+            config.search(|solution| {
+                if first.is_none() {
+                    first = Some(solution);
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            });
+            finish("lexicographic", first);
+            cancel.store(true, Ordering::Relaxed);
+        });
+        scope.spawn(|| finish("greedy", greedy(params, &mut Rng::new(seed), &cancel)));
+        scope.spawn(|| {
+            finish(
+                "shuffled",
+                shuffled(params, &mut Rng::new(seed ^ 1), &cancel),
+            )
+        });
+        scope.spawn(|| {
+            let mut rng = Rng::new(seed ^ 2);
+            while !cancel.load(Ordering::Relaxed) {
+                if let Ok(strategy) = anneal(params, &mut rng, Some(&cancel)) {
+                    finish("annealing", Some(strategy));
+                }
+            }
+        });
+    });
+    winner.into_inner().unwrap()
 }
 
-impl IntoIterator for BitSet {
-    type Item = usize;
-    type IntoIter = BitSetIter;
-    fn into_iter(self) -> Self::IntoIter {
-        BitSetIter(self.0)
+// Builds `params.tries` tries by repeatedly picking a try that works in many of the universes
+// none of the earlier tries work in, and starts over until that covers every universe or the
+// search is cancelled. Always taking the best try never succeeds on the default puzzle (it ends
+// up with 4 disjoint pairs), so the pick is random among the tries that get at least half as far
+// as the best one.
+fn greedy(params: Params, rng: &mut Rng, cancel: &AtomicBool) -> Option<Solution> {
+    let all_battery_pairs: Vec<_> = CombinationIter::new(params.total, params.slots).collect();
+    let all_battery_universes: Vec<_> = CombinationIter::new(params.total, params.good).collect();
+
+    while !cancel.load(Ordering::Relaxed) {
+        let mut uncovered = all_battery_universes.clone();
+        let mut strategy = vec![];
+        for _ in 0..params.tries {
+            let gain = |pair: BitSet| uncovered.iter().filter(|&&u| pair.is_subset(u)).count();
+            let best = all_battery_pairs.iter().map(|&p| gain(p)).max().unwrap();
+            let good: Vec<_> = all_battery_pairs
+                .iter()
+                .filter(|&&p| 2 * gain(p) >= best)
+                .collect();
+            let pair = *good[rng.below(good.len() as u64) as usize];
+            uncovered.retain(|&u| !pair.is_subset(u));
+            strategy.push(pair.into_iter().collect());
+        }
+        if uncovered.is_empty() {
+            return Some(strategy);
+        }
     }
+    None
 }
 
-struct BitSetIter(u64);
-impl Iterator for BitSetIter {
-    type Item = usize;
+// Checks every set of `params.tries` tries like the exhaustive search, but in a random order: the
+// candidate ranks are visited with a random stride coprime to their number, which is a
+// permutation of them.
+fn shuffled(params: Params, rng: &mut Rng, cancel: &AtomicBool) -> Option<Solution> {
+    let all_battery_pairs: Vec<BitSet> = CombinationIter::new(params.total, params.slots).collect();
+    let all_battery_universes: Vec<_> = CombinationIter::new(params.total, params.good).collect();
+    let n = all_battery_pairs.len() as u64;
+    let total = binomial(n, params.tries);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.0 == 0 {
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    let mut stride = 1 + rng.below(total.max(2) - 1);
+    while gcd(stride, total) != 1 {
+        stride += 1;
+    }
+    let mut rank = rng.below(total);
+    for i in 0..total {
+        if i.is_multiple_of(1024) && cancel.load(Ordering::Relaxed) {
             return None;
         }
-        let v = self.0.trailing_zeros();
-        self.0 ^= 1 << v;
-        Some(v as usize)
+        let tries: BitSet<u128> = unrank(rank, n, params.tries);
+        let works = all_battery_universes.iter().all(|&u| {
+            tries
+                .into_iter()
+                .any(|t| u & all_battery_pairs[t] == all_battery_pairs[t])
+        });
+        if works {
+            return Some(
+                tries
+                    .into_iter()
+                    .map(|t| all_battery_pairs[t].into_iter().collect())
+                    .collect(),
+            );
+        }
+        rank = ((u128::from(rank) + u128::from(stride)) % u128::from(total)) as u64;
     }
+    None
 }
 
-// This iterator uses bit tricks to iterate over n-choose-k combinations.
-struct CombinationIter {
-    next_val: u64,
-    n: u64,
+// Prints the state of the search at the candidate with the given rank: the tries it consists of
+// and, after each try fails, the universes that survive and the batteries functional in all of
+// them. Ranks are positions in the enumeration done by `search`, which makes it possible to
+// reproduce and inspect what the search did for one candidate.
+fn dump_candidate(
+    rank: u64,
+    params: Params,
+    anchor: Option<BitSet>,
+    break_symmetry: bool,
+) -> Result<(), String> {
+    let all_battery_pairs: Vec<_> = CombinationIter::new(params.total, params.slots).collect();
+    let all_battery_universes: Vec<_> = CombinationIter::new(params.total, params.good).collect();
+    let free_steps = params.tries - 1 - u64::from(anchor.is_some());
+
+    let total = binomial(all_battery_pairs.len() as u64, free_steps);
+    if rank >= total {
+        return Err(format!(
+            "candidate rank {rank} out of range, there are {total}"
+        ));
+    }
+    let steps: BitSet<u128> = unrank(rank, all_battery_pairs.len() as u64, free_steps);
+
+    let mut tries: Vec<BitSet> = anchor.into_iter().collect();
+    tries.extend(steps.into_iter().map(|pair| all_battery_pairs[pair]));
+    let as_list = |set: BitSet| set.into_iter().collect::<Vec<_>>();
+
+    println!(
+        "Candidate {rank} of {total} (first try {}):",
+        match anchor {
+            Some(anchor) => format!("fixed to {:?}", as_list(anchor)),
+            None => "not fixed".to_string(),
+        }
+    );
+    println!(
+        "  tries: {:?}",
+        tries.iter().map(|&t| as_list(t)).collect::<Vec<_>>()
+    );
+
+    let mut survivors = all_battery_universes.clone();
+    for (i, &pair) in tries.iter().enumerate() {
+        survivors = remove_impossible_universes(pair, survivors);
+        // Mask over the ranks of the surviving universes, bit i standing for the i-th universe,
+        // as long as there are few enough universes for one.
+        let mask = match all_battery_universes.len() <= 128 {
+            true => format!(
+                " (mask {:#x})",
+                survivors.iter().fold(0u128, |acc, u| {
+                    let rank = all_battery_universes.iter().position(|v| v == u).unwrap();
+                    acc | 1 << rank
+                })
+            ),
+            false => String::new(),
+        };
+        let intersection = survivors.iter().cloned().reduce(|acc, v| acc & v);
+        println!(
+            "  after try {} {:?} fails: {} universes left{mask}, functional in all: {:?}",
+            i + 1,
+            as_list(pair),
+            survivors.len(),
+            intersection.map(as_list).unwrap_or_default()
+        );
+    }
+
+    // What the search itself makes of the candidate, which may prune it before getting to the
+    // last tries.
+    let solver = Solver {
+        params,
+        anchor,
+        break_symmetry,
+        ..Solver::default()
+    };
+    solver.check().map_err(|err| err.to_string())?;
+    let event = solver.examine(rank).expect("the rank is in range");
+    let outcome = outcome_name(event.outcome);
+    let pruned_at = event
+        .tries
+        .last()
+        .map(|&pair| as_list(all_battery_pairs[pair]));
+    match event.outcome {
+        TraceOutcome::Solution => {
+            let guaranteed = survivors
+                .iter()
+                .cloned()
+                .reduce(|acc, v| acc & v)
+                .unwrap_or_default();
+            let last: BitSet = guaranteed.into_iter().take(params.slots as usize).collect();
+            println!(
+                "  => {outcome}: {:?} are functional in all of them, so the last try {:?} is \
+                guaranteed to work",
+                as_list(guaranteed),
+                as_list(last)
+            )
+        }
+        TraceOutcome::Failed => println!(
+            "  => {outcome} with {} universes left: fewer than {} batteries are functional in \
+            all of them",
+            event.universes_left, params.slots
+        ),
+        TraceOutcome::RepeatsAnchor => println!(
+            "  => {outcome}: pruned at try {:?}, which repeats the first try",
+            pruned_at.unwrap_or_default()
+        ),
+        TraceOutcome::NotCanonical => println!(
+            "  => {outcome}: pruned at try {:?}, which uses the batteries outside the first try \
+            out of the canonical order",
+            pruned_at.unwrap_or_default()
+        ),
+        TraceOutcome::AlwaysOn => println!(
+            "  => {outcome}: pruned at try {:?}, since the tries up to it always turn on the \
+            toy already",
+            pruned_at.unwrap_or_default()
+        ),
+    }
+    Ok(())
 }
 
-impl CombinationIter {
-    fn new(n: u64, k: u64) -> Self {
-        debug_assert!(n >= k, "k must be smaller than n");
-        debug_assert!(n <= 65, "only n up to 64 is supported");
-        debug_assert!(k > 0, "only positive k is supported");
+// Writes the bipartite graph between the candidate tries and the universes in GraphML. There is an
+// edge between a try and a universe if all batteries of the try are functional in that universe,
+// i.e. a failed try rules the universe out.
+fn write_graphml(file: Option<&str>, params: Params) -> Result<(), String> {
+    use std::fmt::Write;
+
+    let all_battery_pairs: Vec<_> = CombinationIter::new(params.total, params.slots).collect();
+    let all_battery_universes: Vec<_> = CombinationIter::new(params.total, params.good).collect();
+    let batteries = |set: BitSet| {
+        set.into_iter()
+            .map(|b| b.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
 
-        let k_trailing_ones = (1 << k) - 1;
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"  <key id="kind" for="node" attr.name="kind" attr.type="string"/>"#
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"  <key id="batteries" for="node" attr.name="batteries" attr.type="string"/>"#
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"  <graph id="eliminations" edgedefault="undirected">"#
+    )
+    .unwrap();
+    for (kind, prefix, sets) in [
+        ("try", 't', &all_battery_pairs),
+        ("universe", 'u', &all_battery_universes),
+    ] {
+        for (i, &set) in sets.iter().enumerate() {
+            writeln!(
+                out,
+                r#"    <node id="{prefix}{i}"><data key="kind">{kind}</data><data key="batteries">{}</data></node>"#,
+                batteries(set)
+            )
+            .unwrap();
+        }
+    }
+    for (t, &pair) in all_battery_pairs.iter().enumerate() {
+        for (u, &universe) in all_battery_universes.iter().enumerate() {
+            if pair.is_subset(universe) {
+                writeln!(out, r#"    <edge source="t{t}" target="u{u}"/>"#).unwrap();
+            }
+        }
+    }
+    writeln!(out, "  </graph>").unwrap();
+    writeln!(out, "</graphml>").unwrap();
 
-        Self {
-            next_val: k_trailing_ones,
-            n,
+    match file {
+        Some(file) => write_file(file, &out),
+        None => {
+            print!("{out}");
+            Ok(())
         }
     }
 }
 
-// This iterator uses bit tricks to iterate over n-choose-k combinations.
-// The initial value of next_val is 00...01..11 (k trailing 1s). To move from one combination to
-// another we identify the right-most cluster of ones and we shift the cluster's leading bit to the
-// left by one and all other cluster's bits are shifted to least significant positions. For
-// example:
-// xxxx01110000 has cluster 111 and so next state is xxxx10000011
-impl Iterator for CombinationIter {
-    type Item = BitSet;
+// Combines the solutions printed by several (sharded) runs, dropping the ones that are identical up
+// to relabeling batteries. Lines that are not solutions, like headers, are ignored.
+fn merge(files: &[String]) -> Result<(), String> {
+    let mut solutions: Vec<Vec<Vec<usize>>> = vec![];
+    for file in files {
+        let contents = if file == "-" {
+            std::io::read_to_string(std::io::stdin())
+        } else {
+            std::fs::read_to_string(file)
+        }
+        .map_err(|err| format!("cannot read `{file}`: {err}"))?;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.next_val == 0 {
-            return None;
+        for solution in contents.lines().filter_map(parse_solution) {
+            if solutions
+                .iter()
+                .all(|s| !same_solution(&s[..], &solution[..]))
+            {
+                solutions.push(solution);
+            }
         }
+    }
+    println!("Solutions:");
+    for solution in solutions {
+        println!("{solution:?}");
+    }
+    Ok(())
+}
+
+fn mask(elements: &[usize]) -> u64 {
+    elements.iter().fold(0, |acc, &e| acc | 1 << e)
+}
+
+// The states of an interactive game before each of the moves made so far, and the states after
+// the moves that were undone, for `u` and `r`.
+struct History<T> {
+    done: Vec<T>,
+    undone: Vec<T>,
+}
 
-        let val = self.next_val;
+impl<T> History<T> {
+    fn new() -> Self {
+        History {
+            done: vec![],
+            undone: vec![],
+        }
+    }
 
-        // 1. Get least significant 1-bit (last bit of cluster)
-        let one_bit = val & (1 + !val);
+    // Remembers the state before a new move, after which the moves undone so far cannot be
+    // redone any more.
+    fn record(&mut self, before: T) {
+        self.done.push(before);
+        self.undone.clear();
+    }
 
-        // 2. By adding the least significant 1-bit to current state we effectively turn all of
-        //    cluster's bits from 1s to 0s, except for the leftmost bit which gets shifted to the
-        //    left by one. If that bit is not within the rightmost N bits, then we ran out of
-        //    combinations. All the other cluster's bits will be moved to rightmost positions in
-        //    next step.
-        self.next_val = match val.checked_add(one_bit) {
-            // 3. x ^ val gives us the cluster of 1s with an extra 1 prepended. We shift if to the
-            //    right and lose 2 1-bits because the cluster was 1-bit larger, and also because we
-            //    only want to right shift all but the leftmost cluster's bit.
-            Some(x) if x < (1 << self.n) => x | ((x ^ val) >> (one_bit.trailing_zeros() + 2)),
-            Some(_) | None => 0,
+    // Goes back to the state before the last move, if there is one.
+    fn undo(&mut self, state: &mut T) -> bool {
+        let Some(before) = self.done.pop() else {
+            return false;
         };
+        self.undone.push(std::mem::replace(state, before));
+        true
+    }
 
-        Some(val.into())
+    // Makes the last move that was undone again, if there is one.
+    fn redo(&mut self, state: &mut T) -> bool {
+        let Some(after) = self.undone.pop() else {
+            return false;
+        };
+        self.done.push(std::mem::replace(state, after));
+        true
     }
 }
 
-fn remove_impossible_universes(pair: BitSet, mut universes: Vec<BitSet>) -> Vec<BitSet> {
-    let mut i = 0;
-    while i < universes.len() {
-        if universes[i] & pair == pair {
-            // in this universe both batteries worked
-            universes.swap_remove(i);
-        } else {
-            i += 1;
+// Lets the user propose tries one by one. As long as the toy does not turn on, each failed try
+// rules out some configurations of functional batteries; a proposal is good if the configurations
+// left can still all be covered with the remaining tries. Good tries can be undone and redone.
+fn quiz(params: Params, labels: &Labels, language: Language) -> Result<(), String> {
+    use std::io::BufRead;
+
+    let as_list = |set: BitSet| labels.list(set);
+    // The configurations still possible and the number of tries left.
+    let mut state: (Vec<BitSet>, usize) = (
+        CombinationIter::new(params.total, params.good).collect(),
+        params.tries as usize,
+    );
+    let mut history = History::new();
+    let mut lines = std::io::stdin().lock().lines();
+
+    let batteries = match &labels.0 {
+        Some(names) => names.join(", "),
+        None => format!("0..{}", params.total - 1),
+    };
+    let text = |message, args: &[(&str, &dyn std::fmt::Display)]| language.format(message, args);
+    println!(
+        "{}",
+        text(
+            Message::QuizIntro,
+            &[
+                ("good", &params.good),
+                ("total", &params.total),
+                ("batteries", &batteries),
+                ("tries", &params.tries),
+                ("example", &example_try(params, labels)),
+            ]
+        )
+    );
+    while state.1 > 0 {
+        let (survivors, tries_left) = (&state.0, state.1);
+        println!();
+        println!(
+            "{}",
+            text(
+                Message::QuizStillPossible,
+                &[
+                    ("configurations", &survivors.len()),
+                    ("tries", &language.tries(tries_left)),
+                ]
+            )
+        );
+        print!("{}", text(Message::QuizPrompt, &[]));
+        std::io::Write::flush(&mut std::io::stdout()).map_err(|err| err.to_string())?;
+        let Some(line) = lines.next() else {
+            println!();
+            return Ok(());
+        };
+        let line = line.map_err(|err| err.to_string())?;
+        match line.trim() {
+            "q" => return Ok(()),
+            "u" => {
+                if !history.undo(&mut state) {
+                    println!("{}", text(Message::NoTryToUndo, &[]));
+                }
+                continue;
+            }
+            "r" => {
+                if !history.redo(&mut state) {
+                    println!("{}", text(Message::NoTryToRedo, &[]));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let pair = match labels.parse_try(line.trim(), params.slots) {
+            Ok(pair) => pair,
+            Err(err) => {
+                println!("{}", text(Message::TryAgain, &[("error", &err)]));
+                continue;
+            }
+        };
+        if pair.into_iter().any(|b| b as u64 >= params.total) {
+            println!(
+                "{}",
+                text(Message::OnlyBatteries, &[("total", &params.total)])
+            );
+            continue;
+        }
+
+        let rest = remove_impossible_universes(pair, survivors.clone());
+        if rest.is_empty() {
+            println!(
+                "{}",
+                text(Message::QuizCorrect, &[("batteries", &as_list(pair))])
+            );
+            return Ok(());
+        }
+        match cover(&rest, tries_left - 1, params.slots) {
+            Some(plan) => {
+                let plan = plan.into_iter().map(as_list).collect::<Vec<_>>().join(", ");
+                println!(
+                    "{}",
+                    text(
+                        Message::QuizGood,
+                        &[
+                            ("configurations", &rest.len()),
+                            ("tries", &language.tries(tries_left - 1)),
+                            ("plan", &plan),
+                        ]
+                    )
+                );
+                history.record(std::mem::replace(&mut state, (rest, tries_left - 1)));
+            }
+            None => {
+                if rest.len() == survivors.len() {
+                    println!("{}", text(Message::QuizWasted, &[]));
+                } else {
+                    println!(
+                        "{}",
+                        text(
+                            Message::QuizNotGood,
+                            &[
+                                ("configurations", &rest.len()),
+                                ("tries", &language.tries(tries_left - 1)),
+                            ]
+                        )
+                    );
+                }
+                let better = CombinationIter::new(params.total, params.slots).find(|&p| {
+                    cover(
+                        &remove_impossible_universes(p, survivors.clone()),
+                        tries_left - 1,
+                        params.slots,
+                    )
+                    .is_some()
+                });
+                if let Some(better) = better {
+                    println!(
+                        "{}",
+                        text(Message::QuizHint, &[("batteries", &as_list(better))])
+                    );
+                }
+            }
         }
     }
-    universes
+    Ok(())
 }
 
-fn main() {
-    let mut solutions: Vec<Vec<_>> = vec![];
-    let all_battery_pairs: Vec<_> = CombinationIter::new(8, 2).collect();
-
-    // WLOG we can assume that the first battery pair is part of solution
-    let all_battery_universes: Vec<_> =
-        remove_impossible_universes(all_battery_pairs[0], CombinationIter::new(8, 4).collect());
+// Walks the user through the puzzle with the real toy: says which batteries to put in, asks
+// whether the toy turned on and plans the next try from the answers so far. Answers can be undone
+// and redone. With `session`, the game is saved to that file after every answer, and the game
+// saved there is resumed if the file exists.
+fn play(
+    mut params: Params,
+    labels: &Labels,
+    session: Option<&str>,
+    language: Language,
+) -> Result<(), String> {
+    use std::io::BufRead;
 
-    // Next we try all possible quintuples of battery pairs and assume each pair in a quintuple
-    // will not turn on the toy. After that we have used up 6 tries (the quintuple and the one
-    // above), so all that remains is to check if all remaining "universes" contain a battery pair that is functional in each one.
-    let all_five_steps = CombinationIter::new(all_battery_pairs.len() as u64, 5);
-    for five_steps in all_five_steps {
-        let mut all_battery_universes = all_battery_universes.clone();
-        for pair in five_steps {
-            all_battery_universes =
-                remove_impossible_universes(all_battery_pairs[pair], all_battery_universes);
+    let saved = match session {
+        Some(file) if std::path::Path::new(file).exists() => {
+            let saved =
+                Session::parse(&read_input(file)?).map_err(|err| format!("`{file}` {err}"))?;
+            if params != Params::default() && params != saved.params {
+                return Err(format!(
+                    "`{file}` holds a game of another puzzle; leave out the puzzle parameters to \
+                    resume it"
+                ));
+            }
+            params = saved.params;
+            Some(saved)
+        }
+        _ => None,
+    };
+    // The game and the tries that failed in it.
+    let mut state = (Game::new(params), vec![]);
+    let mut history = History::new();
+    let mut lines = std::io::stdin().lock().lines();
+    let save = |failed: &[BitSet], turned_on: Option<BitSet>| match session {
+        Some(file) => {
+            let session = Session {
+                params,
+                failed: failed.to_vec(),
+                turned_on,
+            };
+            std::fs::write(file, session.to_string())
+                .map_err(|err| format!("cannot write `{file}`: {err}"))
         }
+        None => Ok(()),
+    };
 
-        match all_battery_universes
-            .iter()
-            .cloned()
-            .reduce(|acc, v| acc & v)
-        {
-            Some(x) if x.len() >= 2 => {
-                let mut solution = vec![];
-                solution.push(all_battery_pairs[0].into_iter().collect::<Vec<_>>());
-                for pair in five_steps {
-                    solution.push(all_battery_pairs[pair].into_iter().collect::<Vec<_>>());
+    let batteries = match &labels.0 {
+        Some(names) => names.join(", "),
+        None => format!("0..{}", params.total - 1),
+    };
+    let text = |message, args: &[(&str, &dyn std::fmt::Display)]| language.format(message, args);
+    let (yes, no) = (language.text(Message::Yes), language.text(Message::No));
+    println!(
+        "{}",
+        text(
+            Message::PlayIntro,
+            &[("batteries", &batteries), ("yes", &yes), ("no", &no)]
+        )
+    );
+    if let Some(saved) = saved {
+        let file = session.expect("only sessions are saved");
+        if let Some(pair) = saved.turned_on {
+            let number = saved.failed.len() + 1;
+            println!(
+                "{}",
+                text(
+                    Message::PlayOver,
+                    &[
+                        ("file", &file),
+                        ("batteries", &labels.list(pair)),
+                        ("tries", &language.tries(number)),
+                        ("number", &number),
+                    ]
+                )
+            );
+            return Ok(());
+        }
+        for pair in saved.failed {
+            history.record(state.clone());
+            state.0.record_failure(pair);
+            state.1.push(pair);
+        }
+        println!(
+            "{}",
+            text(
+                Message::PlayResuming,
+                &[("file", &file), ("tries", &language.tries(state.1.len()))]
+            )
+        );
+    } else if let Some(file) = session {
+        save(&state.1, None)?;
+        println!("{}", text(Message::PlaySaving, &[("file", &file)]));
+    }
+    'game: loop {
+        let game = &state.0;
+        let next = game.next_try().ok_or(text(Message::PlayNoTry, &[]))?;
+        let number = params.tries - game.tries_left() + 1;
+        let guaranteed = game.universes().iter().all(|&u| u & next == next);
+        println!();
+        println!(
+            "{}",
+            text(
+                match guaranteed {
+                    true => Message::PlayTryGuaranteed,
+                    false => Message::PlayTry,
+                },
+                &[
+                    ("configurations", &game.universes().len()),
+                    ("number", &number),
+                    ("tries", &params.tries),
+                    ("batteries", &labels.list(next)),
+                ]
+            )
+        );
+        let turned_on = loop {
+            print!(
+                "{}",
+                text(Message::PlayPrompt, &[("yes", &yes), ("no", &no)])
+            );
+            std::io::Write::flush(&mut std::io::stdout()).map_err(|err| err.to_string())?;
+            let Some(line) = lines.next() else {
+                println!();
+                return Ok(());
+            };
+            match line.map_err(|err| err.to_string())?.trim() {
+                answer if answer == yes => break true,
+                answer if answer == no => break false,
+                "u" if history.undo(&mut state) => {
+                    save(&state.1, None)?;
+                    continue 'game;
+                }
+                "u" => println!("{}", text(Message::NoAnswerToUndo, &[])),
+                "r" if history.redo(&mut state) => {
+                    save(&state.1, None)?;
+                    continue 'game;
                 }
-                solution.push(x.into_iter().collect::<Vec<_>>());
+                "r" => println!("{}", text(Message::NoAnswerToRedo, &[])),
+                "q" => return Ok(()),
+                _ => println!(
+                    "{}",
+                    text(Message::PlayPleaseAnswer, &[("yes", &yes), ("no", &no)])
+                ),
+            }
+        };
+        if turned_on {
+            save(&state.1, Some(next))?;
+            println!(
+                "{}",
+                text(
+                    Message::PlayDone,
+                    &[
+                        ("tries", &language.tries(number as usize)),
+                        ("number", &number)
+                    ]
+                )
+            );
+            return Ok(());
+        }
+        history.record(state.clone());
+        state.0.record_failure(next);
+        state.1.push(next);
+        // The saved game stays at the answers before, which can then be given again.
+        if state.0.universes().is_empty() {
+            return Err(text(Message::PlayInconsistent, &[]));
+        }
+        save(&state.1, None)?;
+    }
+}
 
-                if solutions
-                    .iter()
-                    .all(|s| !same_solution(&s[..], &solution[..]))
-                {
-                    solutions.push(solution);
+// Lets the user make the tries against the worst case. Instead of fixing the functional batteries
+// up front, the adversary only commits to the configurations still consistent with its answers:
+// each try gets the answer that keeps the most of them possible, which with this toy means that
+// it stays off as long as some configuration without the try's batteries is left. The user loses
+// once `params.tries` tries failed. At the end the number of tries is compared with the fewest
+// any strategy needs against it.
+fn play_adversary(params: Params, labels: &Labels, language: Language) -> Result<(), String> {
+    use std::io::BufRead;
+
+    let all: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+    let best = (1..)
+        .find(|&tries| cover(&all, tries, params.slots).is_some())
+        .expect("trying every pair always turns on the toy");
+    // The configurations still possible and the number of the next try.
+    let mut state = (all, 1);
+    let mut history = History::new();
+    let mut lines = std::io::stdin().lock().lines();
+
+    let batteries = match &labels.0 {
+        Some(names) => names.join(", "),
+        None => format!("0..{}", params.total - 1),
+    };
+    let text = |message, args: &[(&str, &dyn std::fmt::Display)]| language.format(message, args);
+    println!(
+        "{}",
+        text(
+            Message::AdversaryIntro,
+            &[
+                ("good", &params.good),
+                ("batteries", &batteries),
+                ("tries", &language.tries(params.tries as usize)),
+                ("example", &example_try(params, labels)),
+            ]
+        )
+    );
+    loop {
+        let (survivors, number) = (&state.0, state.1);
+        println!();
+        println!(
+            "{}",
+            text(
+                Message::StillPossible,
+                &[("configurations", &survivors.len())]
+            )
+        );
+        print!("{}", text(Message::AdversaryPrompt, &[("number", &number)]));
+        std::io::Write::flush(&mut std::io::stdout()).map_err(|err| err.to_string())?;
+        let Some(line) = lines.next() else {
+            println!();
+            return Ok(());
+        };
+        let line = line.map_err(|err| err.to_string())?;
+        match line.trim() {
+            "q" => return Ok(()),
+            "u" => {
+                if !history.undo(&mut state) {
+                    println!("{}", text(Message::NoTryToUndo, &[]));
+                }
+                continue;
+            }
+            "r" => {
+                if !history.redo(&mut state) {
+                    println!("{}", text(Message::NoTryToRedo, &[]));
                 }
+                continue;
             }
-            Some(_) | None => {}
+            _ => {}
+        }
+        let pair = match labels.parse_try(line.trim(), params.slots) {
+            Ok(pair) => pair,
+            Err(err) => {
+                println!("{}", text(Message::TryAgain, &[("error", &err)]));
+                continue;
+            }
+        };
+        if pair.into_iter().any(|b| b as u64 >= params.total) {
+            println!(
+                "{}",
+                text(Message::OnlyBatteries, &[("total", &params.total)])
+            );
+            continue;
+        }
+
+        let rest = remove_impossible_universes(pair, survivors.clone());
+        if !rest.is_empty() {
+            if rest.len() == survivors.len() {
+                println!("{}", text(Message::AdversaryNothingNew, &[]));
+            } else {
+                println!(
+                    "{}",
+                    text(
+                        Message::AdversaryRulesOut,
+                        &[("configurations", &(survivors.len() - rest.len()))]
+                    )
+                );
+            }
+            if number as u64 == params.tries {
+                println!();
+                println!(
+                    "{}",
+                    text(
+                        Message::AdversaryLost,
+                        &[
+                            ("tries", &language.tries(number)),
+                            ("batteries", &labels.list(rest[0])),
+                        ]
+                    )
+                );
+                let message = match best > number {
+                    true => Message::AdversaryLostOptimal,
+                    false => Message::AdversaryLostBetter,
+                };
+                println!("{}", text(message, &[("best", &language.tries(best))]));
+                return Ok(());
+            }
+            history.record(std::mem::replace(&mut state, (rest, number + 1)));
+            continue;
+        }
+        println!(
+            "{}",
+            text(
+                Message::AdversaryTurnsOn,
+                &[
+                    ("tries", &language.tries(number)),
+                    ("number", &number),
+                    ("batteries", &labels.list(pair)),
+                ]
+            )
+        );
+        if number > best {
+            println!(
+                "{}",
+                text(
+                    Message::AdversaryWonBetter,
+                    &[("best", &language.tries(best)), ("fewer", &(number - best))]
+                )
+            );
+        } else {
+            println!("{}", text(Message::AdversaryWonOptimal, &[]));
+        }
+        return Ok(());
+    }
+}
+
+// Suggests playing against the adversary of `play --adversarial` to see why there is no strategy
+// within `params.tries` tries.
+fn print_adversary_hint(params: Params) {
+    let default = Params::default();
+    let mut command = "batteries play --adversarial".to_string();
+    for (name, value, default) in [
+        ("total", params.total, default.total),
+        ("good", params.good, default.good),
+        ("slots", params.slots, default.slots),
+    ] {
+        if value != default {
+            command.push_str(&format!(" --{name} {value}"));
+        }
+    }
+    command.push_str(&format!(" --tries {}", params.tries));
+    println!(
+        "To see why {} {} not enough, play against a toy that stays off as long as it can: \
+        `{command}`",
+        count_tries(params.tries as usize),
+        if params.tries == 1 { "is" } else { "are" }
+    );
+}
+
+// The language of `quiz` and `play`: the one of `--lang`, or else of the locale if the messages
+// are translated into it.
+fn language(options: &Options) -> Language {
+    options.language.unwrap_or_else(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Language::parse(&value))
+            .unwrap_or_default()
+    })
+}
+
+// A try to show how tries are entered: the first batteries, separated by commas.
+fn example_try(params: Params, labels: &Labels) -> String {
+    let names: Vec<String> = (0..params.slots as usize).map(|b| labels.name(b)).collect();
+    names.join(",")
+}
+
+// "1 try", "2 tries", ...
+fn count_tries(n: usize) -> String {
+    if n == 1 {
+        "1 try".to_string()
+    } else {
+        format!("{n} tries")
+    }
+}
+
+// Reads the first solution line of a file, or of stdin for "-". A file without one may hold a
+// decision tree as printed by `--adaptive` instead.
+fn read_strategy(file: &str) -> Result<Solution, String> {
+    parse_strategy(file, &read_input(file)?)
+}
+
+// Reads all of a file, or of stdin for "-".
+fn read_input(file: &str) -> Result<String, String> {
+    if file == "-" {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(file)
+    }
+    .map_err(|err| format!("cannot read `{file}`: {err}"))
+}
+
+// Finds the strategy in the `contents` of `file` like `read_strategy`.
+fn parse_strategy(file: &str, contents: &str) -> Result<Solution, String> {
+    contents
+        .lines()
+        .find_map(parse_solution)
+        .or_else(|| parse_tree(contents))
+        .ok_or_else(|| format!("no strategy found in `{file}`"))
+}
+
+// Like `read_strategy`, for a strategy that has to be played with the batteries of the puzzle
+// with `params`.
+fn read_puzzle_strategy(file: &str, params: Params) -> Result<Solution, String> {
+    let mut strategy = read_strategy(file)?;
+    // Of the guaranteed batteries the solver prints last, only the first ones are tried.
+    if let Some(last) = strategy.last_mut() {
+        last.truncate(params.slots as usize);
+    }
+    match strategy
+        .iter()
+        .flatten()
+        .find(|&&b| b as u64 >= params.total)
+    {
+        Some(b) => Err(format!(
+            "`{file}` uses battery {b}, but there are only {} batteries",
+            params.total
+        )),
+        None => Ok(strategy),
+    }
+}
+
+// Reads the tries of a decision tree as printed by `--adaptive`. Once the toy turns on there is
+// nothing left to decide, so the tree is a single chain of tries along the `Off` branches: each
+// try is followed by its `Off:` line two columns further in, and that by the next try.
+fn parse_tree(contents: &str) -> Option<Solution> {
+    let mut lines = contents.lines().map(|line| {
+        let text = line.trim_start();
+        (line.len() - text.len(), text.trim_end())
+    });
+    let parse_try = |text: &str| parse_solution(&format!("[{text}]"))?.pop();
+
+    let (mut indent, root) = lines.find(|(_, text)| parse_try(text).is_some())?;
+    let mut tries = vec![parse_try(root)?];
+    while let Some((_, text)) = lines.find(|&(i, _)| i <= indent + 2) {
+        if text != "Off:" {
+            continue;
+        }
+        match lines.next() {
+            Some((i, text)) if i == indent + 4 => tries.push(parse_try(text)?),
+            _ => return None,
+        }
+        indent += 4;
+    }
+    Some(tries)
+}
+
+// Prints what can be said about a puzzle without searching: how big it is and bounds on the
+// number of tries it needs.
+// Simple bounds on the fewest tries that always turn on the toy: with fewer than `counting` there
+// is no strategy, and the `pigeonhole` tries that `reason` describes are one.
+struct Bounds {
+    counting: u64,
+    pigeonhole: u64,
+    reason: String,
+}
+
+fn bounds(params: Params) -> Bounds {
+    let Params {
+        total, good, slots, ..
+    } = params;
+    let universes = binomial(total, good);
+    let per_try = binomial(total - slots, good - slots);
+    // Every universe needs a try that works in it.
+    let counting = universes.div_ceil(per_try);
+    // Split the batteries into as many packs as possible such that some pack is sure to get
+    // `slots` functional batteries, and try all combinations within each pack. With a single
+    // slot, any `total - good + 1` batteries hold a functional one.
+    let (pigeonhole, reason) = match slots {
+        1 => (
+            total - good + 1,
+            format!("one of any {} batteries is functional", total - good + 1),
+        ),
+        _ => {
+            let packs = (good - 1) / (slots - 1);
+            let tries = (0..packs)
+                .map(|pack| binomial(total / packs + u64::from(pack < total % packs), slots))
+                .sum();
+            let reason = format!(
+                "all tries within {packs} packs, one of which gets {slots} functional batteries"
+            );
+            (tries, reason)
+        }
+    };
+    Bounds {
+        counting,
+        pigeonhole,
+        reason,
+    }
+}
+
+// Checks the counting bound of `analyze` before a search and, unless `--quiet` or `--format json`
+// is given, explains why there is no strategy when `--tries` is below it.
+fn feasible(options: &Options) -> bool {
+    let Params {
+        total,
+        good,
+        slots,
+        tries,
+    } = options.params;
+    let Bounds { counting, .. } = bounds(options.params);
+    if tries >= counting {
+        return true;
+    }
+    if !options.quiet && !matches!(options.format, Format::Json) {
+        println!(
+            "No strategy always turns on the toy within {}: each try turns it on in {} of the {} \
+            universes, so covering all of them takes at least {}. Nothing was searched.",
+            count_tries(tries as usize),
+            binomial(total - slots, good - slots),
+            binomial(total, good),
+            count_tries(counting as usize)
+        );
+        print_adversary_hint(options.params);
+    }
+    false
+}
+
+fn analyze(params: Params) {
+    let Params {
+        total,
+        good,
+        slots,
+        tries,
+    } = params;
+    let universes = binomial(total, good);
+    let pairs = binomial(total, slots);
+    // A try turns on the toy in the universes where its batteries are among the functional ones.
+    let per_try = binomial(total - slots, good - slots);
+    let Bounds {
+        counting,
+        pigeonhole,
+        reason,
+    } = bounds(params);
+    // The number of candidates quickly outgrows u64, and then only its size matters.
+    let candidates = |k: u64| {
+        let count = (0..k).fold(1.0, |acc, i| acc * (pairs - i) as f64 / (i + 1) as f64);
+        match count < 2f64.powi(53) {
+            true => format!("{}", count.round()),
+            false => format!("{count:.3e}"),
+        }
+    };
+
+    println!("{good} of {total} batteries are functional, the toy needs {slots}, {tries} tries.");
+    println!("Universes (sets of functional batteries): {universes}");
+    println!(
+        "Bits to tell them apart: {:.2}, so identifying the functional batteries takes at least \
+        {} yes/no tests",
+        (universes as f64).log2(),
+        64 - (universes - 1).leading_zeros()
+    );
+    println!("Possible tries: {pairs}, each working in {per_try} universes");
+    println!(
+        "Candidates searched: {} with the first try fixed, {} without",
+        candidates(tries.saturating_sub(2)),
+        candidates(tries - 1)
+    );
+    println!(
+        "Lower bound: {} (each universe needs a try that works in it)",
+        count_tries(counting as usize)
+    );
+    println!(
+        "Upper bound: {} ({reason})",
+        count_tries(pigeonhole as usize)
+    );
+    if tries < counting {
+        println!("With {} there is no strategy.", count_tries(tries as usize));
+    } else if tries >= pigeonhole {
+        println!("With {} there is a strategy.", count_tries(tries as usize));
+    } else {
+        println!(
+            "Whether {} are enough takes a search to tell.",
+            count_tries(tries as usize)
+        );
+    }
+}
+
+// Checks the strategy in `file` against every universe, only counting the first `params.tries`
+// tries (see `puzzle_tries`), and prints either how many tries it needs or a universe where it
+// fails. A certificate written with `--certificate` is checked instead.
+fn verify(file: &str, params: Params) -> Result<(), String> {
+    let contents = read_input(file)?;
+    if contents.starts_with(certificate::HEADER) {
+        return verify_certificate(file, &contents);
+    }
+    let tries = puzzle_tries(parse_strategy(file, &contents)?, params)?;
+    let universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+    match validate::is_valid_strategy(params, &tries) {
+        Ok(()) => {}
+        Err(Violation::Uncovered { universe, count }) => {
+            println!(
+                "The strategy fails in {count} of the {} universes, for example when batteries \
+                {:?} are the functional ones: none of its tries turns on the toy.",
+                universes.len(),
+                universe.into_iter().collect::<Vec<_>>()
+            );
+            std::process::exit(1);
+        }
+        Err(violation) => return Err(violation.to_string()),
+    }
+    let needed: Vec<usize> = universes
+        .iter()
+        .filter_map(|&universe| tries.iter().position(|&pair| pair.is_subset(universe)))
+        .map(|i| i + 1)
+        .collect();
+    println!(
+        "The strategy turns on the toy in all {} universes, within {} in the worst case and \
+        {:.2} tries on average.",
+        universes.len(),
+        count_tries(needed.iter().copied().max().unwrap_or(0)),
+        needed.iter().sum::<usize>() as f64 / needed.len() as f64
+    );
+    Ok(())
+}
+
+// Writes a certificate that no strategy always turns on the toy within `params.tries` tries to
+// `file` and returns how many lines of universes it has.
+fn write_certificate(file: &str, params: Params, anchor: Option<BitSet>) -> Result<u64, String> {
+    let certificate = Certificate::build(params, anchor)
+        .map_err(|rank| format!("candidate {rank} is a solution"))?;
+    std::fs::write(file, certificate.to_string())
+        .map_err(|err| format!("cannot write `{file}`: {err}"))?;
+    Ok(certificate.lines.len() as u64)
+}
+
+// The lines of a file written by this program that are neither empty nor comments, with their
+// line numbers.
+fn content_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn invalid_line(file: &str, line: usize, what: &str) -> String {
+    format!("`{file}` line {line}: {what}")
+}
+
+// Reads the next line of `lines` as `NAME VALUE` and returns its number and the value.
+fn field<'a>(
+    file: &str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    name: &str,
+) -> Result<(usize, &'a str), String> {
+    match lines.next() {
+        Some((n, line)) => line
+            .strip_prefix(name)
+            .filter(|rest| rest.starts_with(' '))
+            .map(|rest| (n, rest.trim()))
+            .ok_or_else(|| invalid_line(file, n, &format!("expected `{name}`"))),
+        None => Err(format!("`{file}` ends before `{name}`")),
+    }
+}
+
+// Reads the parameters of the puzzle from the next lines of `lines`, one `NAME VALUE` line each.
+fn read_params<'a>(
+    file: &str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Result<Params, String> {
+    let mut number = |name: &str| {
+        let (n, value) = field(file, lines, name)?;
+        value
+            .parse::<u64>()
+            .map_err(|_| invalid_line(file, n, &format!("invalid `{name}`")))
+    };
+    Ok(Params {
+        total: number("total")?,
+        good: number("good")?,
+        slots: number("slots")?,
+        tries: number("tries")?,
+    })
+}
+
+// Checks a certificate written with `--certificate FILE` by going through all its candidates,
+// printing where it fails and exiting with 1 if it does not hold.
+fn verify_certificate(file: &str, contents: &str) -> Result<(), String> {
+    let invalid = |line: usize, what: &str| invalid_line(file, line, what);
+    let mut lines = content_lines(contents);
+    let params = read_params(file, &mut lines)?;
+    let (n, anchor) = field(file, &mut lines, "anchor")?;
+    let anchor = match anchor {
+        "none" => None,
+        anchor => Some(parse_try(anchor, params.slots).map_err(|err| invalid(n, &err))?),
+    };
+    Solver {
+        params,
+        anchor,
+        ..Solver::default()
+    }
+    .check()
+    .map_err(|err| format!("`{file}` is not about a puzzle the search handles: {err}"))?;
+    if binomial(params.total, params.good) > 1_000_000 {
+        return Err(format!(
+            "`{file}` is about a puzzle with too many universes to check"
+        ));
+    }
+    let universes = binomial(params.total, params.good) as usize;
+
+    let mut witnesses: Vec<(u64, Vec<usize>)> = vec![];
+    for (n, line) in lines {
+        let expected = || {
+            invalid(
+                n,
+                "expected a candidate rank and universe ranks like `12: 3 40`",
+            )
         };
+        let (rank, witness) = line.split_once(':').ok_or_else(expected)?;
+        let rank: u64 = rank.trim().parse().map_err(|_| expected())?;
+        let witness: Vec<usize> = witness
+            .split_whitespace()
+            .map(|u| u.parse().ok().filter(|&u| u < universes))
+            .collect::<Option<_>>()
+            .ok_or_else(expected)?;
+        if witnesses.last().is_some_and(|&(last, _)| last >= rank) {
+            return Err(invalid(n, "the candidate ranks have to increase"));
+        }
+        witnesses.push((rank, witness));
     }
-    println!("Solutions:");
-    for solution in solutions {
-        println!("{solution:?}");
+
+    let certificate = Certificate {
+        params,
+        anchor,
+        lines: witnesses,
+    };
+    let checked = match certificate.verify() {
+        Ok(checked) => checked,
+        Err(Flaw::NoSuchCandidate { rank }) => {
+            return Err(format!(
+                "`{file}` has a line for candidate {rank}, which does not exist"
+            ))
+        }
+        Err(flaw) => {
+            println!("The certificate does not hold: {flaw}.");
+            std::process::exit(1);
+        }
+    };
+    let candidates = match anchor {
+        Some(anchor) => format!(
+            "candidates with the first try {:?} that do not repeat it",
+            anchor.into_iter().collect::<Vec<_>>()
+        ),
+        None => "candidates".to_string(),
+    };
+    println!(
+        "The certificate holds: each of the {checked} {candidates} leaves universes in which no \
+        try turns on the toy, so no strategy always turns on the toy within {}.",
+        count_tries(params.tries as usize)
+    );
+    Ok(())
+}
+
+// Reads the strategy in `file` as tries of `params.slots` of the `params.total` batteries, keeping
+// only the first `params.tries` of them.
+fn read_tries(file: &str, params: Params) -> Result<Vec<BitSet>, String> {
+    puzzle_tries(read_strategy(file)?, params)
+}
+
+// The tries of `strategy` for the puzzle with `params`, like `read_tries`.
+fn puzzle_tries(strategy: Solution, params: Params) -> Result<Vec<BitSet>, String> {
+    for (i, batteries) in strategy.iter().enumerate() {
+        if let Some(b) = batteries.iter().find(|&&b| b as u64 >= params.total) {
+            return Err(format!(
+                "there is no battery {b} among {} batteries",
+                params.total
+            ));
+        }
+        let set: BitSet = batteries.iter().copied().collect();
+        // The last entry may hold all the batteries guaranteed to be functional by then, as the
+        // solver prints it.
+        let fits = match i + 1 == strategy.len() {
+            true => batteries.len() as u64 >= params.slots,
+            false => batteries.len() as u64 == params.slots,
+        };
+        if !fits || set.len() as usize != batteries.len() {
+            return Err(format!(
+                "the try {batteries:?} does not hold {} distinct batteries",
+                params.slots
+            ));
+        }
+    }
+    let mut tries = solution_tries(&strategy, params.slots);
+    let budget = params.tries as usize;
+    if tries.len() > budget {
+        println!(
+            "The strategy has {}, only the first {budget} count.",
+            count_tries(tries.len())
+        );
+        tries.truncate(budget);
     }
+    Ok(tries)
 }
 
-fn same_solution(a: &[Vec<usize>], b: &[Vec<usize>]) -> bool {
-    fn are_aligned(a: &[Vec<usize>], b: &[Vec<usize>], map: &[usize]) -> bool {
-        if a.len() != b.len() {
-            panic!("{a:?} {b:?}");
+// Prints how often the first `params.tries` tries of the strategy in `file` turn on the toy, after
+// each of them. This is exact if there are at most a million universes and no `trials` are asked
+// for, and otherwise estimated from that many random universes (100000 by default).
+fn evaluate(
+    file: &str,
+    params: Params,
+    trials: Option<u64>,
+    seed: u64,
+    plain: bool,
+) -> Result<(), String> {
+    let tries = read_tries(file, params)?;
+    let universes = binomial(params.total, params.good);
+    let evaluation = match trials {
+        None if universes <= 1_000_000 => Evaluation::exact(params, &tries),
+        _ => {
+            let samples = trials.unwrap_or(100_000);
+            println!("Sampling {samples} of the {universes} universes (seed {seed}).");
+            Evaluation::sampled(params, &tries, samples, &mut Rng::new(seed))
         }
+    };
 
-        for edge_a in a {
-            let &[a, aa] = &edge_a[..] else { panic!("edge should have two numbers");};
-            let mapped_edge = [map[a], map[aa]];
-            let mapped_edge_rev = [map[aa], map[a]];
-            if !b
-                .iter()
-                .any(|b_edge| b_edge[..] == mapped_edge || b_edge[..] == mapped_edge_rev)
-            {
-                return false;
+    println!("after  try           turned on");
+    for (i, pair) in tries.iter().enumerate() {
+        let batteries = format!("{:?}", pair.into_iter().collect::<Vec<_>>());
+        println!(
+            "{:>5}  {batteries:<12} {:>8.3}%",
+            i + 1,
+            100.0 * evaluation.success_rate(i + 1)
+        );
+    }
+    let rate = evaluation.success_rate(tries.len());
+    let on = evaluation.turned_on.iter().sum::<u64>();
+    if evaluation.exact {
+        println!(
+            "The strategy turns on the toy in {on} of the {} universes ({:.3}%).",
+            evaluation.universes,
+            100.0 * rate
+        );
+    } else {
+        // Normal approximation of the binomial proportion.
+        let margin = 1.96 * (rate * (1.0 - rate) / evaluation.universes.max(1) as f64).sqrt();
+        println!(
+            "The strategy turns on the toy in {:.3}% {} {:.3}% of the universes (95% confidence).",
+            100.0 * rate,
+            if plain { "+/-" } else { "±" },
+            100.0 * margin
+        );
+    }
+    Ok(())
+}
+
+// Plays each strategy against random universes on a toy that stays off with probability
+// `failure_rate` even when all its batteries work, and prints how often it still turned on the toy
+// and after how many tries on average. The exact success rate is printed too: in each universe
+// the strategy only fails if every working try fails.
+fn simulate(
+    files: &[String],
+    params: Params,
+    failure_rate: f64,
+    trials: u64,
+    seed: u64,
+) -> Result<(), String> {
+    let strategies = files
+        .iter()
+        .map(|file| read_puzzle_strategy(file, params))
+        .collect::<Result<Vec<_>, _>>()?;
+    let all_battery_universes: Vec<_> = CombinationIter::new(params.total, params.good).collect();
+    let to_set = |batteries: &Vec<usize>| batteries.iter().copied().collect::<BitSet>();
+
+    println!("Failure rate {failure_rate}, {trials} games per strategy (seed {seed}):");
+    println!("simulated     exact  mean tries  strategy");
+    for (file, strategy) in files.iter().zip(&strategies) {
+        let tries: Vec<BitSet> = strategy.iter().map(to_set).collect();
+        let works = |pair: BitSet, universe: BitSet| pair.is_subset(universe);
+
+        let mut rng = Rng::new(seed);
+        let (mut successes, mut used) = (0u64, 0u64);
+        for _ in 0..trials {
+            let universe = random_combination(params.total, params.good, &mut rng);
+            let turned_on = tries.iter().position(|&pair| {
+                works(pair, universe) && rng.next_u64() as f64 / u64::MAX as f64 >= failure_rate
+            });
+            successes += u64::from(turned_on.is_some());
+            used += turned_on.map_or(tries.len(), |i| i + 1) as u64;
+        }
+
+        let exact = all_battery_universes
+            .iter()
+            .map(|&u| {
+                let working = tries.iter().filter(|&&pair| works(pair, u)).count();
+                1.0 - failure_rate.powi(working as i32)
+            })
+            .sum::<f64>()
+            / all_battery_universes.len() as f64;
+        println!(
+            "{:>8.3}% {:>8.3}% {:>11.3}  {file}",
+            100.0 * successes as f64 / trials.max(1) as f64,
+            100.0 * exact,
+            used as f64 / trials.max(1) as f64
+        );
+    }
+    Ok(())
+}
+
+// Plays a single game with each strategy on the flaky toy of `simulate`, with the same secretly
+// chosen functional batteries for all of them, and prints what happened at each try. The secret
+// is only revealed at the end, like it would be for someone following the strategy.
+fn trace_game(
+    files: &[String],
+    params: Params,
+    failure_rate: f64,
+    seed: u64,
+) -> Result<(), String> {
+    let strategies = files
+        .iter()
+        .map(|file| read_puzzle_strategy(file, params))
+        .collect::<Result<Vec<_>, _>>()?;
+    let universe = random_combination(params.total, params.good, &mut Rng::new(seed));
+    let all = if params.slots == 2 { "both" } else { "all" };
+
+    println!("Failure rate {failure_rate}, one game per strategy (seed {seed}):");
+    for (file, strategy) in files.iter().zip(&strategies) {
+        println!();
+        println!("{file}:");
+        let mut rng = Rng::new(seed);
+        let mut turned_on = None;
+        for (i, pair) in strategy.iter().take(params.tries as usize).enumerate() {
+            let works = pair.iter().all(|&b| universe.contains(b));
+            let flaky = works && (rng.next_u64() as f64 / u64::MAX as f64) < failure_rate;
+            let outcome = match (works, flaky) {
+                (true, false) => "on".to_string(),
+                (true, true) => format!("off (although {all} batteries work)"),
+                (false, _) => "off".to_string(),
+            };
+            println!("  {}. {pair:?}: {outcome}", i + 1);
+            if works && !flaky {
+                turned_on = Some(i + 1);
+                break;
             }
         }
-        true
+        match turned_on {
+            Some(tries) => println!(
+                "  The toy turned on after {}, within the budget of {}.",
+                count_tries(tries),
+                params.tries
+            ),
+            None => println!(
+                "  The toy did not turn on within {}.",
+                count_tries(params.tries as usize)
+            ),
+        }
     }
+    println!();
+    println!(
+        "The functional batteries were {:?}.",
+        universe.into_iter().collect::<Vec<_>>()
+    );
+    Ok(())
+}
 
-    let mut map: Vec<usize> = (0..8).collect();
-    if are_aligned(a, b, &map) {
-        return true;
+// Decides whether the strategies in two files are identical up to relabeling batteries and
+// reordering tries, printing a relabeling that turns the first into the second if so.
+// The batteries neither strategy uses, up to the largest one that one of them does, are relabeled
+// to each other in increasing order.
+fn isomorphic(a: &str, b: &str) -> Result<(), String> {
+    let (a, b) = (read_strategy(a)?, read_strategy(b)?);
+    let Some(map) = isomorphism(&a, &b) else {
+        println!("Not isomorphic.");
+        std::process::exit(1);
+    };
+    let mapping: Vec<_> = map
+        .iter()
+        .enumerate()
+        .map(|(from, to)| format!("{from}->{to}"))
+        .collect();
+    println!("Isomorphic: relabel {}", mapping.join(" "));
+    Ok(())
+}
+
+// Partitions the solutions found by the search into classes of solutions that are identical up to
+// relabeling batteries, by matching each solution against the first one of every class so far.
+// Each class is represented by the smallest key of the solutions found in it, which is the same
+// no matter in which order the search happened to find them.
+fn classify(params: Params, anchor: Option<BitSet>, limit: usize) -> Result<(), String> {
+    // For each class: the masks of the tries of its first solution, its representative and how
+    // many found solutions belong to it.
+    let mut classes: Vec<(Vec<u64>, Vec<u64>, u64)> = vec![];
+    let identity: Vec<usize> = (0..params.total as usize).collect();
+    let full = AtomicBool::new(false);
+    let solver = Solver {
+        params,
+        anchor,
+        cancel: Some(&full),
+        ..Solver::default()
+    };
+    let mut found = 0;
+    solver.search(|solution| {
+        if full.load(Ordering::Relaxed) {
+            return;
+        }
+        let key = strategy_key(&solution, &identity);
+        let class = classes
+            .iter()
+            .position(|(first, _, _)| relabelings(first, &key, &mut |_| true));
+        let class = match class {
+            Some(class) => class,
+            None if classes.len() == limit => {
+                full.store(true, Ordering::Relaxed);
+                return;
+            }
+            None => {
+                classes.push((key.clone(), key.clone(), 0));
+                classes.len() - 1
+            }
+        };
+        let (_, representative, count) = &mut classes[class];
+        if key < *representative {
+            *representative = key;
+        }
+        *count += 1;
+        found += 1;
+    });
+    if full.load(Ordering::Relaxed) {
+        warn_max_solutions(limit);
     }
-    while permute(&mut map) {
-        if are_aligned(a, b, &map) {
-            return true;
+
+    println!(
+        "{} solutions in {} {} up to relabeling batteries:",
+        found,
+        classes.len(),
+        if classes.len() == 1 {
+            "class"
+        } else {
+            "classes"
         }
+    );
+    for (_, representative, count) in &classes {
+        let representative: Solution = representative
+            .iter()
+            .map(|&mask| BitSet(mask).into_iter().collect())
+            .collect();
+        println!("{representative:?}");
+        println!("  {count} solutions found in this class");
+    }
+    Ok(())
+}
+
+// Checks the symmetry argument behind the anchored search: relabeling the batteries of every
+// solution found with the first try fixed to `anchor` must give exactly the solutions of the
+// search without that assumption.
+//
+// The guaranteed batteries of a solution follow from its other tries, so two solutions are the
+// same up to relabeling if those tries are. The relabelings of a solution that uses u of the n
+// batteries are the P(n, u) ways to give these batteries distinct labels, which give the same
+// solution exactly when they differ by one of its automorphisms: a relabeling of the batteries
+// of the other tries that maps them to themselves, together with any relabeling of the batteries
+// that are only guaranteed. So the anchored solutions are split into such classes, each class
+// stands for P(n, u) / automorphisms solutions, and every solution of the search without the
+// assumption has to be in one of the classes.
+fn verify_wlog(params: Params, anchor: BitSet) -> bool {
+    let tries = |solution: &Solution| -> Vec<u64> {
+        let (_, tries) = solution.split_last().expect("solution should not be empty");
+        tries.iter().map(|t| mask(t)).collect()
+    };
+    let mut anchored = 0;
+    // The tries of the first anchored solution of each class, and how many solutions it stands for.
+    let mut classes: Vec<(Vec<u64>, u128)> = vec![];
+    let solver = Solver {
+        params,
+        anchor: Some(anchor),
+        ..Solver::default()
+    };
+    solver.search(|solution| {
+        anchored += 1;
+        let tries = tries(&solution);
+        if classes
+            .iter()
+            .any(|(first, _)| relabelings(first, &tries, &mut |_| true))
+        {
+            return;
+        }
+        let batteries = tries.iter().fold(0, |acc, t| acc | t);
+        let guaranteed = mask(&solution[solution.len() - 1]);
+        let (in_tries, used) = (
+            batteries.count_ones(),
+            (batteries | guaranteed).count_ones(),
+        );
+        let mut automorphisms = 0u128;
+        relabelings(&tries, &tries, &mut |_| {
+            automorphisms += 1;
+            false
+        });
+        let arrangements = |n: u32, k: u32| (n - k + 1..=n).map(u128::from).product::<u128>();
+        let size = arrangements(params.total as u32, used)
+            / (automorphisms * arrangements(used - in_tries, used - in_tries));
+        classes.push((tries, size));
+    });
+    let closure: u128 = classes.iter().map(|(_, size)| size).sum();
+
+    let (mut unanchored, mut unexpected) = (0u128, 0u128);
+    Solver {
+        params,
+        ..Solver::default()
+    }
+    .search(|solution| {
+        unanchored += 1;
+        let tries = tries(&solution);
+        if !classes
+            .iter()
+            .any(|(first, _)| relabelings(first, &tries, &mut |_| true))
+        {
+            unexpected += 1;
+        }
+    });
+    let missing = closure.saturating_sub(unanchored - unexpected);
+    println!(
+        "Anchored search with first try {:?}: {} solutions, {} after relabeling.",
+        anchor.into_iter().collect::<Vec<_>>(),
+        anchored,
+        closure
+    );
+    println!("Search without WLOG assumption: {unanchored} solutions.");
+    if missing == 0 && unexpected == 0 {
+        println!("OK: both searches agree up to relabeling.");
+        true
+    } else {
+        println!(
+            "MISMATCH: {missing} relabeled solutions not found without the assumption, \
+            {unexpected} solutions found only without it."
+        );
+        false
     }
-    false
 }
 
-fn permute<T: PartialOrd>(v: &mut Vec<T>) -> bool {
+fn permute<T: PartialOrd>(v: &mut [T]) -> bool {
     // from the back, find first decrease
     let mut pos = v.len();
     for i in (0..v.len() - 1).rev() {
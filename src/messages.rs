@@ -0,0 +1,484 @@
+//! The texts of the interactive commands `quiz`, `play` and `play --adversarial`, in each
+//! [`Language`] they can be played in. A text names the values it shows with `{name}`
+//! placeholders for the [`Message::values`] it is given, which [`Language::format`] fills in, so
+//! that a translation can put them in whatever order its grammar needs or leave some out.
+//!
+//! ```
+//! use batteries::messages::{Language, Message};
+//!
+//! let de = Language::parse("de_DE.UTF-8").unwrap();
+//! let text = de.format(Message::PlayDone, &[("tries", &de.tries(3)), ("number", &3)]);
+//! assert_eq!(text, "Fertig: Das Spielzeug ging beim 3. Versuch an.");
+//! ```
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+/// A language of the interactive commands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+/// A text of the interactive commands, e.g. a prompt or the answer to a try.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// The letter that answers yes.
+    Yes,
+    /// The letter that answers no.
+    No,
+    OneTry,
+    Tries,
+    StillPossible,
+    TryAgain,
+    OnlyBatteries,
+    NoTryToUndo,
+    NoTryToRedo,
+    QuizIntro,
+    QuizStillPossible,
+    QuizPrompt,
+    QuizCorrect,
+    QuizGood,
+    QuizWasted,
+    QuizNotGood,
+    QuizHint,
+    PlayIntro,
+    PlayOver,
+    PlayResuming,
+    PlaySaving,
+    PlayNoTry,
+    PlayTry,
+    PlayTryGuaranteed,
+    PlayPrompt,
+    NoAnswerToUndo,
+    NoAnswerToRedo,
+    PlayPleaseAnswer,
+    PlayDone,
+    PlayInconsistent,
+    AdversaryIntro,
+    AdversaryPrompt,
+    AdversaryNothingNew,
+    AdversaryRulesOut,
+    AdversaryLost,
+    AdversaryLostOptimal,
+    AdversaryLostBetter,
+    AdversaryTurnsOn,
+    AdversaryWonBetter,
+    AdversaryWonOptimal,
+}
+
+impl Message {
+    /// Every message, in the order of their declaration.
+    pub const ALL: [Message; 40] = [
+        Message::Yes,
+        Message::No,
+        Message::OneTry,
+        Message::Tries,
+        Message::StillPossible,
+        Message::TryAgain,
+        Message::OnlyBatteries,
+        Message::NoTryToUndo,
+        Message::NoTryToRedo,
+        Message::QuizIntro,
+        Message::QuizStillPossible,
+        Message::QuizPrompt,
+        Message::QuizCorrect,
+        Message::QuizGood,
+        Message::QuizWasted,
+        Message::QuizNotGood,
+        Message::QuizHint,
+        Message::PlayIntro,
+        Message::PlayOver,
+        Message::PlayResuming,
+        Message::PlaySaving,
+        Message::PlayNoTry,
+        Message::PlayTry,
+        Message::PlayTryGuaranteed,
+        Message::PlayPrompt,
+        Message::NoAnswerToUndo,
+        Message::NoAnswerToRedo,
+        Message::PlayPleaseAnswer,
+        Message::PlayDone,
+        Message::PlayInconsistent,
+        Message::AdversaryIntro,
+        Message::AdversaryPrompt,
+        Message::AdversaryNothingNew,
+        Message::AdversaryRulesOut,
+        Message::AdversaryLost,
+        Message::AdversaryLostOptimal,
+        Message::AdversaryLostBetter,
+        Message::AdversaryTurnsOn,
+        Message::AdversaryWonBetter,
+        Message::AdversaryWonOptimal,
+    ];
+
+    /// The names of the values that the message is formatted with, which its texts can show.
+    pub fn values(self) -> &'static [&'static str] {
+        match self {
+            Message::Tries => &["n"],
+            Message::StillPossible => &["configurations"],
+            Message::TryAgain => &["error"],
+            Message::OnlyBatteries => &["total"],
+            Message::QuizIntro => &["good", "total", "batteries", "tries", "example"],
+            Message::QuizStillPossible => &["configurations", "tries"],
+            Message::QuizCorrect => &["batteries"],
+            Message::QuizGood => &["configurations", "tries", "plan"],
+            Message::QuizNotGood => &["configurations", "tries"],
+            Message::QuizHint => &["batteries"],
+            Message::PlayIntro => &["batteries", "yes", "no"],
+            Message::PlayOver => &["file", "batteries", "tries", "number"],
+            Message::PlayResuming => &["file", "tries"],
+            Message::PlaySaving => &["file"],
+            Message::PlayTry | Message::PlayTryGuaranteed => {
+                &["configurations", "number", "tries", "batteries"]
+            }
+            Message::PlayPrompt | Message::PlayPleaseAnswer => &["yes", "no"],
+            Message::PlayDone => &["tries", "number"],
+            Message::AdversaryIntro => &["good", "batteries", "tries", "example"],
+            Message::AdversaryPrompt => &["number"],
+            Message::AdversaryRulesOut => &["configurations"],
+            Message::AdversaryLost => &["tries", "batteries"],
+            Message::AdversaryLostOptimal | Message::AdversaryLostBetter => &["best"],
+            Message::AdversaryTurnsOn => &["tries", "number", "batteries"],
+            Message::AdversaryWonBetter => &["best", "fewer"],
+            Message::Yes
+            | Message::No
+            | Message::OneTry
+            | Message::NoTryToUndo
+            | Message::NoTryToRedo
+            | Message::QuizPrompt
+            | Message::QuizWasted
+            | Message::PlayNoTry
+            | Message::NoAnswerToUndo
+            | Message::NoAnswerToRedo
+            | Message::PlayInconsistent
+            | Message::AdversaryNothingNew
+            | Message::AdversaryWonOptimal => &[],
+        }
+    }
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    /// The language of a code like `de`, or of a locale like `de_DE.UTF-8` as in `LANG`.
+    pub fn parse(code: &str) -> Option<Language> {
+        let language = code.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        Language::ALL
+            .into_iter()
+            .find(|l| l.code().eq_ignore_ascii_case(language))
+    }
+
+    /// The ISO 639-1 code of the language.
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+
+    /// The text of `message`, with its placeholders.
+    pub fn text(self, message: Message) -> &'static str {
+        match self {
+            Language::English => english(message),
+            Language::German => german(message),
+        }
+    }
+
+    /// The text of `message` with each `{name}` placeholder replaced by the value of `name` in
+    /// `args`. Placeholders without a value are kept as they are.
+    pub fn format(self, message: Message, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let mut text = String::new();
+        let mut rest = self.text(message);
+        while let Some(start) = rest.find('{') {
+            let (before, placeholder) = rest.split_at(start);
+            text.push_str(before);
+            let name = placeholder[1..].split('}').next().unwrap_or_default();
+            match args.iter().find(|&&(n, _)| n == name) {
+                Some((_, value)) if placeholder[1 + name.len()..].starts_with('}') => {
+                    let _ = write!(text, "{value}");
+                    rest = &placeholder[name.len() + 2..];
+                }
+                _ => {
+                    text.push('{');
+                    rest = &placeholder[1..];
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+
+    /// "1 try", "2 tries", ...
+    pub fn tries(self, n: usize) -> String {
+        match n {
+            1 => String::from(self.text(Message::OneTry)),
+            n => self.format(Message::Tries, &[("n", &n)]),
+        }
+    }
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::Yes => "y",
+        Message::No => "n",
+        Message::OneTry => "1 try",
+        Message::Tries => "{n} tries",
+        Message::StillPossible => "{configurations} configurations are still possible.",
+        Message::TryAgain => "{error}, try again.",
+        Message::OnlyBatteries => "There are only {total} batteries, try again.",
+        Message::NoTryToUndo => "There is no try to undo.",
+        Message::NoTryToRedo => "There is no try to redo.",
+        Message::QuizIntro => {
+            "{good} of the {total} batteries {batteries} are functional. Find tries that turn on \
+            the toy within {tries} tries, whichever batteries are functional. Assume every try \
+            fails; enter tries like `{example}`, `u` to undo a try, `r` to redo it, or `q` to \
+            quit."
+        }
+        Message::QuizStillPossible => {
+            "{configurations} configurations are still possible, {tries} left."
+        }
+        Message::QuizPrompt => "Your next try: ",
+        Message::QuizCorrect => {
+            "Correct! Batteries {batteries} are functional in every configuration left, so this \
+            try turns on the toy."
+        }
+        Message::QuizGood => {
+            "Good: if it fails, {configurations} configurations are left and another {tries} can \
+            still cover them, for example [{plan}]."
+        }
+        Message::QuizWasted => "This try rules out nothing new, so it would waste a try.",
+        Message::QuizNotGood => {
+            "Not good: if it fails, {configurations} configurations are left and there are no \
+            {tries} that turn on the toy in all of them."
+        }
+        Message::QuizHint => "Hint: {batteries} would work. Try again.",
+        Message::PlayIntro => {
+            "Label the batteries {batteries}. After each try, answer `{yes}` if the toy turned on \
+            and `{no}` if it did not, `u` to undo the last answer, `r` to redo it, or `q` to quit."
+        }
+        Message::PlayOver => {
+            "The game saved in `{file}` is over: the toy turned on with batteries {batteries} \
+            after {tries}."
+        }
+        Message::PlayResuming => "Resuming the game saved in `{file}` after {tries} that failed.",
+        Message::PlaySaving => "Saving the game to `{file}` after every answer.",
+        Message::PlayNoTry => "no try is guaranteed to turn on the toy any more",
+        Message::PlayTry => {
+            "{configurations} configurations are still possible. Try {number} of {tries}: put \
+            batteries {batteries} into the toy."
+        }
+        Message::PlayTryGuaranteed => {
+            "{configurations} configurations are still possible. Try {number} of {tries}: put \
+            batteries {batteries} into the toy (they are functional in all of them)."
+        }
+        Message::PlayPrompt => "Did the toy turn on? [{yes}/{no}] ",
+        Message::NoAnswerToUndo => "There is no answer to undo.",
+        Message::NoAnswerToRedo => "There is no answer to redo.",
+        Message::PlayPleaseAnswer => "Please answer `{yes}`, `{no}`, `u`, `r` or `q`.",
+        Message::PlayDone => "Done: the toy turned on after {tries}.",
+        Message::PlayInconsistent => {
+            "no choice of functional batteries fits these answers; is the toy working?"
+        }
+        Message::AdversaryIntro => {
+            "{good} of the batteries {batteries} are functional, but which ones is only decided \
+            as late as possible, to keep the toy off for as long as possible. Turn it on within \
+            {tries}. Enter tries like `{example}`, `u` to undo a try, `r` to redo it, or `q` to \
+            quit."
+        }
+        Message::AdversaryPrompt => "Try {number}: ",
+        Message::AdversaryNothingNew => "The toy stays off. This try ruled out nothing new.",
+        Message::AdversaryRulesOut => {
+            "The toy stays off, which rules out {configurations} configurations."
+        }
+        Message::AdversaryLost => {
+            "You lost: the toy stayed off for all {tries}. Batteries {batteries} may well be the \
+            functional ones, and none of your tries holds only functional batteries."
+        }
+        Message::AdversaryLostOptimal => {
+            "No strategy can do better: against this toy every strategy needs {best}."
+        }
+        Message::AdversaryLostBetter => "The best strategy needs only {best} in the worst case.",
+        Message::AdversaryTurnsOn => {
+            "The toy turns on after {tries}: batteries {batteries} are functional in every \
+            configuration left."
+        }
+        Message::AdversaryWonBetter => {
+            "The best strategy needs only {best} in the worst case, {fewer} fewer."
+        }
+        Message::AdversaryWonOptimal => {
+            "No strategy needs fewer tries in the worst case, so this one is optimal."
+        }
+    }
+}
+
+// The counts of tries are in the nominative or the accusative, so the texts that would need them
+// in the dative count with `{number}` instead or put them in parentheses.
+fn german(message: Message) -> &'static str {
+    match message {
+        Message::Yes => "j",
+        Message::No => "n",
+        Message::OneTry => "1 Versuch",
+        Message::Tries => "{n} Versuche",
+        Message::StillPossible => "{configurations} Konfigurationen sind noch möglich.",
+        Message::TryAgain => "{error}, versuch es noch einmal.",
+        Message::OnlyBatteries => "Es gibt nur {total} Batterien, versuch es noch einmal.",
+        Message::NoTryToUndo => "Es gibt keinen Versuch, der sich rückgängig machen lässt.",
+        Message::NoTryToRedo => "Es gibt keinen Versuch, der sich wiederherstellen lässt.",
+        Message::QuizIntro => {
+            "{good} der {total} Batterien {batteries} funktionieren. Finde Versuche, die das \
+            Spielzeug innerhalb von {tries} Versuchen einschalten, egal welche Batterien \
+            funktionieren. Nimm an, dass jeder Versuch fehlschlägt; gib Versuche wie `{example}` \
+            ein, `u`, um einen Versuch rückgängig zu machen, `r`, um ihn wiederherzustellen, oder \
+            `q` zum Beenden."
+        }
+        Message::QuizStillPossible => {
+            "{configurations} Konfigurationen sind noch möglich, {tries} übrig."
+        }
+        Message::QuizPrompt => "Dein nächster Versuch: ",
+        Message::QuizCorrect => {
+            "Richtig! Die Batterien {batteries} funktionieren in jeder verbleibenden \
+            Konfiguration, also schaltet dieser Versuch das Spielzeug ein."
+        }
+        Message::QuizGood => {
+            "Gut: Wenn er fehlschlägt, bleiben {configurations} Konfigurationen übrig, und die \
+            übrigen Versuche ({tries}) können sie noch abdecken, zum Beispiel [{plan}]."
+        }
+        Message::QuizWasted => {
+            "Dieser Versuch schließt nichts Neues aus, er wäre also verschwendet."
+        }
+        Message::QuizNotGood => {
+            "Nicht gut: Wenn er fehlschlägt, bleiben {configurations} Konfigurationen übrig, und \
+            die übrigen Versuche ({tries}) können das Spielzeug nicht in allen einschalten."
+        }
+        Message::QuizHint => "Tipp: {batteries} würde funktionieren. Versuch es noch einmal.",
+        Message::PlayIntro => {
+            "Beschrifte die Batterien mit {batteries}. Antworte nach jedem Versuch `{yes}`, wenn \
+            das Spielzeug angegangen ist, und `{no}`, wenn nicht, `u`, um die letzte Antwort \
+            rückgängig zu machen, `r`, um sie wiederherzustellen, oder `q` zum Beenden."
+        }
+        Message::PlayOver => {
+            "Das in `{file}` gespeicherte Spiel ist vorbei: Das Spielzeug ging beim {number}. \
+            Versuch mit den Batterien {batteries} an."
+        }
+        Message::PlayResuming => {
+            "Das in `{file}` gespeicherte Spiel geht weiter, fehlgeschlagen sind bisher {tries}."
+        }
+        Message::PlaySaving => "Das Spiel wird nach jeder Antwort in `{file}` gespeichert.",
+        Message::PlayNoTry => "kein Versuch schaltet das Spielzeug mehr sicher ein",
+        Message::PlayTry => {
+            "{configurations} Konfigurationen sind noch möglich. Versuch {number} von {tries}: \
+            Setze die Batterien {batteries} in das Spielzeug ein."
+        }
+        Message::PlayTryGuaranteed => {
+            "{configurations} Konfigurationen sind noch möglich. Versuch {number} von {tries}: \
+            Setze die Batterien {batteries} in das Spielzeug ein (sie funktionieren in allen)."
+        }
+        Message::PlayPrompt => "Ist das Spielzeug angegangen? [{yes}/{no}] ",
+        Message::NoAnswerToUndo => "Es gibt keine Antwort, die sich rückgängig machen lässt.",
+        Message::NoAnswerToRedo => "Es gibt keine Antwort, die sich wiederherstellen lässt.",
+        Message::PlayPleaseAnswer => "Bitte antworte `{yes}`, `{no}`, `u`, `r` oder `q`.",
+        Message::PlayDone => "Fertig: Das Spielzeug ging beim {number}. Versuch an.",
+        Message::PlayInconsistent => {
+            "keine Auswahl funktionierender Batterien passt zu diesen Antworten; funktioniert \
+            das Spielzeug?"
+        }
+        Message::AdversaryIntro => {
+            "{good} der Batterien {batteries} funktionieren, aber welche, wird erst so spät wie \
+            möglich entschieden, damit das Spielzeug so lange wie möglich aus bleibt. Du hast \
+            {tries}, um es einzuschalten. Gib Versuche wie `{example}` ein, `u`, um einen Versuch \
+            rückgängig zu machen, `r`, um ihn wiederherzustellen, oder `q` zum Beenden."
+        }
+        Message::AdversaryPrompt => "Versuch {number}: ",
+        Message::AdversaryNothingNew => {
+            "Das Spielzeug bleibt aus. Dieser Versuch hat nichts Neues ausgeschlossen."
+        }
+        Message::AdversaryRulesOut => {
+            "Das Spielzeug bleibt aus, was {configurations} Konfigurationen ausschließt."
+        }
+        Message::AdversaryLost => {
+            "Verloren: Alle deine Versuche ({tries}) sind fehlgeschlagen. Die Batterien \
+            {batteries} könnten durchaus die funktionierenden sein, und keiner deiner Versuche \
+            enthält nur funktionierende Batterien."
+        }
+        Message::AdversaryLostOptimal => {
+            "Keine Strategie kann es besser: Gegen dieses Spielzeug braucht jede Strategie {best}."
+        }
+        Message::AdversaryLostBetter => {
+            "Die beste Strategie braucht im schlimmsten Fall nur {best}."
+        }
+        Message::AdversaryTurnsOn => {
+            "Das Spielzeug geht beim {number}. Versuch an: Die Batterien {batteries} funktionieren \
+            in jeder verbleibenden Konfiguration."
+        }
+        Message::AdversaryWonBetter => {
+            "Die beste Strategie braucht im schlimmsten Fall nur {best}, {fewer} weniger."
+        }
+        Message::AdversaryWonOptimal => {
+            "Keine Strategie braucht im schlimmsten Fall weniger Versuche, diese ist also optimal."
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = text
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    #[test]
+    fn texts_only_show_the_values_they_are_given() {
+        for message in Message::ALL {
+            for language in Language::ALL {
+                let text = language.text(message);
+                assert!(!text.is_empty(), "{message:?} in {language:?}");
+                for name in placeholders(text) {
+                    assert!(
+                        message.values().contains(&name),
+                        "{message:?} in {language:?} has `{{{name}}}`"
+                    );
+                }
+            }
+        }
+        // The answers have to tell yes from no, and from the commands.
+        for language in Language::ALL {
+            let (yes, no) = (language.text(Message::Yes), language.text(Message::No));
+            assert_ne!(yes, no);
+            assert!(!["u", "r", "q"].contains(&yes) && !["u", "r", "q"].contains(&no));
+        }
+    }
+
+    #[test]
+    fn fills_in_placeholders() {
+        let en = Language::English;
+        let text = en.format(Message::OnlyBatteries, &[("total", &8)]);
+        assert_eq!(text, "There are only 8 batteries, try again.");
+        assert_eq!(
+            en.format(Message::OnlyBatteries, &[]),
+            en.text(Message::OnlyBatteries)
+        );
+        assert_eq!(en.tries(1), "1 try");
+        assert_eq!(Language::German.tries(7), "7 Versuche");
+    }
+
+    #[test]
+    fn parses_codes_and_locales() {
+        assert_eq!(Language::parse("de"), Some(Language::German));
+        assert_eq!(Language::parse("de_AT.UTF-8"), Some(Language::German));
+        assert_eq!(Language::parse("en-GB"), Some(Language::English));
+        assert_eq!(Language::parse("C"), None);
+        assert_eq!(Language::parse(""), None);
+    }
+}
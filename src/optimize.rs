@@ -0,0 +1,320 @@
+//! Rewrites of found solutions that make them easier to carry out by hand without making them
+//! any less sure to turn on the toy: [`minimize`] drops the tries that others make redundant, and
+//! [`minimize_swaps`] and [`pareto_front`] reorder them. Any order of the tries is still a valid
+//! strategy: we stop at the first try that turns on the toy, and it's the set of tries that
+//! guarantees one of them does.
+//!
+//! ```
+//! use batteries::optimize::minimize_swaps;
+//!
+//! // Keeping battery 1 in its slot and then battery 2 in its slot takes 2 swaps instead of 4.
+//! let (reordered, swaps) = minimize_swaps(&[vec![0, 1], vec![2, 3], vec![1, 2]], 2);
+//! assert_eq!(reordered, [vec![0, 1], vec![2, 1], vec![2, 3]]);
+//! assert_eq!(swaps, 2);
+//! ```
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{solution_tries, BitSet, CombinationIter, Params, PermutationIter, Solution};
+
+/// Reorders the tries of `solution` and chooses which battery goes into which of the `slots`
+/// slots so that as few batteries as possible have to be swapped between consecutive tries. If
+/// more than `slots` batteries are guaranteed to work at the end, the first ones are tried.
+/// Returns the reordered solution, with each try listed in slot order, and the number of swaps.
+///
+/// This goes through every order of the tries and of the batteries in each of them, so it is
+/// only meant for a handful of tries with a few slots each.
+pub fn minimize_swaps(solution: &[Vec<usize>], slots: usize) -> (Solution, usize) {
+    // The orientations of a try are the orders of its batteries, starting with the listed one.
+    let orientations: Vec<Vec<usize>> = PermutationIter::new(slots as u64, slots as u64).collect();
+    // oriented[t][o] is try t in orientation o.
+    let oriented: Vec<Vec<Vec<usize>>> = solution
+        .iter()
+        .map(|t| {
+            orientations
+                .iter()
+                .map(|o| o.iter().map(|&i| t[i]).collect())
+                .collect()
+        })
+        .collect();
+    let swaps = |x: &[usize], y: &[usize]| x.iter().zip(y).filter(|(a, b)| a != b).count();
+
+    let mut best: Option<(usize, Vec<Vec<usize>>)> = None;
+    let mut orders = PermutationIter::new(solution.len() as u64, solution.len() as u64);
+    while let Some(order) = orders.next_slice() {
+        // cost[o] is the fewest swaps to reach the current try with orientation o; from[i][o] is
+        // the orientation of try i - 1 on that cheapest path.
+        let mut cost = vec![0; orientations.len()];
+        let mut from = vec![vec![0; orientations.len()]; order.len()];
+        for i in 1..order.len() {
+            let mut next = vec![usize::MAX; orientations.len()];
+            for (o, next) in next.iter_mut().enumerate() {
+                for (prev, &prev_cost) in cost.iter().enumerate() {
+                    let c =
+                        prev_cost + swaps(&oriented[order[i - 1]][prev], &oriented[order[i]][o]);
+                    if c < *next {
+                        *next = c;
+                        from[i][o] = prev;
+                    }
+                }
+            }
+            cost = next;
+        }
+        let mut o = (0..cost.len())
+            .min_by_key(|&o| cost[o])
+            .expect("a try has an orientation");
+        let total = cost[o];
+        if best.as_ref().is_none_or(|(b, _)| total < *b) {
+            let mut reordered = vec![vec![]; order.len()];
+            for i in (0..order.len()).rev() {
+                reordered[i] = oriented[order[i]][o].clone();
+                o = from[i][o];
+            }
+            best = Some((total, reordered));
+        }
+    }
+    best.map(|(total, reordered)| (reordered, total))
+        .expect("at least one order is tried")
+}
+
+/// The cost of carrying out a strategy with its tries in the given order, where the toy is
+/// switched on as soon as possible. Every objective is better when smaller.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Objectives {
+    pub worst_tries: usize,
+    /// Assuming all configurations of functional batteries are equally likely.
+    pub expected_tries: f64,
+    /// Most times any one battery is put into the toy; one that stays in the toy between
+    /// consecutive tries is not put in again.
+    pub max_insertions_per_battery: usize,
+    /// Times batteries are put into the toy in all.
+    pub insertions: usize,
+}
+
+impl Objectives {
+    /// The objectives of going through the tries of `strategy` in order, when the functional
+    /// batteries are one of `universes`.
+    pub fn of(strategy: &[Vec<usize>], universes: &[BitSet]) -> Self {
+        let needed = |universe: &BitSet| {
+            strategy
+                .iter()
+                .position(|pair| pair.iter().all(|&b| universe.contains(b)))
+                .map_or(strategy.len(), |i| i + 1)
+        };
+        let worst_tries = universes.iter().map(needed).max().unwrap_or(0);
+        let expected_tries =
+            universes.iter().map(needed).sum::<usize>() as f64 / universes.len().max(1) as f64;
+
+        let mut per_battery = [0; 64];
+        let mut previous: &[usize] = &[];
+        for pair in strategy {
+            for &b in pair.iter().filter(|b| !previous.contains(b)) {
+                per_battery[b] += 1;
+            }
+            previous = pair;
+        }
+        Objectives {
+            worst_tries,
+            expected_tries,
+            max_insertions_per_battery: per_battery.iter().copied().max().unwrap_or(0),
+            insertions: per_battery.iter().sum(),
+        }
+    }
+
+    /// Whether these objectives are all no worse than `other` and some of them better.
+    pub fn dominates(&self, other: &Objectives) -> bool {
+        let no_worse = self.worst_tries <= other.worst_tries
+            && self.expected_tries <= other.expected_tries
+            && self.max_insertions_per_battery <= other.max_insertions_per_battery
+            && self.insertions <= other.insertions;
+        no_worse && self != other
+    }
+}
+
+/// Goes through all orderings of the tries of each of `solutions` and keeps the non-dominated
+/// ones, one strategy per distinct combination of objectives, sorted by worst-case tries,
+/// insertions, insertions per battery and expected tries. Like [`minimize_swaps`], this is only
+/// meant for a handful of tries.
+pub fn pareto_front(solutions: &[Solution], params: Params) -> Vec<(Objectives, Solution)> {
+    let universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+    let mut front: Vec<(Objectives, Solution)> = vec![];
+    for solution in solutions {
+        // The guaranteed batteries are only a try of their own once the others are reordered.
+        let solution: Solution = solution_tries(solution, params.slots)
+            .into_iter()
+            .map(|pair| pair.into_iter().collect())
+            .collect();
+        let mut orders = PermutationIter::new(solution.len() as u64, solution.len() as u64);
+        while let Some(order) = orders.next_slice() {
+            let strategy: Solution = order.iter().map(|&i| solution[i].clone()).collect();
+            let objectives = Objectives::of(&strategy, &universes);
+            if front
+                .iter()
+                .all(|(o, _)| !o.dominates(&objectives) && *o != objectives)
+            {
+                front.retain(|(o, _)| !objectives.dominates(o));
+                front.push((objectives, strategy));
+            }
+        }
+    }
+    front.sort_by(|(a, _), (b, _)| {
+        (a.worst_tries, a.insertions, a.max_insertions_per_battery)
+            .cmp(&(b.worst_tries, b.insertions, b.max_insertions_per_battery))
+            .then(a.expected_tries.total_cmp(&b.expected_tries))
+    });
+    front
+}
+
+/// Drops the tries of `solution` that the other tries make redundant, going through them from
+/// the first one to the last one but the guaranteed batteries: a try can be dropped if the
+/// batteries that are functional in every universe where the remaining tries fail still make a
+/// try. The guaranteed batteries are then the ones of the tries left, which need not be the same.
+/// None of the tries left can be dropped, though dropping others could have left fewer. With
+/// `anchored` the first try is kept, so that it is still the one assumed WLOG.
+pub fn minimize(solution: &[Vec<usize>], params: Params, anchored: bool) -> Solution {
+    let universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+    let guaranteed = |tries: &[BitSet]| {
+        universes
+            .iter()
+            .filter(|&&universe| tries.iter().all(|pair| !pair.is_subset(universe)))
+            .fold(BitSet((1 << params.total) - 1), |all, &universe| {
+                all & universe
+            })
+    };
+    let (_, tries) = solution.split_last().expect("solution should not be empty");
+    let mut tries: Vec<BitSet> = tries
+        .iter()
+        .map(|batteries| batteries.iter().copied().collect())
+        .collect();
+    let mut i = usize::from(anchored);
+    while i < tries.len() {
+        let mut rest = tries.clone();
+        rest.remove(i);
+        if u64::from(guaranteed(&rest).len()) >= params.slots {
+            tries = rest;
+        } else {
+            i += 1;
+        }
+    }
+    let last = guaranteed(&tries);
+    tries
+        .into_iter()
+        .chain([last])
+        .map(|set| set.into_iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate;
+
+    // The tries of the solution of the original puzzle, with the guaranteed batteries last.
+    fn original() -> Solution {
+        vec![
+            vec![0, 1],
+            vec![2, 3],
+            vec![2, 4],
+            vec![3, 4],
+            vec![0, 5],
+            vec![1, 5],
+            vec![6, 7],
+        ]
+    }
+
+    fn swaps(solution: &[Vec<usize>]) -> usize {
+        solution
+            .windows(2)
+            .map(|w| w[0].iter().zip(&w[1]).filter(|(a, b)| a != b).count())
+            .sum()
+    }
+
+    #[test]
+    fn keeps_the_tries_and_counts_the_swaps_between_them() {
+        let solution = original();
+        let (reordered, count) = minimize_swaps(&solution, 2);
+        assert_eq!(count, swaps(&reordered));
+        assert!(count < swaps(&solution));
+        let sorted = |solution: &[Vec<usize>]| {
+            let mut tries: Vec<Vec<usize>> = solution
+                .iter()
+                .map(|t| {
+                    let mut t = t.clone();
+                    t.sort_unstable();
+                    t
+                })
+                .collect();
+            tries.sort_unstable();
+            tries
+        };
+        assert_eq!(sorted(&reordered), sorted(&solution));
+        // The triangles on 0, 1, 5 and on 2, 3, 4 and the try [6, 7] share no batteries, so
+        // moving from one to another takes 2 swaps and every other step 1.
+        assert_eq!(count, 8);
+    }
+
+    #[test]
+    fn keeps_the_orderings_that_no_other_one_beats() {
+        let params = Params::default();
+        let front = pareto_front(&[original()], params);
+        let universes: Vec<BitSet> = CombinationIter::new(8, 4).collect();
+        for (i, (objectives, strategy)) in front.iter().enumerate() {
+            assert_eq!(Objectives::of(strategy, &universes), *objectives);
+            assert!(front.iter().enumerate().all(
+                |(j, (other, _))| i == j || !other.dominates(objectives) && other != objectives
+            ));
+        }
+        // Every try is the only one that works in some universe, so whatever the order, the
+        // worst case takes all of them.
+        assert!(front.iter().all(|(o, _)| o.worst_tries == 7));
+        // The best ordering for each objective on its own is on the front.
+        let solution = original();
+        let all: Vec<Objectives> = PermutationIter::new(7, 7)
+            .map(|order| {
+                let strategy: Solution = order.iter().map(|&i| solution[i].clone()).collect();
+                Objectives::of(&strategy, &universes)
+            })
+            .collect();
+        let fewest = |objective: fn(&Objectives) -> f64, objectives: &[Objectives]| {
+            objectives
+                .iter()
+                .map(objective)
+                .fold(f64::INFINITY, f64::min)
+        };
+        let on_front: Vec<Objectives> = front.iter().map(|&(o, _)| o).collect();
+        let objectives: [fn(&Objectives) -> f64; 3] = [
+            |o| o.expected_tries,
+            |o| o.max_insertions_per_battery as f64,
+            |o| o.insertions as f64,
+        ];
+        for objective in objectives {
+            assert_eq!(fewest(objective, &on_front), fewest(objective, &all));
+        }
+        let first = all[0];
+        assert!(!first.dominates(&first));
+    }
+
+    #[test]
+    fn drops_redundant_tries_and_stays_valid() {
+        let params = Params {
+            tries: 9,
+            ..Params::default()
+        };
+        // The solution of the original puzzle with [0, 2] and [1, 3] on top.
+        let mut solution = original();
+        solution.insert(1, vec![0, 2]);
+        solution.insert(4, vec![1, 3]);
+        for anchored in [false, true] {
+            let minimal = minimize(&solution, params, anchored);
+            let tries = solution_tries(&minimal, params.slots);
+            assert_eq!(validate::is_valid_strategy(params, &tries), Ok(()));
+            assert!(minimal.len() < solution.len());
+            assert_eq!(minimize(&minimal, params, anchored), minimal);
+            if anchored {
+                assert_eq!(minimal[0], [0, 1]);
+            }
+        }
+        assert_eq!(minimize(&original(), params, false), original());
+    }
+}
@@ -0,0 +1,897 @@
+//! JSON-RPC 2.0 with one request or response per line, so that editors, bots and other tools can
+//! drive the solver as a long-lived child process (`batteries --stdio`) or a daemon listening on
+//! a unix socket (`batteries --socket PATH`). A [`Server`] answers
+//! four methods, all of which take the puzzle as `total`, `good`, `slots` and `tries` parameters
+//! that default to the original puzzle:
+//!
+//! - `solve` finds a strategy, within `time_limit` seconds if that is given;
+//! - `verify` checks the tries of `strategy`, a solution as `solve` returns it;
+//! - `recommend-next-test` starts a game against a real toy, or with no parameters goes on with
+//!   the one being played, and returns a try that keeps the toy guaranteed to turn on in time;
+//! - `apply-outcome` records whether the toy turned on with `try`.
+//!
+//! Batteries are numbers and tries are arrays of them, as in `--format json`. What a server did
+//! is counted in its [`Metrics`], which `--metrics ADDRESS` serves over HTTP for Prometheus.
+//!
+//! ```
+//! use batteries::rpc::Server;
+//!
+//! let mut server = Server::default();
+//! let request = r#"{"jsonrpc": "2.0", "id": 1, "method": "recommend-next-test"}"#;
+//! let result = r#"{"try":[0,1],"tries_left":7,"universes_left":70}"#;
+//! assert_eq!(
+//!     server.handle(request),
+//!     Some(format!(r#"{{"jsonrpc":"2.0","id":1,"result":{result}}}"#))
+//! );
+//! ```
+
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::validate;
+use crate::{
+    checked_binomial, solution_tries, BitSet, EliminationTable, Game, Params, SearchOutcome, Solver,
+};
+
+// How many elimination tables a server keeps for `solve`, dropping the least recently used one
+// when it needs another.
+const TABLES: usize = 16;
+
+/// A JSON value. Numbers are kept as `f64`, which holds every count the methods deal with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// The members in the order they were written.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses a single JSON value, with nothing but whitespace around it.
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            text: text.as_bytes(),
+            at: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.at == text.len() {
+            true => Ok(value),
+            false => Err(format!("unexpected text at byte {}", parser.at)),
+        }
+    }
+
+    /// The member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn object(members: impl IntoIterator<Item = (&'static str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    fn set(set: impl IntoIterator<Item = usize>) -> Json {
+        Json::Array(set.into_iter().map(|b| Json::Number(b as f64)).collect())
+    }
+}
+
+// Compact, on a single line.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Number(n) if n.is_finite() => write!(f, "{n}"),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser<'t> {
+    text: &'t [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.text.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    fn error(&self, what: &str) -> String {
+        format!("{what} at byte {}", self.at)
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        match self.text[self.at..].starts_with(literal.as_bytes()) {
+            true => {
+                self.at += literal.len();
+                Ok(())
+            }
+            false => Err(self.error(&format!("expected `{literal}`"))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.text.get(self.at) {
+            None => Err(self.error("unexpected end")),
+            Some(b'n') => self.expect("null").map(|()| Json::Null),
+            Some(b't') => self.expect("true").map(|()| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|()| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.at += 1;
+                let mut items = vec![];
+                self.skip_whitespace();
+                if self.text.get(self.at) == Some(&b']') {
+                    self.at += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.text.get(self.at) {
+                        Some(b',') => self.at += 1,
+                        Some(b']') => {
+                            self.at += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.at += 1;
+                let mut members = vec![];
+                self.skip_whitespace();
+                if self.text.get(self.at) == Some(&b'}') {
+                    self.at += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.text.get(self.at) != Some(&b'"') {
+                        return Err(self.error("expected a member name"));
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    members.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.text.get(self.at) {
+                        Some(b',') => self.at += 1,
+                        Some(b'}') => {
+                            self.at += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.at;
+        while self
+            .text
+            .get(self.at)
+            .is_some_and(|&c| c.is_ascii_digit() || b"+-.eE".contains(&c))
+        {
+            self.at += 1;
+        }
+        // The bytes are ASCII, and Rust's float syntax accepts every JSON number.
+        let number = std::str::from_utf8(&self.text[start..self.at]).expect("ASCII");
+        match number.parse() {
+            Ok(n) => Ok(Json::Number(n)),
+            Err(_) => Err(format!("invalid number `{number}` at byte {start}")),
+        }
+    }
+
+    // A string, starting at its opening quote.
+    fn string(&mut self) -> Result<String, String> {
+        self.at += 1;
+        let mut bytes = vec![];
+        loop {
+            match self.text.get(self.at) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.at += 1;
+                    // The text is a &str, and escapes only add whole characters.
+                    return Ok(String::from_utf8(bytes).expect("UTF-8"));
+                }
+                Some(b'\\') => {
+                    let escaped = match self.text.get(self.at + 1) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.at += 2;
+                            let c = self.unicode_escape()?;
+                            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.at += 2;
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(&c) => {
+                    self.at += 1;
+                    bytes.push(c);
+                }
+            }
+        }
+    }
+
+    // The character of a `\u` escape, whose hex digits start here, joining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.code_unit()?;
+        let code = match high {
+            0xd800..=0xdbff if self.text[self.at..].starts_with(b"\\u") => {
+                self.at += 2;
+                let low = self.code_unit()?;
+                0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+            }
+            unit => unit,
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid `\\u` escape"))
+    }
+
+    fn code_unit(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.at..self.at + 4);
+        let unit = digits
+            .and_then(|d| u32::from_str_radix(std::str::from_utf8(d).ok()?, 16).ok())
+            .ok_or_else(|| self.error("invalid `\\u` escape"))?;
+        self.at += 4;
+        Ok(unit)
+    }
+}
+
+/// Error codes of the JSON-RPC 2.0 specification.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+struct Error {
+    code: i64,
+    message: String,
+}
+
+fn invalid_params(message: impl Into<String>) -> Error {
+    Error {
+        code: INVALID_PARAMS,
+        message: message.into(),
+    }
+}
+
+// The upper bounds of the buckets of the histogram of how long `solve` takes, in seconds.
+const SOLVE_BUCKETS: [f64; 6] = [0.001, 0.01, 0.1, 1.0, 10.0, 60.0];
+
+/// Counts what a [`Server`] did, for monitoring. It can be read while the server runs, from
+/// another thread, and is shown in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    solutions: AtomicU64,
+    table_hits: AtomicU64,
+    table_misses: AtomicU64,
+    // The number of searches that took at most each bound of `SOLVE_BUCKETS`, or longer than the
+    // last, i.e. not yet cumulative, and their total time.
+    solve_buckets: [AtomicU64; SOLVE_BUCKETS.len() + 1],
+    solve_micros: AtomicU64,
+}
+
+impl Metrics {
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_solve(&self, took: Duration) {
+        let seconds = took.as_secs_f64();
+        let bucket = SOLVE_BUCKETS.iter().take_while(|&&le| seconds > le).count();
+        Metrics::count(&self.solve_buckets[bucket]);
+        let micros = u64::try_from(took.as_micros()).unwrap_or(u64::MAX);
+        self.solve_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counters = [
+            (
+                "requests",
+                "JSON-RPC requests, counting each of a batch",
+                &self.requests,
+            ),
+            (
+                "errors",
+                "JSON-RPC requests answered with an error",
+                &self.errors,
+            ),
+            (
+                "solutions_found",
+                "`solve` requests that found a strategy",
+                &self.solutions,
+            ),
+            (
+                "table_cache_hits",
+                "`solve` requests that reused an elimination table",
+                &self.table_hits,
+            ),
+            (
+                "table_cache_misses",
+                "`solve` requests that built an elimination table",
+                &self.table_misses,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            writeln!(f, "# HELP batteries_{name}_total {help}.")?;
+            writeln!(f, "# TYPE batteries_{name}_total counter")?;
+            writeln!(
+                f,
+                "batteries_{name}_total {}",
+                counter.load(Ordering::Relaxed)
+            )?;
+        }
+        writeln!(
+            f,
+            "# HELP batteries_solve_seconds How long the searches of `solve` took."
+        )?;
+        writeln!(f, "# TYPE batteries_solve_seconds histogram")?;
+        let mut searches = 0;
+        for (i, bucket) in self.solve_buckets.iter().enumerate() {
+            searches += bucket.load(Ordering::Relaxed);
+            match SOLVE_BUCKETS.get(i) {
+                Some(le) => writeln!(
+                    f,
+                    "batteries_solve_seconds_bucket{{le=\"{le}\"}} {searches}"
+                )?,
+                None => writeln!(
+                    f,
+                    "batteries_solve_seconds_bucket{{le=\"+Inf\"}} {searches}"
+                )?,
+            }
+        }
+        let seconds = self.solve_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        writeln!(f, "batteries_solve_seconds_sum {seconds}")?;
+        writeln!(f, "batteries_solve_seconds_count {searches}")
+    }
+}
+
+/// Answers JSON-RPC requests one line at a time, keeping the game of `recommend-next-test` and
+/// `apply-outcome` between them. The [`EliminationTable`]s that `solve` builds are kept too, so
+/// that solving a puzzle again, with any number of tries, skips building its table.
+#[derive(Default)]
+pub struct Server {
+    game: Option<Game>,
+    // The most recently used last.
+    tables: Vec<EliminationTable>,
+    metrics: Arc<Metrics>,
+}
+
+impl Server {
+    /// What the server did so far, which keeps being updated.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Forgets the game being played, e.g. when the client playing it went away, but keeps the
+    /// tables.
+    pub fn end_game(&mut self) {
+        self.game = None;
+    }
+
+    /// Answers a line holding a request, or a batch of them, with a line holding the response.
+    /// Notifications, i.e. requests without an `id`, get no response.
+    pub fn handle(&mut self, line: &str) -> Option<String> {
+        let request = match Json::parse(line) {
+            Ok(request) => request,
+            Err(err) => {
+                Metrics::count(&self.metrics.requests);
+                Metrics::count(&self.metrics.errors);
+                return Some(response(
+                    Json::Null,
+                    Err(Error {
+                        code: PARSE_ERROR,
+                        message: err,
+                    }),
+                ));
+            }
+        };
+        match request {
+            Json::Array(requests) if !requests.is_empty() => {
+                let responses: Vec<String> = requests
+                    .iter()
+                    .filter_map(|request| self.respond(request))
+                    .collect();
+                (!responses.is_empty()).then(|| format!("[{}]", responses.join(",")))
+            }
+            request => self.respond(&request),
+        }
+    }
+
+    fn respond(&mut self, request: &Json) -> Option<String> {
+        Metrics::count(&self.metrics.requests);
+        let id = request.get("id").cloned();
+        let result = match (request.get("jsonrpc"), request.get("method")) {
+            (Some(Json::String(version)), Some(Json::String(method))) if version == "2.0" => {
+                let params = request
+                    .get("params")
+                    .cloned()
+                    .unwrap_or(Json::Object(vec![]));
+                match params {
+                    Json::Object(_) => self.call(method, &params),
+                    _ => Err(invalid_params("the params have to be an object")),
+                }
+            }
+            _ => Err(Error {
+                code: INVALID_REQUEST,
+                message: "a request needs `\"jsonrpc\": \"2.0\"` and a `method`".to_string(),
+            }),
+        };
+        if result.is_err() {
+            Metrics::count(&self.metrics.errors);
+        }
+        // Invalid requests are answered even without an id, which they may lack for being
+        // invalid.
+        match (id, result) {
+            (None, Ok(_)) => None,
+            (None, Err(err)) if err.code != INVALID_REQUEST => None,
+            (id, result) => Some(response(id.unwrap_or(Json::Null), result)),
+        }
+    }
+
+    fn call(&mut self, method: &str, params: &Json) -> Result<Json, Error> {
+        match method {
+            "solve" => self.solve(params),
+            "verify" => verify(params),
+            "recommend-next-test" => {
+                let given = matches!(params, Json::Object(members) if !members.is_empty());
+                if given || self.game.is_none() {
+                    let params = puzzle(params)?;
+                    if checked_binomial(params.total, params.good).is_none_or(|n| n > 1_000_000) {
+                        return Err(invalid_params("the puzzle has too many universes to play"));
+                    }
+                    self.game = Some(Game::new(params));
+                }
+                let game = self.game.as_ref().expect("a game was just started");
+                Ok(Json::object([
+                    ("try", game.next_try().map_or(Json::Null, Json::set)),
+                    ("tries_left", Json::Number(game.tries_left() as f64)),
+                    (
+                        "universes_left",
+                        Json::Number(game.universes().len() as f64),
+                    ),
+                ]))
+            }
+            "apply-outcome" => {
+                let Some(game) = &mut self.game else {
+                    return Err(invalid_params(
+                        "there is no game; start one with `recommend-next-test`",
+                    ));
+                };
+                let batteries = batteries(params.get("try"), "try")?;
+                let turned_on = match params.get("turned_on") {
+                    Some(&Json::Bool(turned_on)) => turned_on,
+                    _ => return Err(invalid_params("`turned_on` has to be true or false")),
+                };
+                if turned_on {
+                    self.game = None;
+                    return Ok(Json::object([("done", Json::Bool(true))]));
+                }
+                game.record_failure(batteries);
+                Ok(Json::object([
+                    ("done", Json::Bool(false)),
+                    ("tries_left", Json::Number(game.tries_left() as f64)),
+                    (
+                        "universes_left",
+                        Json::Number(game.universes().len() as f64),
+                    ),
+                ]))
+            }
+            _ => Err(Error {
+                code: METHOD_NOT_FOUND,
+                message: format!("there is no method `{method}`"),
+            }),
+        }
+    }
+
+    fn solve(&mut self, params: &Json) -> Result<Json, Error> {
+        let puzzle = puzzle(params)?;
+        let deadline = match params.get("time_limit") {
+            None => None,
+            Some(&Json::Number(seconds)) if seconds >= 0.0 && seconds.is_finite() => {
+                Some(Instant::now() + Duration::from_secs_f64(seconds))
+            }
+            Some(_) => return Err(invalid_params("`time_limit` has to be a number of seconds")),
+        };
+        let solver = Solver {
+            params: puzzle,
+            anchor: (puzzle.tries >= 2).then(|| BitSet((1 << puzzle.slots) - 1)),
+            deadline,
+            first: true,
+            ..Solver::default()
+        };
+        // Checked before building a table, which the puzzles it rejects may be too large for.
+        solver
+            .check()
+            .map_err(|err| invalid_params(err.to_string()))?;
+        match self.tables.iter().position(|table| table.is_for(puzzle)) {
+            Some(i) => {
+                Metrics::count(&self.metrics.table_hits);
+                let table = self.tables.remove(i);
+                self.tables.push(table);
+            }
+            None => {
+                Metrics::count(&self.metrics.table_misses);
+                if self.tables.len() == TABLES {
+                    self.tables.remove(0);
+                }
+                self.tables.push(EliminationTable::new(puzzle));
+            }
+        }
+        let mut strategy = None;
+        let started = Instant::now();
+        let report = Solver {
+            table: self.tables.last(),
+            ..solver
+        }
+        .search(|solution| strategy = Some(solution));
+        self.metrics.record_solve(started.elapsed());
+        if strategy.is_some() {
+            Metrics::count(&self.metrics.solutions);
+        }
+        let outcome = match report.outcome() {
+            SearchOutcome::Found => "found",
+            SearchOutcome::NoneExist => "none-exist",
+            SearchOutcome::Unfinished => "unfinished",
+        };
+        let strategy = strategy.map_or(Json::Null, |strategy| {
+            Json::Array(strategy.into_iter().map(Json::set).collect())
+        });
+        Ok(Json::object([
+            ("strategy", strategy),
+            ("outcome", Json::String(outcome.to_string())),
+        ]))
+    }
+}
+
+fn response(id: Json, result: Result<Json, Error>) -> String {
+    let outcome = match result {
+        Ok(result) => ("result", result),
+        Err(Error { code, message }) => (
+            "error",
+            Json::object([
+                ("code", Json::Number(code as f64)),
+                ("message", Json::String(message)),
+            ]),
+        ),
+    };
+    Json::object([
+        ("jsonrpc", Json::String("2.0".to_string())),
+        ("id", id),
+        outcome,
+    ])
+    .to_string()
+}
+
+fn number(params: &Json, name: &str, default: u64) -> Result<u64, Error> {
+    match params.get(name) {
+        None => Ok(default),
+        Some(&Json::Number(n)) if n >= 0.0 && n.fract() == 0.0 && n <= u64::MAX as f64 => {
+            Ok(n as u64)
+        }
+        Some(_) => Err(invalid_params(format!(
+            "`{name}` has to be a non-negative integer"
+        ))),
+    }
+}
+
+// The puzzle the parameters are about.
+fn puzzle(params: &Json) -> Result<Params, Error> {
+    let default = Params::default();
+    let puzzle = Params {
+        total: number(params, "total", default.total)?,
+        good: number(params, "good", default.good)?,
+        slots: number(params, "slots", default.slots)?,
+        tries: number(params, "tries", default.tries)?,
+    };
+    let Params {
+        total,
+        good,
+        slots,
+        tries,
+    } = puzzle;
+    if !(1 <= slots && slots <= good && good <= total && total <= 64) || tries == 0 {
+        return Err(invalid_params(
+            "the puzzle has to satisfy 1 <= slots <= good <= total <= 64 with at least 1 try",
+        ));
+    }
+    Ok(puzzle)
+}
+
+// The batteries of a try or a solution entry, which have to be distinct and below 64.
+fn batteries(value: Option<&Json>, name: &str) -> Result<BitSet, Error> {
+    let invalid = || invalid_params(format!("`{name}` has to be an array of distinct batteries"));
+    let Some(Json::Array(items)) = value else {
+        return Err(invalid());
+    };
+    let mut set = BitSet::default();
+    for item in items {
+        match *item {
+            Json::Number(b) if (0.0..64.0).contains(&b) && b.fract() == 0.0 => {
+                if !set.insert(b as usize) {
+                    return Err(invalid());
+                }
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(set)
+}
+
+fn verify(params: &Json) -> Result<Json, Error> {
+    let puzzle = puzzle(params)?;
+    if checked_binomial(puzzle.total, puzzle.good).is_none_or(|n| n > 1_000_000) {
+        return Err(invalid_params("the puzzle has too many universes to check"));
+    }
+    let Some(Json::Array(entries)) = params.get("strategy") else {
+        return Err(invalid_params("`strategy` has to be an array of tries"));
+    };
+    let solution = entries
+        .iter()
+        .map(|entry| Ok(batteries(Some(entry), "strategy")?.into_iter().collect()))
+        .collect::<Result<Vec<Vec<usize>>, Error>>()?;
+    let tries = solution_tries(&solution, puzzle.slots);
+    Ok(match validate::is_valid_strategy(puzzle, &tries) {
+        Ok(()) => Json::object([
+            ("valid", Json::Bool(true)),
+            ("tries", Json::Number(tries.len() as f64)),
+        ]),
+        Err(violation) => Json::object([
+            ("valid", Json::Bool(false)),
+            ("reason", Json::String(violation.to_string())),
+        ]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prints_json() {
+        let text = r#" {"a": [1, -2.5e1, true, null], "b\né😀": {}} "#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(
+            json.get("a"),
+            Some(&Json::Array(vec![
+                Json::Number(1.0),
+                Json::Number(-25.0),
+                Json::Bool(true),
+                Json::Null,
+            ]))
+        );
+        assert_eq!(json.to_string(), "{\"a\":[1,-25,true,null],\"b\\né😀\":{}}");
+        assert_eq!(Json::parse(&json.to_string()), Ok(json));
+        for invalid in ["", "[1,]", "{\"a\" 1}", "tru", "\"open", "1 2"] {
+            assert!(Json::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    fn call(server: &mut Server, method: &str, params: &str) -> Json {
+        let request =
+            format!(r#"{{"jsonrpc": "2.0", "id": 7, "method": "{method}", "params": {params}}}"#);
+        let response = Json::parse(&server.handle(&request).unwrap()).unwrap();
+        assert_eq!(response.get("id"), Some(&Json::Number(7.0)));
+        match (response.get("result"), response.get("error")) {
+            (Some(result), None) => result.clone(),
+            (None, Some(error)) => panic!("{method} failed: {error}"),
+            _ => panic!("neither a result nor an error: {response}"),
+        }
+    }
+
+    #[test]
+    fn solves_and_verifies() {
+        let mut server = Server::default();
+        let result = call(
+            &mut server,
+            "solve",
+            r#"{"total": 5, "good": 4, "tries": 2}"#,
+        );
+        assert_eq!(
+            result.get("outcome"),
+            Some(&Json::String("found".to_string()))
+        );
+        let strategy = result.get("strategy").unwrap().to_string();
+        assert_eq!(strategy, "[[0,1],[2,3,4]]");
+
+        let params = format!(r#"{{"total": 5, "good": 4, "tries": 2, "strategy": {strategy}}}"#);
+        let result = call(&mut server, "verify", &params);
+        assert_eq!(result.get("valid"), Some(&Json::Bool(true)));
+        let result = call(
+            &mut server,
+            "verify",
+            r#"{"total": 5, "good": 4, "strategy": [[0, 1]]}"#,
+        );
+        assert_eq!(result.get("valid"), Some(&Json::Bool(false)));
+
+        let result = call(&mut server, "solve", r#"{"tries": 6}"#);
+        assert_eq!(
+            result.get("outcome"),
+            Some(&Json::String("none-exist".to_string()))
+        );
+        assert_eq!(result.get("strategy"), Some(&Json::Null));
+    }
+
+    #[test]
+    fn keeps_one_table_per_puzzle() {
+        let mut server = Server::default();
+        for tries in [7, 6, 7] {
+            call(&mut server, "solve", &format!(r#"{{"tries": {tries}}}"#));
+        }
+        assert_eq!(server.tables.len(), 1);
+        for total in 2..=TABLES as u64 + 2 {
+            call(
+                &mut server,
+                "solve",
+                &format!(r#"{{"total": {total}, "good": {total}, "slots": 1, "tries": 1}}"#),
+            );
+        }
+        assert_eq!(server.tables.len(), TABLES);
+        assert!(!server
+            .tables
+            .iter()
+            .any(|table| table.is_for(Params::default())));
+
+        call(&mut server, "recommend-next-test", "{}");
+        server.end_game();
+        let apply = r#"{"try": [0, 1], "turned_on": false}"#;
+        let request = format!(
+            r#"{{"jsonrpc": "2.0", "id": 1, "method": "apply-outcome", "params": {apply}}}"#
+        );
+        assert!(server.handle(&request).unwrap().contains("\"error\""));
+    }
+
+    #[test]
+    fn plays_a_game_until_the_toy_turns_on() {
+        let mut server = Server::default();
+        let universe = BitSet(0b1111_0000);
+        let mut tries = 0;
+        let mut next = call(&mut server, "recommend-next-test", "{}");
+        loop {
+            let Some(Json::Array(batteries)) = next.get("try") else {
+                panic!("no try to recommend: {next}");
+            };
+            let pair: BitSet = batteries
+                .iter()
+                .map(|b| match b {
+                    &Json::Number(b) => b as usize,
+                    _ => panic!("not a battery: {b}"),
+                })
+                .collect();
+            tries += 1;
+            let params = format!(
+                r#"{{"try": {}, "turned_on": {}}}"#,
+                next.get("try").unwrap(),
+                pair.is_subset(universe)
+            );
+            let outcome = call(&mut server, "apply-outcome", &params);
+            if outcome.get("done") == Some(&Json::Bool(true)) {
+                break;
+            }
+            next = call(&mut server, "recommend-next-test", "{}");
+        }
+        assert!(tries <= 7);
+    }
+
+    #[test]
+    fn counts_what_it_did() {
+        let mut server = Server::default();
+        let metrics = server.metrics();
+        for tries in [7, 6] {
+            call(&mut server, "solve", &format!(r#"{{"tries": {tries}}}"#));
+        }
+        server.handle("[");
+        let text = metrics.to_string();
+        for line in [
+            "batteries_requests_total 3",
+            "batteries_errors_total 1",
+            "batteries_solutions_found_total 1",
+            "batteries_table_cache_hits_total 1",
+            "batteries_table_cache_misses_total 1",
+            "batteries_solve_seconds_bucket{le=\"+Inf\"} 2",
+            "batteries_solve_seconds_count 2",
+        ] {
+            assert!(text.lines().any(|l| l == line), "no `{line}` in\n{text}");
+        }
+    }
+
+    #[test]
+    fn reports_errors_like_the_specification() {
+        let mut server = Server::default();
+        let code = |response: Option<String>| {
+            let response = Json::parse(&response.unwrap()).unwrap();
+            response.get("error").and_then(|e| e.get("code")).cloned()
+        };
+        let number = |code: i64| Some(Json::Number(code as f64));
+        assert_eq!(code(server.handle("{")), number(PARSE_ERROR));
+        assert_eq!(code(server.handle(r#"{"id": 1}"#)), number(INVALID_REQUEST));
+        let unknown = r#"{"jsonrpc": "2.0", "id": 1, "method": "fly"}"#;
+        assert_eq!(code(server.handle(unknown)), number(METHOD_NOT_FOUND));
+        let apply = r#"{"jsonrpc": "2.0", "id": 1, "method": "apply-outcome"}"#;
+        assert_eq!(code(server.handle(apply)), number(INVALID_PARAMS));
+        let bad = r#"{"jsonrpc": "2.0", "id": 1, "method": "solve", "params": {"good": 9}}"#;
+        assert_eq!(code(server.handle(bad)), number(INVALID_PARAMS));
+
+        // Notifications get no response, and a batch only the responses to its requests.
+        let notification = r#"{"jsonrpc": "2.0", "method": "recommend-next-test"}"#;
+        assert_eq!(server.handle(notification), None);
+        let batch = format!("[{notification}, {unknown}]");
+        let response = Json::parse(&server.handle(&batch).unwrap()).unwrap();
+        assert!(matches!(response, Json::Array(responses) if responses.len() == 1));
+    }
+}
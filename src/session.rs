@@ -0,0 +1,305 @@
+//! Games played with a real toy, saved so that they can be resumed later: `play --session FILE`
+//! saves the game after every answer. The text of a [`Session`] starts with [`HEADER`] and the
+//! puzzle, one `NAME VALUE` line each, followed by a `failed` line for each try that did not
+//! turn on the toy and a `turned on` line for the one that did, if the game is over. How many
+//! tries are left and which configurations are still possible follow as comments, for whoever
+//! reads the file; they are not read back.
+//!
+//! ```
+//! use batteries::session::Session;
+//! use batteries::{BitSet, Params};
+//!
+//! let session = Session {
+//!     params: Params::default(),
+//!     failed: vec![BitSet(0b11), BitSet(0b1100)],
+//!     turned_on: None,
+//! };
+//! let text = session.to_string();
+//! assert!(text.contains("failed 0,1\nfailed 2,3\n# 5 tries left, 41 configurations"));
+//! assert_eq!(Session::parse(&text), Ok(session));
+//! ```
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{BitSet, Game, Params};
+
+/// Starts the text of every [`Session`].
+pub const HEADER: &str = "# batteries session";
+
+/// A game as saved with `play --session FILE`: the puzzle, the tries that failed so far and the
+/// one that turned on the toy, if the game is over.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub params: Params,
+    pub failed: Vec<BitSet>,
+    pub turned_on: Option<BitSet>,
+}
+
+/// Why a text is not a [`Session`], to be shown after the name of the file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionError {
+    /// The text does not start with [`HEADER`].
+    NotASession,
+    /// The text ends before the parameter `name` of the puzzle.
+    Truncated { name: &'static str },
+    /// The puzzle has no battery, more than 64 or no try, or a toy that takes more batteries
+    /// than are functional.
+    Unplayable,
+    /// Line `line` is not what it should be.
+    Invalid { line: usize, what: String },
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::NotASession => write!(f, "is not a game saved with `--session`"),
+            SessionError::Truncated { name } => write!(f, "ends before `{name}`"),
+            SessionError::Unplayable => {
+                write!(f, "is not a session of a puzzle this program plays")
+            }
+            SessionError::Invalid { line, what } => write!(f, "line {line}: {what}"),
+        }
+    }
+}
+
+impl Session {
+    /// The game after the tries that failed.
+    pub fn game(&self) -> Game {
+        let mut game = Game::new(self.params);
+        for &pair in &self.failed {
+            game.record_failure(pair);
+        }
+        game
+    }
+
+    /// Reads a session from the text it is written as. Comments and empty lines are skipped.
+    pub fn parse(contents: &str) -> Result<Session, SessionError> {
+        if !contents.starts_with(HEADER) {
+            return Err(SessionError::NotASession);
+        }
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let mut number = |name: &'static str| {
+            let (line, text) = lines.next().ok_or(SessionError::Truncated { name })?;
+            let invalid = |what: &str| SessionError::Invalid {
+                line,
+                what: format!("{what} `{name}`"),
+            };
+            let value = text
+                .strip_prefix(name)
+                .filter(|rest| rest.starts_with(' '))
+                .ok_or_else(|| invalid("expected"))?;
+            value.trim().parse::<u64>().map_err(|_| invalid("invalid"))
+        };
+        let params = Params {
+            total: number("total")?,
+            good: number("good")?,
+            slots: number("slots")?,
+            tries: number("tries")?,
+        };
+        let Params {
+            total,
+            good,
+            slots,
+            tries,
+        } = params;
+        if !(1 <= slots && slots <= good && good <= total && total <= 64) || tries == 0 {
+            return Err(SessionError::Unplayable);
+        }
+        let mut session = Session {
+            params,
+            failed: vec![],
+            turned_on: None,
+        };
+        for (line, text) in lines {
+            let invalid = |what: String| SessionError::Invalid { line, what };
+            if session.turned_on.is_some() {
+                return Err(invalid(
+                    "the game is over after the toy turned on".to_string(),
+                ));
+            }
+            let (turned_on, value) = match (
+                text.strip_prefix("failed "),
+                text.strip_prefix("turned on "),
+            ) {
+                (Some(value), _) => (false, value),
+                (_, Some(value)) => (true, value),
+                _ => {
+                    return Err(invalid(
+                        "expected `failed` or `turned on` with a try".to_string(),
+                    ))
+                }
+            };
+            let pair = parse_try(value.trim(), slots).map_err(invalid)?;
+            if pair.into_iter().any(|b| b as u64 >= total) {
+                return Err(invalid(format!("there are only {total} batteries")));
+            }
+            if session.failed.len() as u64 >= tries {
+                return Err(invalid(format!(
+                    "the puzzle only has {}",
+                    count_tries(tries as usize)
+                )));
+            }
+            match turned_on {
+                true => session.turned_on = Some(pair),
+                false => session.failed.push(pair),
+            }
+        }
+        Ok(session)
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let batteries = |set: BitSet| {
+            set.into_iter()
+                .map(|b| b.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let Params {
+            total,
+            good,
+            slots,
+            tries,
+        } = self.params;
+        writeln!(f, "{HEADER}")?;
+        writeln!(
+            f,
+            "total {total}\ngood {good}\nslots {slots}\ntries {tries}"
+        )?;
+        for &pair in &self.failed {
+            writeln!(f, "failed {}", batteries(pair))?;
+        }
+        match self.turned_on {
+            Some(pair) => {
+                writeln!(f, "turned on {}", batteries(pair))?;
+                writeln!(
+                    f,
+                    "# the toy turned on after {}",
+                    count_tries(self.failed.len() + 1)
+                )
+            }
+            None => {
+                let game = self.game();
+                writeln!(
+                    f,
+                    "# {} left, {} configurations still possible:",
+                    count_tries(game.tries_left() as usize),
+                    game.universes().len()
+                )?;
+                for &universe in game.universes() {
+                    writeln!(f, "# {}", batteries(universe))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn count_tries(n: usize) -> String {
+    match n {
+        1 => "1 try".to_string(),
+        n => format!("{n} tries"),
+    }
+}
+
+// Reads a try as its batteries separated by commas, or as a `BitSet`.
+fn parse_try(value: &str, slots: u64) -> Result<BitSet, String> {
+    let invalid = || format!("invalid try `{value}`, expected {slots} distinct batteries");
+    let batteries: BitSet = if value.contains(',') && !value.trim().starts_with('{') {
+        let mut batteries = BitSet::default();
+        for battery in value.split(',') {
+            let battery: usize = battery.trim().parse().map_err(|_| invalid())?;
+            if battery >= 64 || !batteries.insert(battery) {
+                return Err(invalid());
+            }
+        }
+        batteries
+    } else {
+        value
+            .parse()
+            .map_err(|err| format!("{}: {err}", invalid()))?
+    };
+    match u64::from(batteries.len()) == slots {
+        true => Ok(batteries),
+        false => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let params = Params::default();
+        let sessions = [
+            Session {
+                params,
+                failed: vec![],
+                turned_on: None,
+            },
+            Session {
+                params,
+                failed: vec![BitSet(0b11), BitSet(0b1100), BitSet(0b10100)],
+                turned_on: None,
+            },
+            Session {
+                params: Params {
+                    total: 6,
+                    good: 3,
+                    slots: 3,
+                    tries: 20,
+                },
+                failed: vec![BitSet(0b111)],
+                turned_on: Some(BitSet(0b111000)),
+            },
+        ];
+        for session in sessions {
+            assert_eq!(Session::parse(&session.to_string()), Ok(session));
+        }
+    }
+
+    #[test]
+    fn rejects_games_that_cannot_have_happened() {
+        let header = format!("{HEADER}\ntotal 8\ngood 4\nslots 2\ntries 2\n");
+        let error = |moves: &str| Session::parse(&format!("{header}{moves}")).unwrap_err();
+        let invalid = |line: usize, what: &str| SessionError::Invalid {
+            line,
+            what: what.to_string(),
+        };
+        assert_eq!(Session::parse("total 8\n"), Err(SessionError::NotASession));
+        assert_eq!(
+            Session::parse(&format!("{HEADER}\ntotal 8\ngood 4\n")),
+            Err(SessionError::Truncated { name: "slots" })
+        );
+        assert_eq!(
+            Session::parse(&format!("{HEADER}\ntotal 8\ngood 4\nslots 5\ntries 2\n")),
+            Err(SessionError::Unplayable)
+        );
+        assert_eq!(
+            error("turned on 0,1\nfailed 2,3\n"),
+            invalid(7, "the game is over after the toy turned on")
+        );
+        assert_eq!(
+            error("failed 0,8\n"),
+            invalid(6, "there are only 8 batteries")
+        );
+        assert_eq!(
+            error("failed 0,1\nfailed 2,3\nfailed 4,5\n"),
+            invalid(8, "the puzzle only has 2 tries")
+        );
+        assert_eq!(
+            error("failed 0,1,2\n"),
+            invalid(6, "invalid try `0,1,2`, expected 2 distinct batteries")
+        );
+    }
+}
@@ -0,0 +1,1521 @@
+//! The search for strategies, which needs threads and clocks and so the standard library.
+
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{
+    binomial, checked_binomial, cover, rank, BitSet, CombinationIter, EliminationTable, Params,
+    Shard, Solution,
+};
+
+/// Searches for strategies that always turn on the toy. The fields say what part of the search
+/// space to explore; the default searches everything for the original puzzle.
+///
+/// ```no_run
+/// use batteries::{BitSet, Solver};
+///
+/// let solver = Solver {
+///     anchor: Some(BitSet(0b11)),
+///     ..Solver::default()
+/// };
+/// let report = solver.search(|solution| println!("{solution:?}"));
+/// println!("{} strategies", report.found);
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct Solver<'a> {
+    pub params: Params,
+    /// Fixes the first try. Since all batteries are alike, any strategy can be relabeled so that
+    /// its first try is the anchor, so this loses no solutions up to relabeling while making the
+    /// search much smaller.
+    pub anchor: Option<BitSet>,
+    /// Only searches the corresponding slice of the candidate tries (ordered by their rank in
+    /// [`CombinationIter`]); the slices of all shards are disjoint and cover everything.
+    pub shard: Option<Shard>,
+    /// Stops the search once it is reached; the report says how far it got.
+    pub deadline: Option<Instant>,
+    /// Writes a single-line JSON status record to stderr at this interval, for batch schedulers
+    /// and log scrapers watching long runs. The last record of a search has `"done": true`.
+    pub heartbeat: Option<Duration>,
+    /// Writes a line to stderr at this interval saying how much of the search space was
+    /// examined, about how long the rest will take and how many strategies were found so far, for
+    /// people watching long runs. The search ends with a line saying how long it took.
+    pub progress: Option<Duration>,
+    /// Stops the search early once set, e.g. by another engine that got there first.
+    pub cancel: Option<&'a AtomicBool>,
+    /// Stops the search as soon as a strategy is found and only reports that one. With several
+    /// threads it is the one found first, which need not be the first in the usual order.
+    pub first: bool,
+    /// Reuses this table of which tries rule out which universes instead of building it when the
+    /// search starts, e.g. for several searches with different numbers of tries. It has to be
+    /// for the same puzzle.
+    pub table: Option<&'a EliminationTable>,
+    /// Only examines candidates whose batteries outside the anchor are used in a canonical order:
+    /// going through the tries from the last one in [`CombinationIter`] order down, each battery
+    /// that was not used yet is the highest one left. Every strategy can be relabeled into such
+    /// a candidate, so this loses no solutions up to relabeling while examining far fewer
+    /// candidates, but most relabelings of each solution are no longer reported.
+    pub break_symmetry: bool,
+    /// Searches on this many threads; by default the search runs on the calling thread, and on
+    /// WASI, which has no threads, it always does. The solutions are reported in the same order
+    /// either way, each slice of the candidates once it and the slices before it are searched.
+    pub threads: Option<NonZeroUsize>,
+    /// Called with what the search did with each candidate it examined and each range of
+    /// candidates it pruned, to find out why the search takes as long as it does. With several
+    /// threads it is called from all of them, in no particular order. [`Solver::solutions`]
+    /// does not trace its search.
+    pub trace: Option<&'a (dyn Fn(TraceEvent) + Sync)>,
+}
+
+/// How the search dealt with the candidates of a [`TraceEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceOutcome {
+    /// Pruned because the try that was picked last repeats the anchor.
+    RepeatsAnchor,
+    /// Pruned because the candidates are not canonical, with [`Solver::break_symmetry`].
+    NotCanonical,
+    /// Pruned because the tries picked so far always turn on the toy already.
+    AlwaysOn,
+    /// A candidate that is a solution.
+    Solution,
+    /// A candidate that leaves universes without enough batteries functional in all of them.
+    Failed,
+}
+
+/// What the search did with the candidates with ranks in `ranks`, which all share `tries`: a
+/// single candidate that was examined, or all the candidates that were pruned at once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Ranks of the candidates, as sets of indices of tries in [`CombinationIter`] order.
+    pub ranks: Range<u64>,
+    /// Indices of the tries all the candidates share, in the order the search picks them: from
+    /// the last one down. The anchor is not among them.
+    pub tries: Vec<usize>,
+    pub outcome: TraceOutcome,
+    /// Universes left once the anchor and `tries` failed.
+    pub universes_left: u64,
+}
+
+/// How much of the search space was explored, so interrupted searches can still say something
+/// useful.
+pub struct SearchReport {
+    pub examined: u64,
+    /// Examined candidates that were ruled out without looking at them one by one, because one of
+    /// their tries repeats the first try or the tries before it always turn on the toy already,
+    /// or because they are not canonical with [`Solver::break_symmetry`].
+    pub pruned: u64,
+    pub total: u64,
+    pub found: u64,
+    pub timed_out: bool,
+    /// Whether the tries of some examined candidate before its last ones already always turn on
+    /// the toy, so that fewer tries are enough even if no strategy with all of them was found.
+    pub fewer_tries_suffice: bool,
+    /// Fewest universes consistent with the tries of any examined candidate that failed to be a
+    /// solution.
+    pub fewest_remaining: Option<usize>,
+}
+
+/// Whether a search settled if there is a strategy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchOutcome {
+    /// At least one strategy was found.
+    Found,
+    /// Every candidate was examined and none is a strategy, so there is none (in the shard that
+    /// was searched).
+    NoneExist,
+    /// The search stopped at the deadline or was cancelled before finding a strategy.
+    Unfinished,
+}
+
+impl SearchReport {
+    pub fn outcome(&self) -> SearchOutcome {
+        if self.found > 0 {
+            SearchOutcome::Found
+        } else if self.examined == self.total && !self.timed_out {
+            SearchOutcome::NoneExist
+        } else {
+            SearchOutcome::Unfinished
+        }
+    }
+}
+
+/// Why a [`Solver`] cannot search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolverError {
+    /// The numbers do not satisfy 1 <= slots <= good <= total <= 64.
+    InvalidParams(Params),
+    /// There are more possible tries than the 128 that candidates are picked from.
+    TooManyPairs { pairs: u64 },
+    /// Fewer tries than the anchor and the last one, which are all a search needs.
+    TooFewTries { tries: u64, fewest: u64 },
+    /// More tries than there are different ones to pick, besides the last one.
+    TooManyTries { tries: u64, most: u64 },
+    /// The anchor does not hold `slots` of the batteries.
+    InvalidAnchor(BitSet),
+    /// The candidates do not fit into u64 to be counted.
+    TooManyCandidates,
+    /// The shard is not one of 1..=count with a positive count.
+    InvalidShard(Shard),
+    /// The table is for another puzzle.
+    WrongTable,
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolverError::InvalidParams(params) => write!(
+                f,
+                "{} batteries with {} functional ones and {} slots do not satisfy \
+                1 <= slots <= good <= total <= 64",
+                params.total, params.good, params.slots
+            ),
+            SolverError::TooManyPairs { pairs } => {
+                write!(
+                    f,
+                    "the search supports up to 128 possible tries, not {pairs}"
+                )
+            }
+            SolverError::TooFewTries { tries, fewest } => {
+                write!(f, "the search needs at least {fewest} tries, not {tries}")
+            }
+            SolverError::TooManyTries { tries, most } => {
+                write!(
+                    f,
+                    "the possible tries only make for strategies with up to {most} tries, not \
+                    {tries}"
+                )
+            }
+            SolverError::InvalidAnchor(anchor) => {
+                write!(f, "the anchor {anchor:#} is not one of the possible tries")
+            }
+            SolverError::TooManyCandidates => write!(f, "there are too many candidates to search"),
+            SolverError::InvalidShard(Shard { index, count }) => {
+                write!(f, "there is no shard {index} of {count}")
+            }
+            SolverError::WrongTable => write!(f, "the table is for another puzzle"),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+impl<'a> Solver<'a> {
+    /// Runs the search and calls `on_solution` for every strategy found. A strategy is the list
+    /// of tries, where the last entry holds the batteries guaranteed to be functional once all
+    /// the previous tries failed. Strategies that only differ by relabeling batteries are all
+    /// reported. Panics if the solver cannot search; see [`Solver::try_search`].
+    pub fn search(&self, on_solution: impl FnMut(Solution)) -> SearchReport {
+        self.try_search(on_solution)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`Solver::search`], but returns why the solver cannot search instead of panicking.
+    pub fn try_search(
+        &self,
+        mut on_solution: impl FnMut(Solution),
+    ) -> Result<SearchReport, SolverError> {
+        self.check()?;
+        Ok(search(*self, None, &mut on_solution))
+    }
+
+    /// Says what the search does with the candidate with this rank, as a set of indices of tries
+    /// in [`CombinationIter`] order, or `None` if there is no such candidate. Only that candidate
+    /// is looked at, on the calling thread and without status records, so this is how to find
+    /// out why a particular candidate is or is not a solution. Panics if the solver cannot
+    /// search; see [`Solver::check`].
+    pub fn examine(&self, rank: u64) -> Option<TraceEvent> {
+        if let Err(err) = self.check() {
+            panic!("{err}");
+        }
+        let event = Mutex::new(None);
+        let record = |e: TraceEvent| *event.lock().unwrap() = Some(e);
+        let config = Solver {
+            shard: None,
+            deadline: None,
+            heartbeat: None,
+            progress: None,
+            cancel: None,
+            threads: None,
+            trace: Some(&record),
+            ..*self
+        };
+        search(config, Some(rank..rank.saturating_add(1)), &mut |_| {});
+        event.into_inner().unwrap()
+    }
+
+    /// Checks that the puzzle and the part of the search space to explore make sense: the
+    /// parameters are in range, there are at most 128 possible tries, there are tries for the
+    /// anchor, if there is one, and the last one, the anchor is one of the possible tries, the
+    /// candidates can be counted, the shard exists and the table is for this puzzle.
+    pub fn check(&self) -> Result<(), SolverError> {
+        let Params {
+            total,
+            good,
+            slots,
+            tries,
+        } = self.params;
+        if !(1 <= slots && slots <= good && good <= total && total <= 64) {
+            return Err(SolverError::InvalidParams(self.params));
+        }
+        let pairs = binomial(total, slots);
+        if pairs > 128 {
+            return Err(SolverError::TooManyPairs { pairs });
+        }
+        let fixed = if self.anchor.is_some() { 2 } else { 1 };
+        if tries < fixed {
+            return Err(SolverError::TooFewTries {
+                tries,
+                fewest: fixed,
+            });
+        }
+        if let Some(anchor) = self.anchor {
+            if u64::from(anchor.len()) != slots || anchor.0 >> total != 0 {
+                return Err(SolverError::InvalidAnchor(anchor));
+            }
+        }
+        // The anchor is one of the pairs, and repeating it is pruned.
+        let steps = tries - fixed;
+        let distinct = pairs - u64::from(self.anchor.is_some());
+        if steps > distinct {
+            return Err(SolverError::TooManyTries {
+                tries,
+                most: distinct + fixed,
+            });
+        }
+        if checked_binomial(pairs, steps).is_none() {
+            return Err(SolverError::TooManyCandidates);
+        }
+        if let Some(shard) = self.shard {
+            if !(1 <= shard.index && shard.index <= shard.count) {
+                return Err(SolverError::InvalidShard(shard));
+            }
+        }
+        if self.table.is_some_and(|table| !table.is_for(self.params)) {
+            return Err(SolverError::WrongTable);
+        }
+        Ok(())
+    }
+
+    /// Runs the search on a background thread and returns the strategies found as an iterator,
+    /// in the order [`Solver::search`] reports them. The search waits for each strategy to be
+    /// taken before looking for the next one, and stops once the iterator is dropped, so e.g.
+    /// `take(1)` only searches up to the first strategy. `cancel` is checked whenever the next
+    /// strategy is asked for. On WASI, which has no threads, the whole search runs on the calling
+    /// thread before the first strategy is returned, and `cancel` is checked by the search
+    /// instead. Panics if the solver cannot search; see [`Solver::check`].
+    ///
+    /// ```no_run
+    /// use batteries::Solver;
+    ///
+    /// let first = Solver::default().solutions().next();
+    /// println!("{first:?}");
+    /// ```
+    pub fn solutions(&self) -> Solutions<'a> {
+        if let Err(err) = self.check() {
+            panic!("{err}");
+        }
+        let config = Solver {
+            params: self.params,
+            anchor: self.anchor,
+            shard: self.shard,
+            deadline: self.deadline,
+            heartbeat: self.heartbeat,
+            progress: self.progress,
+            cancel: None,
+            first: self.first,
+            table: None,
+            break_symmetry: self.break_symmetry,
+            threads: self.threads,
+            trace: None,
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        if cfg!(target_os = "wasi") {
+            let (sender, receiver) = mpsc::channel();
+            let report = Solver {
+                cancel: self.cancel,
+                ..config
+            }
+            .search(|solution| {
+                sender.send(solution).expect("the receiver is still there");
+            });
+            return Solutions {
+                receiver: Some(receiver),
+                stop,
+                cancel: None,
+                search: None,
+                report: Some(report),
+            };
+        }
+        // Without a buffer every send waits for the iterator to ask for the strategy.
+        let (sender, receiver) = mpsc::sync_channel(0);
+        let search = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                let config = Solver {
+                    cancel: Some(&*stop),
+                    ..config
+                };
+                config.search(|solution| {
+                    if sender.send(solution).is_err() {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                })
+            })
+        };
+        Solutions {
+            receiver: Some(receiver),
+            stop,
+            cancel: self.cancel,
+            search: Some(search),
+            report: None,
+        }
+    }
+
+    /// Searches for a strategy with `params.tries` tries and then with one try fewer at a time,
+    /// down to a single try, until a search finds none without finding that fewer tries are
+    /// enough ([`SearchReport::fewer_tries_suffice`]) or the deadline passes. After each search
+    /// `on_budget` gets its configuration, the first strategy it found and its report; the
+    /// search with a single try does without the anchor, as that try is the last one. Returns
+    /// the fewest tries that a strategy was found with.
+    pub fn min_tries(
+        &self,
+        mut on_budget: impl FnMut(&Solver, Option<&Solution>, &SearchReport),
+    ) -> Option<u64> {
+        let built;
+        let table = match self.table {
+            Some(table) => table,
+            None => {
+                built = EliminationTable::new(self.params);
+                &built
+            }
+        };
+        let mut fewest = None;
+        for tries in (1..=self.params.tries).rev() {
+            let config = Solver {
+                params: Params {
+                    tries,
+                    ..self.params
+                },
+                anchor: self.anchor.filter(|_| tries >= 2),
+                first: true,
+                table: Some(table),
+                ..*self
+            };
+            let mut first = None;
+            let report = config.search(|solution| first = Some(solution));
+            on_budget(&config, first.as_ref(), &report);
+            match first {
+                Some(_) => fewest = Some(tries),
+                None if report.fewer_tries_suffice => {}
+                None => break,
+            }
+        }
+        fewest
+    }
+}
+
+/// Narrows down the fewest tries that always turn on the toy in the puzzle of `params`, whose
+/// `tries` are ignored, from `lowest..=highest` by bisection, where `highest` tries are known to
+/// be enough. Each search fixes the first try, skips relabelings and stops at the first strategy,
+/// which keeps it exhaustive up to relabeling; [`cover`] settles up to 2 tries instead. Returns
+/// the range the fewest tries were narrowed down to, which is a single number unless `deadline`
+/// passed first or a search would have too many candidates.
+pub fn fewest_tries(
+    params: Params,
+    (mut lowest, mut highest): (u64, u64),
+    deadline: Option<Instant>,
+    threads: Option<NonZeroUsize>,
+) -> (u64, u64) {
+    let universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+    let table = EliminationTable::new(params);
+    let pairs = table.pairs().len() as u64;
+    let works = |tries: u64| -> Option<bool> {
+        if tries < 3 {
+            return Some(cover(&universes, tries as usize, params.slots).is_some());
+        }
+        checked_binomial(pairs, tries - 1)?;
+        let mut found = false;
+        let report = Solver {
+            params: Params { tries, ..params },
+            anchor: Some(BitSet((1 << params.slots) - 1)),
+            deadline,
+            first: true,
+            table: Some(&table),
+            break_symmetry: true,
+            threads,
+            ..Solver::default()
+        }
+        .search(|_| found = true);
+        let works = found || report.fewer_tries_suffice;
+        (works || !report.timed_out).then_some(works)
+    };
+    while lowest < highest {
+        let tries = (lowest + highest) / 2;
+        match works(tries) {
+            Some(true) => highest = tries,
+            Some(false) => lowest = tries + 1,
+            None => break,
+        }
+    }
+    (lowest, highest)
+}
+
+/// The strategies found by [`Solver::solutions`]. Dropping it stops the search and waits for its
+/// thread to notice, which happens after at most about a thousand more candidates.
+pub struct Solutions<'a> {
+    receiver: Option<mpsc::Receiver<Solution>>,
+    stop: Arc<AtomicBool>,
+    cancel: Option<&'a AtomicBool>,
+    search: Option<std::thread::JoinHandle<SearchReport>>,
+    // How far the search got, if it ran to the end up front.
+    report: Option<SearchReport>,
+}
+
+impl Solutions<'_> {
+    /// Stops the search, unless it is done already, and says how far it got.
+    pub fn finish(mut self) -> SearchReport {
+        self.stop().expect("the search is only stopped once")
+    }
+
+    fn stop(&mut self) -> Option<SearchReport> {
+        self.stop.store(true, Ordering::Relaxed);
+        // A search waiting to hand over a strategy gives up once nobody can take it.
+        self.receiver = None;
+        let Some(search) = self.search.take() else {
+            return self.report.take();
+        };
+        Some(
+            search
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+        )
+    }
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Solution;
+
+    fn next(&mut self) -> Option<Solution> {
+        if self.cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+            self.stop();
+        }
+        self.receiver.as_ref()?.recv().ok()
+    }
+}
+
+impl Drop for Solutions<'_> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Searches the candidates with ranks in `ranks`, or else those of the shard.
+fn search(
+    config: Solver,
+    ranks: Option<Range<u64>>,
+    on_solution: &mut dyn FnMut(Solution),
+) -> SearchReport {
+    let Solver {
+        params,
+        anchor,
+        shard,
+        threads,
+        ..
+    } = config;
+    let started = Instant::now();
+    let built;
+    let table = match config.table {
+        Some(table) => table,
+        None => {
+            built = EliminationTable::new(params);
+            &built
+        }
+    };
+
+    let free_steps = params.tries - if anchor.is_some() { 2 } else { 1 };
+
+    let anchor_index = anchor.map(|a| rank(a) as usize);
+    let initial = match anchor_index {
+        Some(anchor) => table.survivors(anchor).to_vec(),
+        None => (0..table.words)
+            .map(|w| match table.universes.len() - 128 * w {
+                n if n >= 128 => u128::MAX,
+                n => (1 << n) - 1,
+            })
+            .collect(),
+    };
+    // The batteries outside the anchor, from the highest one down.
+    let free_batteries: Vec<usize> = (0..params.total as usize)
+        .rev()
+        .filter(|&b| !anchor.is_some_and(|a| a.contains(b)))
+        .collect();
+    let space = SearchSpace {
+        canonical_used: (0..=free_batteries.len())
+            .map(|used| free_batteries[..used].iter().copied().collect())
+            .collect(),
+        anchor_index,
+        table,
+        initial,
+    };
+
+    // Next we try all possible sets of battery pairs for the remaining tries but the last one and
+    // assume each pair will not turn on the toy. After that we have used up all tries but one, so
+    // all that remains is to check if all remaining "universes" contain a battery pair that is
+    // functional in each one. The candidates are sets of pair indices, of which there can be
+    // more than 64. They are ranked in `CombinationIter` order, and without free steps the
+    // only candidate is the empty one.
+    let candidates = binomial(table.pairs.len() as u64, free_steps);
+    let ranks = match ranks {
+        Some(ranks) => {
+            let start = ranks.start.min(candidates);
+            start..ranks.end.clamp(start, candidates)
+        }
+        None => match shard {
+            Some(Shard { index, count }) => {
+                let total = u128::from(candidates);
+                let bound = |i: u64| (total * u128::from(i) / u128::from(count)) as u64;
+                bound(index - 1)..bound(index)
+            }
+            None => 0..candidates,
+        },
+    };
+
+    let mut status = Status::new(config, started);
+    let threads = match cfg!(target_os = "wasi") {
+        true => 1,
+        false => threads.map_or(1, NonZeroUsize::get),
+    };
+    let report = match threads {
+        1 => {
+            let mut tick = |report: &SearchReport| status.tick(report);
+            let found_first = AtomicBool::new(false);
+            space.search(
+                config,
+                free_steps,
+                ranks,
+                &found_first,
+                &mut tick,
+                on_solution,
+            )
+        }
+        threads => {
+            space.search_in_parallel(config, free_steps, ranks, threads, &mut status, on_solution)
+        }
+    };
+    status.finish(&report);
+    report
+}
+
+// Writes the status records that `Solver::heartbeat` and `Solver::progress` ask for, each when
+// its interval has passed since it was last written.
+struct Status {
+    started: Instant,
+    heartbeat: Option<(Duration, Instant)>,
+    progress: Option<(Duration, Instant)>,
+}
+
+impl Status {
+    fn new(config: Solver, started: Instant) -> Self {
+        let schedule = |interval: Duration| (interval, started + interval);
+        Status {
+            started,
+            heartbeat: config.heartbeat.map(schedule),
+            progress: config.progress.map(schedule),
+        }
+    }
+
+    // When the next record is due, if any are written at all.
+    fn next(&self) -> Option<Instant> {
+        [self.heartbeat, self.progress]
+            .into_iter()
+            .flatten()
+            .map(|(_, next)| next)
+            .min()
+    }
+
+    fn tick(&mut self, report: &SearchReport) {
+        let now = Instant::now();
+        if let Some((interval, next)) = &mut self.heartbeat {
+            if now >= *next {
+                emit_heartbeat(report, self.started, false);
+                *next = now + *interval;
+            }
+        }
+        if let Some((interval, next)) = &mut self.progress {
+            if now >= *next {
+                emit_progress(report, self.started, false);
+                *next = now + *interval;
+            }
+        }
+    }
+
+    fn finish(&self, report: &SearchReport) {
+        if self.heartbeat.is_some() {
+            emit_heartbeat(report, self.started, true);
+        }
+        if self.progress.is_some() {
+            emit_progress(report, self.started, true);
+        }
+    }
+}
+
+// What the search needs to know to examine a candidate, shared by all threads.
+struct SearchSpace<'t> {
+    table: &'t EliminationTable,
+    // The set of universes that are possible before the free steps, i.e. after the anchor failed.
+    initial: Vec<u128>,
+    anchor_index: Option<usize>,
+    // For `Solver::break_symmetry`: the batteries outside the anchor that a canonical candidate
+    // uses, by how many of them it uses.
+    canonical_used: Vec<BitSet>,
+}
+
+impl SearchSpace<'_> {
+    // Examines the candidates of `free_steps` tries with ranks in `ranks` in order. `tick` is
+    // called with the report so
+    // far every 1024 candidates, whenever the deadline, status records and cancel flag are
+    // checked.
+    // With `Solver::first`, `found_first` is set once a strategy is found, and the search also
+    // stops once it is set by another thread.
+    fn search(
+        &self,
+        config: Solver,
+        free_steps: u64,
+        ranks: Range<u64>,
+        found_first: &AtomicBool,
+        tick: &mut dyn FnMut(&SearchReport),
+        on_solution: &mut dyn FnMut(Solution),
+    ) -> SearchReport {
+        let words = self.initial.len();
+        let mut sets = vec![0; (free_steps as usize + 1) * words];
+        sets[..words].copy_from_slice(&self.initial);
+        let mut walk = Walk {
+            space: self,
+            config,
+            report: SearchReport {
+                examined: 0,
+                pruned: 0,
+                total: ranks.end - ranks.start,
+                found: 0,
+                timed_out: false,
+                fewer_tries_suffice: false,
+                fewest_remaining: None,
+            },
+            next_check: 0,
+            stopped: false,
+            found_first,
+            steps: vec![],
+            used: BitSet::default(),
+            sets,
+            ranks,
+            tick,
+            on_solution,
+        };
+        walk.descend(free_steps, self.table.pairs.len() as u64, 0);
+        walk.report
+    }
+
+    // Splits the candidates into many more chunks than threads, so threads that get cheap chunks
+    // pick up more of them, and searches the chunks on `threads` threads. The calling thread
+    // reports the solutions of each chunk once it and all chunks before it are done, which keeps
+    // the order of the single-threaded search without holding the solutions of the whole search,
+    // and lets `on_solution` cancel it. In between it writes the status records from counters the
+    // workers update whenever they check the clock.
+    fn search_in_parallel(
+        &self,
+        config: Solver,
+        free_steps: u64,
+        ranks: Range<u64>,
+        threads: usize,
+        status: &mut Status,
+        on_solution: &mut dyn FnMut(Solution),
+    ) -> SearchReport {
+        let Range { start: first, end } = ranks;
+        let chunks = (threads * 16) as u64;
+        let bound =
+            |i: u64| first + (u128::from(end - first) * u128::from(i) / u128::from(chunks)) as u64;
+        let next_chunk = AtomicU64::new(0);
+        let (examined, found) = (AtomicU64::new(0), AtomicU64::new(0));
+        let found_first = AtomicBool::new(false);
+        let mut total = SearchReport {
+            examined: 0,
+            pruned: 0,
+            total: end - first,
+            found: 0,
+            timed_out: false,
+            fewer_tries_suffice: false,
+            fewest_remaining: None,
+        };
+        let mut merge = |report: SearchReport, solutions: Vec<Solution>| {
+            total.examined += report.examined;
+            total.pruned += report.pruned;
+            total.timed_out |= report.timed_out;
+            total.fewer_tries_suffice |= report.fewer_tries_suffice;
+            total.fewest_remaining = match (total.fewest_remaining, report.fewest_remaining) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            for solution in solutions {
+                // Several threads may have found one before they noticed the others did.
+                if config.first && total.found > 0 {
+                    break;
+                }
+                total.found += 1;
+                on_solution(solution);
+            }
+        };
+
+        std::thread::scope(|scope| {
+            let (done, finished) = mpsc::channel::<(u64, SearchReport, Vec<Solution>)>();
+            for _ in 0..threads {
+                let done = done.clone();
+                let worker = || {
+                    let done = done;
+                    loop {
+                        let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                        if chunk >= chunks {
+                            break;
+                        }
+                        let steps = bound(chunk)..bound(chunk + 1);
+                        let mut published = (0, 0);
+                        let mut tick = |report: &SearchReport| {
+                            examined.fetch_add(report.examined - published.0, Ordering::Relaxed);
+                            found.fetch_add(report.found - published.1, Ordering::Relaxed);
+                            published = (report.examined, report.found);
+                        };
+                        let mut solutions = vec![];
+                        let report = self.search(
+                            config,
+                            free_steps,
+                            steps,
+                            &found_first,
+                            &mut tick,
+                            &mut |s| solutions.push(s),
+                        );
+                        tick(&report);
+                        let stopped = report.timed_out
+                            || config.cancel.is_some_and(|c| c.load(Ordering::Relaxed))
+                            || found_first.load(Ordering::Relaxed);
+                        if stopped {
+                            // Make the other threads stop as well instead of starting new chunks.
+                            next_chunk.store(chunks, Ordering::Relaxed);
+                        }
+                        // The calling thread only stops listening once every worker is done.
+                        done.send((chunk, report, solutions)).unwrap();
+                    }
+                };
+                scope.spawn(worker);
+            }
+            drop(done);
+
+            // The chunks that are done but wait for one before them, and the next to report.
+            let mut waiting = std::collections::BTreeMap::new();
+            let mut next = 0;
+            // All senders are dropped once every worker is done, which ends the wait.
+            loop {
+                let received = match status.next() {
+                    Some(due) => {
+                        finished.recv_timeout(due.saturating_duration_since(Instant::now()))
+                    }
+                    None => finished
+                        .recv()
+                        .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok((chunk, report, solutions)) => {
+                        waiting.insert(chunk, (report, solutions));
+                        while let Some((report, solutions)) = waiting.remove(&next) {
+                            merge(report, solutions);
+                            next += 1;
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        let report = SearchReport {
+                            examined: examined.load(Ordering::Relaxed),
+                            pruned: 0,
+                            total: end - first,
+                            found: found.load(Ordering::Relaxed),
+                            timed_out: false,
+                            fewer_tries_suffice: false,
+                            fewest_remaining: None,
+                        };
+                        status.tick(&report);
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            // Chunks after one that was never searched, because the search stopped early.
+            for (report, solutions) in waiting.into_values() {
+                merge(report, solutions);
+            }
+        });
+        total
+    }
+}
+
+// A depth-first walk over the candidates of a range of ranks, in the order of
+// `CombinationIter`: its colexicographic order lists the candidates by their last pair, then by
+// the pair before it and so on, so the walk picks the pairs from the last one down. The universes
+// left after the pairs picked so far are kept for each depth, so candidates that share pairs only
+// filter the universes for them once, and subtrees without any universe left are skipped.
+struct Walk<'s, 'a> {
+    space: &'s SearchSpace<'s>,
+    config: Solver<'a>,
+    ranks: Range<u64>,
+    report: SearchReport,
+    // Value of `report.examined` at which to check the deadline, status records and cancel flag
+    // next.
+    next_check: u64,
+    stopped: bool,
+    found_first: &'s AtomicBool,
+    // The pairs picked so far, from the last one down.
+    steps: Vec<usize>,
+    // The batteries outside the anchor in the pairs picked so far.
+    used: BitSet,
+    // The set of universes left at each depth, one after the other.
+    sets: Vec<u128>,
+    tick: &'s mut dyn FnMut(&SearchReport),
+    on_solution: &'s mut dyn FnMut(Solution),
+}
+
+impl Walk<'_, '_> {
+    // Picks `left` more pairs, all below `below`; `base` is the rank of the candidates with the
+    // smallest remaining pairs.
+    fn descend(&mut self, left: u64, below: u64, base: u64) {
+        let depth = self.steps.len();
+        if left == 0 {
+            self.examine(depth, base);
+            return;
+        }
+        let words = self.space.initial.len();
+        for pair in left - 1..below {
+            // The candidates whose `left`-th smallest pair is `pair` have consecutive ranks.
+            let first = base + binomial(pair, left);
+            let last = first + binomial(pair, left - 1);
+            if last <= self.ranks.start {
+                continue;
+            }
+            if first >= self.ranks.end || self.stopped {
+                return;
+            }
+            let ranks = first.max(self.ranks.start)..last.min(self.ranks.end);
+
+            // Repeating the first try cannot turn on the toy.
+            if self.space.anchor_index == Some(pair as usize) {
+                self.skip(ranks, pair as usize, TraceOutcome::RepeatsAnchor);
+                continue;
+            }
+            let pair_set = self.space.table.pairs[pair as usize];
+            let used = self.used | (pair_set - self.config.anchor.unwrap_or_default());
+            if self.config.break_symmetry && used != self.space.canonical_used[used.len() as usize]
+            {
+                self.skip(ranks, pair as usize, TraceOutcome::NotCanonical);
+                continue;
+            }
+            let (parents, children) = self.sets.split_at_mut((depth + 1) * words);
+            let survivors = self.space.table.survivors(pair as usize);
+            let mut any_left = false;
+            for ((child, parent), mask) in children
+                .iter_mut()
+                .zip(&parents[depth * words..])
+                .zip(survivors)
+            {
+                *child = parent & mask;
+                any_left |= *child != 0;
+            }
+            // No universe is left, so the tries so far always turn on the toy; that is a
+            // solution with fewer tries, which shows up with a useful last try as well.
+            if !any_left {
+                self.report.fewer_tries_suffice = true;
+                self.skip(ranks, pair as usize, TraceOutcome::AlwaysOn);
+                continue;
+            }
+            self.steps.push(pair as usize);
+            let outer = std::mem::replace(&mut self.used, used);
+            self.descend(left - 1, pair, first);
+            self.used = outer;
+            self.steps.pop();
+        }
+    }
+
+    // Counts candidates that need no closer look as examined, where `pair` is the try that was
+    // about to be picked for them.
+    fn skip(&mut self, ranks: Range<u64>, pair: usize, outcome: TraceOutcome) {
+        self.poll();
+        if !self.stopped {
+            self.report.examined += ranks.end - ranks.start;
+            self.report.pruned += ranks.end - ranks.start;
+            if let Some(trace) = self.config.trace {
+                let words = self.space.initial.len();
+                let parent = &self.sets[self.steps.len() * words..][..words];
+                let survivors = self.space.table.survivors(pair);
+                let universes_left = parent
+                    .iter()
+                    .zip(survivors)
+                    .map(|(set, mask)| (set & mask).count_ones())
+                    .sum::<u32>();
+                let mut tries = self.steps.clone();
+                tries.push(pair);
+                trace(TraceEvent {
+                    ranks,
+                    tries,
+                    outcome,
+                    universes_left: u64::from(universes_left),
+                });
+            }
+        }
+    }
+
+    // Checking the clock is cheap, but not free compared to one candidate.
+    fn poll(&mut self) {
+        let Solver {
+            deadline,
+            heartbeat,
+            progress,
+            cancel,
+            first,
+            ..
+        } = self.config;
+        let quiet = heartbeat.is_none() && progress.is_none();
+        if self.report.examined < self.next_check
+            || (deadline.is_none() && quiet && cancel.is_none() && !first)
+        {
+            return;
+        }
+        self.next_check = self.report.examined + 1024;
+        if cancel.is_some_and(|c| c.load(Ordering::Relaxed))
+            || (first && self.found_first.load(Ordering::Relaxed))
+        {
+            self.stopped = true;
+            return;
+        }
+        (self.tick)(&self.report);
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            self.report.timed_out = true;
+            self.stopped = true;
+        }
+    }
+
+    // Looks at the universes left after all pairs of the candidate with this rank failed.
+    fn examine(&mut self, depth: usize, rank: u64) {
+        self.poll();
+        if self.stopped {
+            return;
+        }
+        self.report.examined += 1;
+        let words = self.space.initial.len();
+        let remaining = &self.sets[depth * words..][..words];
+
+        // The intersection only shrinks as more universes are added to it, so once it has fewer
+        // batteries than the toy needs the candidate is lost and the rest need not be looked at.
+        let slots = self.config.params.slots;
+        let mut guaranteed = None;
+        'universes: for (w, &set) in remaining.iter().enumerate() {
+            let mut set = set;
+            while set != 0 {
+                let universe = self.space.table.universes[128 * w + set.trailing_zeros() as usize];
+                let acc = guaranteed.map_or(universe, |acc| acc & universe);
+                guaranteed = Some(acc);
+                if u64::from(acc.len()) < slots {
+                    break 'universes;
+                }
+                set &= set - 1;
+            }
+        }
+        if let Some(trace) = self.config.trace {
+            let solved = guaranteed.is_some_and(|x| u64::from(x.len()) >= slots);
+            trace(TraceEvent {
+                ranks: rank..rank + 1,
+                tries: self.steps.clone(),
+                outcome: match solved {
+                    true => TraceOutcome::Solution,
+                    false => TraceOutcome::Failed,
+                },
+                universes_left: u64::from(
+                    remaining.iter().map(|set| set.count_ones()).sum::<u32>(),
+                ),
+            });
+        }
+        match guaranteed {
+            Some(x) if u64::from(x.len()) >= slots => {
+                let mut solution = vec![];
+                if let Some(anchor) = self.config.anchor {
+                    solution.push(anchor.into_iter().collect::<Vec<_>>());
+                }
+                for &pair in self.steps.iter().rev() {
+                    solution.push(self.space.table.pairs[pair].into_iter().collect::<Vec<_>>());
+                }
+                solution.push(x.into_iter().collect::<Vec<_>>());
+                self.report.found += 1;
+                (self.on_solution)(solution);
+                if self.config.first {
+                    self.found_first.store(true, Ordering::Relaxed);
+                    self.stopped = true;
+                }
+            }
+            // Subtrees without universes are skipped before getting here, so this is the anchor
+            // without free steps, which always turns on the toy by itself.
+            None => self.report.fewer_tries_suffice = true,
+            Some(_) => {
+                let remaining = remaining.iter().map(|set| set.count_ones()).sum::<u32>() as usize;
+                if self
+                    .report
+                    .fewest_remaining
+                    .is_none_or(|fewest| remaining < fewest)
+                {
+                    self.report.fewest_remaining = Some(remaining);
+                }
+            }
+        }
+    }
+}
+
+// The status record written for `Solver::heartbeat`.
+fn emit_heartbeat(report: &SearchReport, started: Instant, done: bool) {
+    let elapsed = started.elapsed().as_secs_f64();
+    let rate = if elapsed > 0.0 {
+        report.examined as f64 / elapsed
+    } else {
+        0.0
+    };
+    let rss = match resident_memory_bytes() {
+        Some(bytes) => bytes.to_string(),
+        None => "null".to_string(),
+    };
+    eprintln!(
+        "{{\"elapsed_s\":{elapsed:.3},\"examined\":{},\"total\":{},\"progress\":{:.6},\
+        \"rate_per_s\":{rate:.1},\"solutions\":{},\"rss_bytes\":{rss},\"done\":{done}}}",
+        report.examined,
+        report.total,
+        report.examined as f64 / report.total.max(1) as f64,
+        report.found,
+    );
+}
+
+// The line written for `Solver::progress`.
+fn emit_progress(report: &SearchReport, started: Instant, done: bool) {
+    let elapsed = started.elapsed();
+    let found = match report.found {
+        1 => "1 strategy".to_string(),
+        n => format!("{n} strategies"),
+    };
+    if done {
+        eprintln!(
+            "Examined {} of {} candidates in {}, {found} found.",
+            report.examined,
+            report.total,
+            format_duration(elapsed.as_secs_f64()),
+        );
+        return;
+    }
+    let fraction = report.examined as f64 / report.total.max(1) as f64;
+    // Assumes the rest of the candidates take as long as the ones so far, on average.
+    let left = match report.examined {
+        0 => "unknown".to_string(),
+        examined => {
+            let seconds = elapsed.as_secs_f64() * (report.total - examined) as f64;
+            format!("about {}", format_duration(seconds / examined as f64))
+        }
+    };
+    eprintln!(
+        "Examined {} of {} candidates ({:.1}%), {found} found, {left} left.",
+        report.examined,
+        report.total,
+        100.0 * fraction,
+    );
+}
+
+// E.g. 2h 05m, 3m 09s or 4.2s.
+fn format_duration(seconds: f64) -> String {
+    let whole = seconds.round() as u64;
+    match whole {
+        _ if seconds < 60.0 => format!("{seconds:.1}s"),
+        0..3600 => format!("{}m {:02}s", whole / 60, whole % 60),
+        _ => format!("{}h {:02}m", whole / 3600, whole / 60 % 60),
+    }
+}
+
+// Resident set size of this process, where the platform makes it cheap to find out.
+fn resident_memory_bytes() -> Option<u64> {
+    if cfg!(target_os = "wasi") {
+        return None;
+    }
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let resident_pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(resident_pages * 4096)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{solution_tries, validate, Rng};
+
+    // Fewest tries that always turn on the toy, and for every number of tries the sets of tries
+    // (as masks of try indices) that cover every universe, by going through all sets of tries.
+    struct BruteForce {
+        pairs: Vec<BitSet>,
+        universes: Vec<BitSet>,
+        turned_on_by: Vec<u64>,
+    }
+
+    impl BruteForce {
+        fn new(params: Params) -> Self {
+            let pairs: Vec<BitSet> = CombinationIter::new(params.total, params.slots).collect();
+            let universes: Vec<BitSet> = CombinationIter::new(params.total, params.good).collect();
+            let turned_on_by = universes
+                .iter()
+                .map(|&u| {
+                    (0..pairs.len())
+                        .filter(|&p| pairs[p].is_subset(u))
+                        .fold(0, |mask, p| mask | 1 << p)
+                })
+                .collect();
+            BruteForce {
+                pairs,
+                universes,
+                turned_on_by,
+            }
+        }
+
+        fn fewest_tries(&self) -> u64 {
+            (0..1u64 << self.pairs.len())
+                .filter(|&tries| self.turned_on_by.iter().all(|&on| on & tries != 0))
+                .map(|tries| u64::from(tries.count_ones()))
+                .min()
+                .expect("all tries together always turn on the toy")
+        }
+
+        // Candidates the solver should report: sets of `steps` distinct tries that leave some
+        // universes, all sharing `slots` functional batteries.
+        fn solutions(&self, params: Params, steps: u64, anchor: Option<usize>) -> u64 {
+            (0..1u64 << self.pairs.len())
+                .filter(|&tries| u64::from(tries.count_ones()) == steps)
+                .filter(|&tries| anchor.is_none_or(|a| tries & 1 << a == 0))
+                .filter(|&tries| {
+                    let tries = tries | anchor.map_or(0, |a| 1 << a);
+                    let left = self
+                        .universes
+                        .iter()
+                        .zip(&self.turned_on_by)
+                        .filter(|&(_, &on)| on & tries == 0)
+                        .map(|(&u, _)| u);
+                    let shared = left.reduce(|a, b| a & b);
+                    shared.is_some_and(|s| u64::from(s.len()) >= params.slots)
+                })
+                .count() as u64
+        }
+    }
+
+    #[test]
+    fn random_puzzles_match_brute_force() {
+        let mut rng = Rng::new(305);
+        let mut checked = 0;
+        while checked < 40 {
+            let total = 2 + rng.below(6);
+            let good = 1 + rng.below(total);
+            let slots = 1 + rng.below(good);
+            let mut params = Params {
+                total,
+                good,
+                slots,
+                tries: 0,
+            };
+            let brute_force = BruteForce::new(params);
+            if brute_force.pairs.len() > 16 {
+                continue;
+            }
+            let fewest = brute_force.fewest_tries();
+            if fewest < 3 {
+                continue;
+            }
+            checked += 1;
+
+            params.tries = fewest;
+            let mut found = vec![];
+            let report = Solver {
+                params,
+                ..Solver::default()
+            }
+            .search(|s| found.push(s));
+            assert_eq!(report.found, found.len() as u64);
+            assert_eq!(
+                report.found,
+                brute_force.solutions(params, fewest - 1, None),
+                "{total} {good} {slots} {fewest}"
+            );
+            for solution in &found {
+                assert_eq!(
+                    validate::is_valid_strategy(params, &solution_tries(solution, slots)),
+                    Ok(())
+                );
+            }
+
+            let anchor = BitSet((1 << slots) - 1);
+            let anchored = Solver {
+                params,
+                anchor: Some(anchor),
+                threads: NonZeroUsize::new(2),
+                ..Solver::default()
+            };
+            let in_parallel = anchored.search(|_| {}).found;
+            assert_eq!(
+                in_parallel,
+                brute_force.solutions(params, fewest - 2, Some(rank(anchor) as usize))
+            );
+            assert_eq!(anchored.solutions().count() as u64, in_parallel);
+            let canonical = Solver {
+                break_symmetry: true,
+                ..anchored
+            };
+            assert!(canonical.search(|_| {}).found > 0);
+
+            params.tries = fewest - 1;
+            let report = Solver {
+                params,
+                ..Solver::default()
+            }
+            .search(|_| {});
+            assert_eq!(report.outcome(), SearchOutcome::NoneExist);
+        }
+    }
+
+    #[test]
+    fn solutions_verify_when_more_batteries_are_guaranteed_than_tried() {
+        let mut more = 0;
+        for (total, good, slots, tries) in [(5, 4, 2, 3), (6, 5, 2, 3), (7, 6, 3, 3), (8, 4, 2, 7)]
+        {
+            let params = Params {
+                total,
+                good,
+                slots,
+                tries,
+            };
+            let mut found = vec![];
+            Solver {
+                params,
+                ..Solver::default()
+            }
+            .search(|s| found.push(s));
+            assert!(!found.is_empty(), "{params:?}");
+            for solution in &found {
+                more += usize::from(solution.last().unwrap().len() > slots as usize);
+                let tries = solution_tries(solution, slots);
+                assert!(tries.iter().all(|t| u64::from(t.len()) == slots));
+                assert_eq!(validate::is_valid_strategy(params, &tries), Ok(()));
+            }
+        }
+        assert!(more > 0);
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_search() {
+        let solver = |params: Params, anchor: Option<BitSet>| Solver {
+            params,
+            anchor,
+            ..Solver::default()
+        };
+        let params = Params::default();
+        assert_eq!(solver(params, None).check(), Ok(()));
+        assert_eq!(solver(params, Some(BitSet(0b11))).check(), Ok(()));
+
+        let tries = |tries| Params { tries, ..params };
+        assert_eq!(solver(tries(2), Some(BitSet(0b11))).check(), Ok(()));
+        assert_eq!(
+            solver(tries(1), Some(BitSet(0b11)))
+                .try_search(|_| {})
+                .err(),
+            Some(SolverError::TooFewTries {
+                tries: 1,
+                fewest: 2
+            })
+        );
+        assert_eq!(solver(tries(1), None).check(), Ok(()));
+        assert_eq!(
+            solver(tries(30), None).check(),
+            Err(SolverError::TooManyTries {
+                tries: 30,
+                most: 29
+            })
+        );
+        assert_eq!(
+            solver(tries(30), Some(BitSet(0b11))).check(),
+            Err(SolverError::TooManyTries {
+                tries: 30,
+                most: 29
+            })
+        );
+        for anchor in [0b111, 0b1_0000_0001] {
+            assert_eq!(
+                solver(params, Some(BitSet(anchor))).check(),
+                Err(SolverError::InvalidAnchor(BitSet(anchor)))
+            );
+        }
+        let wrong = Params { slots: 5, ..params };
+        assert_eq!(
+            solver(wrong, None).check(),
+            Err(SolverError::InvalidParams(wrong))
+        );
+        let wide = Params {
+            total: 17,
+            good: 8,
+            ..params
+        };
+        assert_eq!(
+            solver(wide, None).check(),
+            Err(SolverError::TooManyPairs { pairs: 136 })
+        );
+        let long = Params {
+            total: 16,
+            good: 8,
+            tries: 60,
+            ..params
+        };
+        assert_eq!(
+            solver(long, None).check(),
+            Err(SolverError::TooManyCandidates)
+        );
+        let shard = Shard { index: 3, count: 2 };
+        assert_eq!(
+            Solver {
+                shard: Some(shard),
+                ..Solver::default()
+            }
+            .check(),
+            Err(SolverError::InvalidShard(shard))
+        );
+        let table = EliminationTable::new(wide_table_params());
+        assert_eq!(
+            Solver {
+                table: Some(&table),
+                ..Solver::default()
+            }
+            .check(),
+            Err(SolverError::WrongTable)
+        );
+    }
+
+    #[test]
+    fn examines_each_candidate_like_the_search() {
+        for break_symmetry in [false, true] {
+            let solver = Solver {
+                params: Params {
+                    total: 6,
+                    good: 3,
+                    tries: 5,
+                    ..Params::default()
+                },
+                anchor: Some(BitSet(0b11)),
+                break_symmetry,
+                ..Solver::default()
+            };
+            let events = Mutex::new(vec![]);
+            let record = |event: TraceEvent| events.lock().unwrap().push(event);
+            let report = Solver {
+                trace: Some(&record),
+                ..solver
+            }
+            .search(|_| {});
+            let events = events.into_inner().unwrap();
+            for rank in 0..report.total {
+                let event = events.iter().find(|e| e.ranks.contains(&rank)).unwrap();
+                let expected = TraceEvent {
+                    ranks: rank..rank + 1,
+                    ..event.clone()
+                };
+                assert_eq!(solver.examine(rank), Some(expected));
+            }
+            assert_eq!(solver.examine(report.total), None);
+        }
+    }
+
+    #[test]
+    fn threads_report_solutions_in_order_as_they_go() {
+        let solver = Solver::default();
+        let mut all = vec![];
+        solver.search(|s| all.push(s));
+        // Stopping after a few solutions gets the first ones of the single-threaded order, and
+        // the other threads stop soon after instead of searching the rest.
+        let stop = AtomicBool::new(false);
+        let mut first = vec![];
+        let report = Solver {
+            threads: NonZeroUsize::new(4),
+            cancel: Some(&stop),
+            ..solver
+        }
+        .search(|s| {
+            if first.len() < 5 {
+                first.push(s);
+            }
+            stop.store(first.len() == 5, Ordering::Relaxed);
+        });
+        assert_eq!(first, all[..5]);
+        assert!(report.examined < report.total);
+    }
+
+    #[test]
+    fn min_tries_agree_with_the_frontier() {
+        let frontier = |params: Params| {
+            let pairs = binomial(params.total, params.slots);
+            let (lowest, highest) = fewest_tries(params, (1, pairs), None, None);
+            assert_eq!(lowest, highest, "{params:?}");
+            lowest
+        };
+        // Once the first try fails, the 3 batteries it left out are functional.
+        let five = Params {
+            total: 5,
+            good: 4,
+            slots: 2,
+            tries: 0,
+        };
+        assert_eq!(frontier(five), 2);
+
+        let mut checked = 0;
+        for total in 2..=6 {
+            for good in 1..total {
+                for slots in 1..=good {
+                    let pairs = binomial(total, slots);
+                    if pairs > 15 {
+                        continue;
+                    }
+                    let params = Params {
+                        total,
+                        good,
+                        slots,
+                        tries: 0,
+                    };
+                    let fewest = frontier(params);
+                    let solver = Solver {
+                        params: Params {
+                            tries: pairs.min(fewest + 2),
+                            ..params
+                        },
+                        anchor: Some(BitSet((1 << slots) - 1)),
+                        ..Solver::default()
+                    };
+                    let mut last = None;
+                    let found = solver.min_tries(|config, first, _| {
+                        if let Some(solution) = first {
+                            let tries = solution_tries(solution, slots);
+                            assert_eq!(validate::is_valid_strategy(config.params, &tries), Ok(()));
+                        }
+                        last = Some((config.params.tries, first.is_some()));
+                    });
+                    assert_eq!(found, Some(fewest), "{params:?}");
+                    assert_eq!(last, Some((fewest - 1, false)), "{params:?}");
+                    checked += 1;
+                }
+            }
+        }
+        assert!(checked > 20, "only {checked} puzzles were checked");
+    }
+
+    fn wide_table_params() -> Params {
+        Params {
+            total: 9,
+            ..Params::default()
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! The binary format of the traces that [`Solver::trace`](crate::Solver::trace) events are
+//! recorded in, so that a search can be replayed and analyzed later. A trace starts with
+//! [`MAGIC`], the [`VERSION`], the puzzle parameters and the anchor (0, or 1 and its bits). Each
+//! record that follows is the code of its outcome in [`OUTCOMES`], the first rank, the number of
+//! candidates, the number of universes left and the tries, as their count followed by their
+//! indices. All numbers but the outcome are LEB128 varints, so most records take a few bytes.
+//!
+//! ```
+//! use batteries::trace::{read_trace, write_event, write_header};
+//! use batteries::{Params, TraceEvent, TraceOutcome};
+//!
+//! let event = TraceEvent {
+//!     ranks: 0..1,
+//!     tries: vec![2, 1],
+//!     outcome: TraceOutcome::Failed,
+//!     universes_left: 30,
+//! };
+//! let mut trace = vec![];
+//! write_header(&mut trace, Params::default(), None);
+//! write_event(&mut trace, &event);
+//! assert_eq!(read_trace(&trace), Some((Params::default(), None, vec![event])));
+//! ```
+
+use crate::{BitSet, Params, TraceEvent, TraceOutcome};
+
+/// Starts every trace, followed by the version of the format.
+pub const MAGIC: &[u8] = b"BATTRACE";
+pub const VERSION: u8 = 1;
+
+/// The outcomes of trace records by their code.
+pub const OUTCOMES: [TraceOutcome; 5] = [
+    TraceOutcome::RepeatsAnchor,
+    TraceOutcome::NotCanonical,
+    TraceOutcome::AlwaysOn,
+    TraceOutcome::Solution,
+    TraceOutcome::Failed,
+];
+
+/// Appends the start of a trace of the search for the puzzle with `params` and the first try
+/// `anchor` to `out`.
+pub fn write_header(out: &mut Vec<u8>, params: Params, anchor: Option<BitSet>) {
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    for n in [params.total, params.good, params.slots, params.tries] {
+        write_varint(out, n);
+    }
+    match anchor {
+        None => write_varint(out, 0),
+        Some(anchor) => {
+            write_varint(out, 1);
+            write_varint(out, anchor.0);
+        }
+    }
+}
+
+/// Appends the record of `event` to `out`.
+pub fn write_event(out: &mut Vec<u8>, event: &TraceEvent) {
+    let code = OUTCOMES.iter().position(|&o| o == event.outcome);
+    out.push(code.expect("every outcome has a code") as u8);
+    write_varint(out, event.ranks.start);
+    write_varint(out, event.ranks.end - event.ranks.start);
+    write_varint(out, event.universes_left);
+    write_varint(out, event.tries.len() as u64);
+    for &index in &event.tries {
+        write_varint(out, index as u64);
+    }
+}
+
+/// Reads a trace written with [`write_header`] and [`write_event`]: the parameters of the
+/// puzzle, the anchor and the records. None if `bytes` are not a complete trace of this version.
+pub fn read_trace(bytes: &[u8]) -> Option<(Params, Option<BitSet>, Vec<TraceEvent>)> {
+    let mut bytes = bytes.strip_prefix(MAGIC)?.strip_prefix(&[VERSION])?;
+    let mut number = || read_varint(&mut bytes);
+    let params = Params {
+        total: number()?,
+        good: number()?,
+        slots: number()?,
+        tries: number()?,
+    };
+    let anchor = match number()? {
+        0 => None,
+        _ => Some(BitSet(number()?)),
+    };
+    let mut events = vec![];
+    while let Some((&code, rest)) = bytes.split_first() {
+        bytes = rest;
+        let mut number = || read_varint(&mut bytes);
+        let outcome = *OUTCOMES.get(code as usize)?;
+        let start = number()?;
+        let ranks = start..start.checked_add(number()?)?;
+        let universes_left = number()?;
+        let tries = (0..number()?)
+            .map(|_| number().map(|index| index as usize))
+            .collect::<Option<_>>()?;
+        events.push(TraceEvent {
+            ranks,
+            tries,
+            outcome,
+            universes_left,
+        });
+    }
+    Some((params, anchor, events))
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+// Reads a varint from the start of `bytes` and moves past it.
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        n |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Solver;
+    use std::sync::Mutex;
+
+    #[test]
+    fn reads_back_what_a_search_traced() {
+        let params = Params::default();
+        let anchor = Some(BitSet(0b11));
+        let events = Mutex::new(vec![]);
+        let record = |event: TraceEvent| events.lock().unwrap().push(event);
+        Solver {
+            params,
+            anchor,
+            trace: Some(&record),
+            ..Solver::default()
+        }
+        .search(|_| {});
+        let events = events.into_inner().unwrap();
+        let mut trace = vec![];
+        write_header(&mut trace, params, anchor);
+        for event in &events {
+            write_event(&mut trace, event);
+        }
+        assert_eq!(read_trace(&trace), Some((params, anchor, events)));
+        // Cut off in the middle of the last record.
+        assert_eq!(read_trace(&trace[..trace.len() - 1]), None);
+    }
+
+    #[test]
+    fn writes_numbers_in_as_few_bytes_as_they_need() {
+        for (n, bytes) in [(0, 1), (127, 1), (128, 2), (300, 2), (u64::MAX, 10)] {
+            let mut out = vec![];
+            write_varint(&mut out, n);
+            assert_eq!(out.len(), bytes);
+            assert_eq!(read_varint(&mut &out[..]), Some(n));
+        }
+        assert_eq!(read_varint(&mut &[0x80u8; 10][..]), None);
+    }
+}
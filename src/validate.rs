@@ -0,0 +1,257 @@
+//! The invariants of the puzzle as checks that can be run on strategies and search states from
+//! anywhere, e.g. strategies read from a file or found by another program, or the universes a
+//! [`Game`](crate::Game) still considers possible.
+//!
+//! ```
+//! use batteries::validate::{is_valid_strategy, universe_consistency_check, Violation};
+//! use batteries::{BitSet, Game, Params};
+//!
+//! let params = Params::default();
+//! let tries = [0b11, 0b1100, 0b10100, 0b11000, 0b100001, 0b100010, 0b11000000].map(BitSet);
+//! assert_eq!(is_valid_strategy(params, &tries), Ok(()));
+//! assert!(matches!(
+//!     is_valid_strategy(params, &tries[..6]),
+//!     Err(Violation::Uncovered { count: 9, .. })
+//! ));
+//!
+//! let mut game = Game::new(params);
+//! game.record_failure(tries[0]);
+//! assert_eq!(universe_consistency_check(params, &tries[..1], game.universes()), Ok(()));
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{BitSet, CombinationIter, Params};
+
+/// How a strategy or a set of universes breaks the rules of the puzzle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The strategy has more tries than the puzzle allows.
+    TooManyTries { tries: usize, allowed: u64 },
+    /// The try with this index holds a battery that is not among the puzzle's batteries.
+    NoSuchBattery { index: usize, battery: usize },
+    /// The try with this index does not hold as many batteries as the toy takes.
+    WrongSize { index: usize, size: u32 },
+    /// None of the tries turns on the toy in `count` of the universes, `universe` among them.
+    Uncovered { universe: BitSet, count: u64 },
+    /// A set of batteries that does not have as many functional ones as a universe of the puzzle.
+    NotAUniverse { universe: BitSet },
+    /// A universe that is listed more than once.
+    Duplicate { universe: BitSet },
+    /// A universe in which the failed try with this index would have turned on the toy.
+    RuledOut { universe: BitSet, index: usize },
+    /// A universe that the failed tries do not rule out but that is not listed.
+    Missing { universe: BitSet },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::TooManyTries { tries, allowed } => {
+                write!(f, "{tries} tries are more than the {allowed} allowed")
+            }
+            Violation::NoSuchBattery { index, battery } => {
+                write!(
+                    f,
+                    "try {} holds battery {battery}, which does not exist",
+                    index + 1
+                )
+            }
+            Violation::WrongSize { index, size } => {
+                write!(f, "try {} holds {size} batteries", index + 1)
+            }
+            Violation::Uncovered { universe, count } => write!(
+                f,
+                "no try turns on the toy in {count} universes, e.g. when {universe:#} are \
+                functional"
+            ),
+            Violation::NotAUniverse { universe } => {
+                write!(f, "{universe:#} is not a universe of the puzzle")
+            }
+            Violation::Duplicate { universe } => write!(f, "{universe:#} is listed twice"),
+            Violation::RuledOut { universe, index } => write!(
+                f,
+                "{universe:#} is ruled out by the failed try {}",
+                index + 1
+            ),
+            Violation::Missing { universe } => write!(
+                f,
+                "{universe:#} is still possible after the failed tries but not listed"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Violation {}
+
+/// Checks that `tries` are a solution of the puzzle with `params`: there are at most
+/// `params.tries` of them, each holds `params.slots` of the batteries, and in every universe one
+/// of them turns on the toy.
+pub fn is_valid_strategy(params: Params, tries: &[BitSet]) -> Result<(), Violation> {
+    if tries.len() as u64 > params.tries {
+        return Err(Violation::TooManyTries {
+            tries: tries.len(),
+            allowed: params.tries,
+        });
+    }
+    for (index, &pair) in tries.iter().enumerate() {
+        if let Some(battery) = pair.into_iter().find(|&b| b as u64 >= params.total) {
+            return Err(Violation::NoSuchBattery { index, battery });
+        }
+        if u64::from(pair.len()) != params.slots {
+            return Err(Violation::WrongSize {
+                index,
+                size: pair.len(),
+            });
+        }
+    }
+    let mut uncovered = CombinationIter::new(params.total, params.good)
+        .filter(|&universe| !tries.iter().any(|&pair| pair.is_subset(universe)));
+    match uncovered.next() {
+        None => Ok(()),
+        Some(universe) => Err(Violation::Uncovered {
+            universe,
+            count: 1 + uncovered.count() as u64,
+        }),
+    }
+}
+
+/// Checks that `universes` are exactly the universes of the puzzle with `params` that the
+/// `failed` tries did not rule out, in any order: each is a set of `params.good` of the
+/// batteries, none of them contains a failed try, none is listed twice and none is missing.
+pub fn universe_consistency_check(
+    params: Params,
+    failed: &[BitSet],
+    universes: &[BitSet],
+) -> Result<(), Violation> {
+    let mut sorted: Vec<BitSet> = universes.to_vec();
+    sorted.sort_unstable();
+    for (i, &universe) in sorted.iter().enumerate() {
+        let in_range = universe.into_iter().all(|b| (b as u64) < params.total);
+        if !in_range || u64::from(universe.len()) != params.good {
+            return Err(Violation::NotAUniverse { universe });
+        }
+        if i > 0 && sorted[i - 1] == universe {
+            return Err(Violation::Duplicate { universe });
+        }
+        if let Some(index) = failed.iter().position(|&pair| pair.is_subset(universe)) {
+            return Err(Violation::RuledOut { universe, index });
+        }
+    }
+    for universe in CombinationIter::new(params.total, params.good) {
+        let possible = !failed.iter().any(|&pair| pair.is_subset(universe));
+        if possible && sorted.binary_search(&universe).is_err() {
+            return Err(Violation::Missing { universe });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Game;
+
+    fn original_strategy() -> [BitSet; 7] {
+        [
+            0b11, 0b1100, 0b10100, 0b11000, 0b100001, 0b100010, 0b11000000,
+        ]
+        .map(BitSet)
+    }
+
+    #[test]
+    fn accepts_a_solution_and_names_an_uncovered_universe_otherwise() {
+        let params = Params::default();
+        let tries = original_strategy();
+        assert_eq!(is_valid_strategy(params, &tries), Ok(()));
+        // Without the last try the toy stays off whenever 6 and 7 work together with one
+        // battery of each triangle 0-1-5 and 2-3-4.
+        assert_eq!(
+            is_valid_strategy(params, &tries[..6]),
+            Err(Violation::Uncovered {
+                universe: BitSet(0b1100_0101),
+                count: 9,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_strategies() {
+        let params = Params::default();
+        let mut tries = original_strategy().to_vec();
+        tries.push(BitSet(0b101));
+        assert_eq!(
+            is_valid_strategy(params, &tries),
+            Err(Violation::TooManyTries {
+                tries: 8,
+                allowed: 7
+            })
+        );
+        let tries = [BitSet(0b11), BitSet(0b1_0000_0001)];
+        assert_eq!(
+            is_valid_strategy(params, &tries),
+            Err(Violation::NoSuchBattery {
+                index: 1,
+                battery: 8
+            })
+        );
+        let tries = [BitSet(0b11), BitSet(0b1110)];
+        assert_eq!(
+            is_valid_strategy(params, &tries),
+            Err(Violation::WrongSize { index: 1, size: 3 })
+        );
+    }
+
+    #[test]
+    fn checks_the_universes_left_after_failed_tries() {
+        let params = Params::default();
+        let failed = &original_strategy()[..2];
+        let mut game = Game::new(params);
+        for &pair in failed {
+            game.record_failure(pair);
+        }
+        let universes = game.universes().to_vec();
+        assert_eq!(
+            universe_consistency_check(params, failed, &universes),
+            Ok(())
+        );
+
+        let missing = universes[3];
+        let fewer: Vec<BitSet> = universes
+            .iter()
+            .copied()
+            .filter(|&u| u != missing)
+            .collect();
+        assert_eq!(
+            universe_consistency_check(params, failed, &fewer),
+            Err(Violation::Missing { universe: missing })
+        );
+
+        let mut twice = universes.clone();
+        twice.push(missing);
+        assert_eq!(
+            universe_consistency_check(params, failed, &twice),
+            Err(Violation::Duplicate { universe: missing })
+        );
+
+        let mut ruled_out = universes.clone();
+        ruled_out.push(BitSet(0b1111));
+        assert_eq!(
+            universe_consistency_check(params, failed, &ruled_out),
+            Err(Violation::RuledOut {
+                universe: BitSet(0b1111),
+                index: 0
+            })
+        );
+
+        let mut too_small = universes;
+        too_small.push(BitSet(0b111));
+        assert_eq!(
+            universe_consistency_check(params, failed, &too_small),
+            Err(Violation::NotAUniverse {
+                universe: BitSet(0b111)
+            })
+        );
+    }
+}